@@ -0,0 +1,22 @@
+#version 450 core
+
+in VS_OUT_PS_IN {
+  vec2 uv;
+} ps_in;
+
+layout (location = 0) out vec4 FinalFragColor;
+layout (binding = 0) uniform sampler2D Scene;
+layout (location = 0) uniform float Threshold;
+// the contribution ramps in over [Threshold - Knee, Threshold + Knee]
+layout (location = 1) uniform float Knee;
+
+void main() {
+  vec3 color = texture(Scene, ps_in.uv).rgb;
+  float brightness = max(color.r, max(color.g, color.b));
+
+  float soft = clamp(brightness - Threshold + Knee, 0.0, 2.0 * Knee);
+  soft = soft * soft / (4.0 * Knee + 1.0e-4);
+  float contribution = max(soft, brightness - Threshold) / max(brightness, 1.0e-4);
+
+  FinalFragColor = vec4(color * contribution, 1.0);
+}
\ No newline at end of file
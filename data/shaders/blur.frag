@@ -0,0 +1,23 @@
+#version 450 core
+
+in VS_OUT_PS_IN {
+  vec2 uv;
+} ps_in;
+
+layout (location = 0) out vec4 FinalFragColor;
+layout (binding = 0) uniform sampler2D Source;
+// offset between two taps, one texel along x or y
+layout (location = 0) uniform vec2 Direction;
+// center tap first, the others are used on both sides
+layout (location = 1) uniform float Weights[5];
+
+void main() {
+  vec3 sum = texture(Source, ps_in.uv).rgb * Weights[0];
+  for (int i = 1; i < 5; ++i) {
+    vec2 offset = Direction * float(i);
+    sum += texture(Source, ps_in.uv + offset).rgb * Weights[i];
+    sum += texture(Source, ps_in.uv - offset).rgb * Weights[i];
+  }
+
+  FinalFragColor = vec4(sum, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,52 @@
+#version 450 core
+
+in VS_OUT_PS_IN {
+  vec2 uv;
+} ps_in;
+
+layout (location = 0) out vec4 FinalFragColor;
+layout (binding = 0) uniform sampler2D Scene;
+layout (binding = 1) uniform sampler2D Bloom;
+// 256 colors, indexed by luminance
+layout (binding = 2) uniform sampler2D Palette;
+// 0 disables the bloom
+layout (location = 0) uniform float BloomIntensity;
+// 0 disables the color grading
+layout (location = 1) uniform float GradingStrength;
+layout (location = 2) uniform float Exposure;
+// 0 clamps, 1 is Reinhard, 2 the ACES filmic curve
+layout (location = 3) uniform int ToneMapper;
+
+vec3 aces_filmic(vec3 x) {
+  // Narkowicz fit of the ACES reference rendering transform
+  const float a = 2.51;
+  const float b = 0.03;
+  const float c = 2.43;
+  const float d = 0.59;
+  const float e = 0.14;
+  return clamp((x * (a * x + b)) / (x * (c * x + d) + e), 0.0, 1.0);
+}
+
+void main() {
+  vec3 color = texture(Scene, ps_in.uv).rgb;
+  if (BloomIntensity > 0.0) {
+    color += texture(Bloom, ps_in.uv).rgb * BloomIntensity;
+  }
+
+  color *= Exposure;
+  if (ToneMapper == 1) {
+    color = color / (1.0 + color);
+  } else if (ToneMapper == 2) {
+    color = aces_filmic(color);
+  } else {
+    color = clamp(color, 0.0, 1.0);
+  }
+
+  if (GradingStrength > 0.0) {
+    float luma = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    vec3 graded = texture(Palette, vec2((luma * 255.0 + 0.5) / 256.0, 0.5)).rgb;
+    color = mix(color, graded, GradingStrength);
+  }
+
+  FinalFragColor = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,11 @@
+#version 450 core
+
+in VS_OUT_PS_IN {
+  vec4 color;
+} ps_in;
+
+layout (location = 0) out vec4 FinalFragColor;
+
+void main() {
+  FinalFragColor = ps_in.color;
+}
\ No newline at end of file
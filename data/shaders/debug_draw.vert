@@ -0,0 +1,19 @@
+#version 450 core
+
+layout (location = 0) in vec2 VsInPos;
+layout (location = 1) in vec4 VsInColor;
+
+layout (location = 0) uniform mat4 ViewProjection;
+
+out gl_PerVertex {
+  vec4 gl_Position;
+};
+
+out VS_OUT_PS_IN {
+  vec4 color;
+} vs_out;
+
+void main() {
+  gl_Position = ViewProjection * vec4(VsInPos, 0.0, 1.0);
+  vs_out.color = VsInColor;
+}
\ No newline at end of file
@@ -0,0 +1,6 @@
+// Constants shared by all the draws of a frame, see FrameConstants in rendering.
+layout (binding = 0, std140) uniform FrameConstants {
+  mat4 Projection;
+  vec2 WorldSize;
+  float Time;
+} Frame;
\ No newline at end of file
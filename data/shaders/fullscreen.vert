@@ -0,0 +1,16 @@
+#version 450 core
+
+out gl_PerVertex {
+  vec4 gl_Position;
+};
+
+out VS_OUT_PS_IN {
+  vec2 uv;
+} vs_out;
+
+// a single triangle covering the screen, generated from the vertex index
+void main() {
+  vec2 pos = vec2(float((gl_VertexID << 1) & 2), float(gl_VertexID & 2));
+  vs_out.uv = pos;
+  gl_Position = vec4(pos * 2.0 - 1.0, 0.0, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+// One particle, as written by ParticlesSim::update. Transforms are in world space.
+struct ParticleInstance {
+  mat4 transform;
+  vec4 color;
+  uint texid;
+  uint texid_next;
+  float frame_blend;
+};
\ No newline at end of file
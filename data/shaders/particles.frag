@@ -2,12 +2,28 @@
 
 in VS_OUT_PS_IN {
   flat uint texid;
+  flat uint texid_next;
+  flat float frame_blend;
   vec2 uv;
+  vec4 color;
 } ps_in;
 
 layout (location = 0) out vec4 FinalFragColor;
 layout (binding = 0) uniform sampler2DArray Sprites;
+// texels with an alpha below this value are discarded, BlendMode::OPAQUE_ALPHA_CUTOFF for
+// opaque batches and 0 for the others
+layout (location = 0) uniform float AlphaCutoff;
 
 void main() {
-  FinalFragColor = texture(Sprites, vec3(ps_in.uv, float(ps_in.texid)));
+  vec4 texel = texture(Sprites, vec3(ps_in.uv, float(ps_in.texid)));
+  if (ps_in.frame_blend > 0.0) {
+    vec4 next_texel = texture(Sprites, vec3(ps_in.uv, float(ps_in.texid_next)));
+    texel = mix(texel, next_texel, ps_in.frame_blend);
+  }
+  if (texel.a < AlphaCutoff) {
+    discard;
+  }
+
+  // sprites are stored with straight alpha, the instance color is premultiplied
+  FinalFragColor = vec4(texel.rgb * texel.a, texel.a) * ps_in.color;
 }
\ No newline at end of file
@@ -3,28 +3,35 @@
 layout (location = 0) in vec2 VsInPos;
 layout (location = 1) in vec2 VsInUV;
 
-struct ParticleInstance {
-  mat4 transform;
-  uint texid;
-};
+#include "particle_instance.glsl"
+#include "frame_constants.glsl"
 
 layout (binding = 0, std430) readonly buffer InstanceData {
   ParticleInstance particles[];
 } Instances;
 
+// index of the first instance of the batch being drawn
+layout (location = 0) uniform uint InstanceOffset;
+
 out gl_PerVertex {
   vec4 gl_Position;
 };
 
 out VS_OUT_PS_IN {
   flat uint texid;
+  flat uint texid_next;
+  flat float frame_blend;
   vec2 uv;
+  vec4 color;
 } vs_out;
 
 void main() {
-  ParticleInstance pi = Instances.particles[gl_InstanceID];
-  gl_Position = pi.transform * vec4(VsInPos, 0.0, 1.0);
+  ParticleInstance pi = Instances.particles[InstanceOffset + gl_InstanceID];
+  gl_Position = Frame.Projection * pi.transform * vec4(VsInPos, 0.0, 1.0);
 
   vs_out.texid = pi.texid;
+  vs_out.texid_next = pi.texid_next;
+  vs_out.frame_blend = pi.frame_blend;
   vs_out.uv = VsInUV;
+  vs_out.color = pi.color;
 }
\ No newline at end of file
@@ -0,0 +1,17 @@
+#version 450 core
+
+in VS_OUT_PS_IN {
+  vec2 uv;
+} ps_in;
+
+layout (location = 0) out vec4 FinalFragColor;
+layout (binding = 0) uniform sampler2D Scene;
+layout (binding = 1) uniform sampler2D History;
+// fraction of the accumulated image kept from one frame to the next
+layout (location = 0) uniform float Persistence;
+
+void main() {
+  vec4 current = texture(Scene, ps_in.uv);
+  vec4 history = texture(History, ps_in.uv) * Persistence;
+  FinalFragColor = max(current, history);
+}
\ No newline at end of file
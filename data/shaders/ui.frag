@@ -0,0 +1,15 @@
+#version 450 core
+
+in VS_OUT_PS_IN {
+  vec2 uv;
+  vec4 color;
+} ps_in;
+
+layout (location = 0) out vec4 FinalFragColor;
+// single channel coverage texture
+layout (binding = 0) uniform sampler2D FontAtlas;
+
+void main() {
+  float coverage = texture(FontAtlas, ps_in.uv).r;
+  FinalFragColor = vec4(ps_in.color.rgb, ps_in.color.a * coverage);
+}
\ No newline at end of file
@@ -0,0 +1,23 @@
+#version 450 core
+
+layout (location = 0) in vec2 VsInPos;
+layout (location = 1) in vec2 VsInUV;
+layout (location = 2) in vec4 VsInColor;
+
+// screen pixels (origin top left) to clip space
+layout (location = 0) uniform mat4 Projection;
+
+out gl_PerVertex {
+  vec4 gl_Position;
+};
+
+out VS_OUT_PS_IN {
+  vec2 uv;
+  vec4 color;
+} vs_out;
+
+void main() {
+  gl_Position = Projection * vec4(VsInPos, 0.0, 1.0);
+  vs_out.uv = VsInUV;
+  vs_out.color = VsInColor;
+}
\ No newline at end of file
@@ -0,0 +1,237 @@
+use crate::{
+    colors::{HslColor, RGBAColor, RGBAColorF32, TURBO_SRGB_FLOATS},
+    utility::saturate,
+};
+
+/// Color space used to blend between two neighbouring stops of a gradient.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum GradientInterpolation {
+    Rgb,
+    /// Hue is blended along the shortest arc of the color wheel.
+    Hsl,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GradientStop {
+    /// Position of the stop, in the [0, 1] range.
+    pub pos: f32,
+    pub color: RGBAColorF32,
+}
+
+/// Piecewise linear color gradient, sampled with a parameter in the [0, 1] range.
+#[derive(Clone, Debug)]
+pub struct ColorGradient {
+    stops: Vec<GradientStop>,
+    interpolation: GradientInterpolation,
+}
+
+impl ColorGradient {
+    pub fn new(interpolation: GradientInterpolation) -> Self {
+        Self {
+            stops: Vec::new(),
+            interpolation,
+        }
+    }
+
+    pub fn constant(c: RGBAColorF32) -> Self {
+        let mut g = Self::new(GradientInterpolation::Rgb);
+        g.add_stop(0f32, c);
+        g
+    }
+
+    pub fn two_color(start: RGBAColorF32, end: RGBAColorF32) -> Self {
+        let mut g = Self::new(GradientInterpolation::Rgb);
+        g.add_stop(0f32, start).add_stop(1f32, end);
+        g
+    }
+
+    /// Builds a gradient with the palette entries evenly spaced over [0, 1].
+    /// Works with any of the `ColorMap` palettes.
+    pub fn from_palette(palette: &[RGBAColor]) -> Self {
+        let mut g = Self::new(GradientInterpolation::Rgb);
+        let last = (palette.len().max(2) - 1) as f32;
+
+        palette.iter().enumerate().for_each(|(idx, clr)| {
+            g.add_stop(idx as f32 / last, RGBAColorF32::from(*clr));
+        });
+
+        g
+    }
+
+    /// The Turbo colormap (dark blue -> green -> yellow -> dark red).
+    pub fn turbo() -> Self {
+        let mut g = Self::new(GradientInterpolation::Rgb);
+        let last = (TURBO_SRGB_FLOATS.len() - 1) as f32;
+
+        TURBO_SRGB_FLOATS.iter().enumerate().for_each(|(idx, clr)| {
+            g.add_stop(idx as f32 / last, RGBAColorF32::new(clr[0], clr[1], clr[2]));
+        });
+
+        g
+    }
+
+    pub fn with_interpolation(mut self, interpolation: GradientInterpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Adds a stop, keeping the stops sorted by position.
+    pub fn add_stop(&mut self, pos: f32, color: RGBAColorF32) -> &mut Self {
+        let pos = saturate(pos);
+        let idx = self
+            .stops
+            .iter()
+            .position(|s| s.pos > pos)
+            .unwrap_or(self.stops.len());
+        self.stops.insert(idx, GradientStop { pos, color });
+        self
+    }
+
+    pub fn stops(&self) -> &[GradientStop] {
+        &self.stops
+    }
+
+    /// Returns the color at `t`. Values outside [0, 1] are clamped; an empty gradient is
+    /// opaque white so it can be used as a neutral tint.
+    pub fn sample(&self, t: f32) -> RGBAColorF32 {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return RGBAColorF32::new(1f32, 1f32, 1f32),
+        };
+
+        let t = saturate(t);
+        if t <= first.pos {
+            return first.color;
+        }
+
+        if t >= last.pos {
+            return last.color;
+        }
+
+        let idx = self
+            .stops
+            .iter()
+            .position(|s| s.pos > t)
+            .unwrap_or(self.stops.len() - 1);
+
+        let s0 = &self.stops[idx - 1];
+        let s1 = &self.stops[idx];
+        let span = s1.pos - s0.pos;
+        let k = if span > 0f32 {
+            (t - s0.pos) / span
+        } else {
+            1f32
+        };
+
+        match self.interpolation {
+            GradientInterpolation::Rgb => lerp_rgb(s0.color, s1.color, k),
+            GradientInterpolation::Hsl => lerp_hsl(s0.color, s1.color, k),
+        }
+    }
+}
+
+fn lerp_rgb(a: RGBAColorF32, b: RGBAColorF32, t: f32) -> RGBAColorF32 {
+    a * (1f32 - t) + b * t
+}
+
+fn lerp_hsl(a: RGBAColorF32, b: RGBAColorF32, t: f32) -> RGBAColorF32 {
+    let ha = HslColor::from(a);
+    let hb = HslColor::from(b);
+
+    let mut dh = hb.h - ha.h;
+    if dh > 180f32 {
+        dh -= 360f32;
+    } else if dh < -180f32 {
+        dh += 360f32;
+    }
+
+    let mut h = ha.h + dh * t;
+    if h < 0f32 {
+        h += 360f32;
+    } else if h >= 360f32 {
+        h -= 360f32;
+    }
+
+    let rgb = RGBAColorF32::from(HslColor::new(
+        h,
+        ha.s + (hb.s - ha.s) * t,
+        ha.l + (hb.l - ha.l) * t,
+    ));
+
+    RGBAColorF32::new_with_alpha(rgb.r, rgb.g, rgb.b, a.a + (b.a - a.a) * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: RGBAColorF32, b: RGBAColorF32) -> bool {
+        a.as_slice()
+            .iter()
+            .zip(b.as_slice().iter())
+            .all(|(x, y)| (x - y).abs() <= 0.001f32)
+    }
+
+    #[test]
+    fn test_sample_two_color() {
+        let g = ColorGradient::two_color(
+            RGBAColorF32::new_with_alpha(0f32, 0f32, 0f32, 0f32),
+            RGBAColorF32::new(1f32, 1f32, 1f32),
+        );
+
+        assert!(approx_eq(
+            g.sample(0.5f32),
+            RGBAColorF32::new_with_alpha(0.5f32, 0.5f32, 0.5f32, 0.5f32)
+        ));
+        assert!(approx_eq(g.sample(-1f32), g.sample(0f32)));
+        assert!(approx_eq(
+            g.sample(2f32),
+            RGBAColorF32::new(1f32, 1f32, 1f32)
+        ));
+    }
+
+    #[test]
+    fn test_stops_are_sorted() {
+        let mut g = ColorGradient::new(GradientInterpolation::Rgb);
+        g.add_stop(1f32, RGBAColorF32::new(0f32, 0f32, 1f32))
+            .add_stop(0f32, RGBAColorF32::new(1f32, 0f32, 0f32))
+            .add_stop(0.5f32, RGBAColorF32::new(0f32, 1f32, 0f32));
+
+        assert!(g.stops().windows(2).all(|w| w[0].pos <= w[1].pos));
+        assert!(approx_eq(
+            g.sample(0.5f32),
+            RGBAColorF32::new(0f32, 1f32, 0f32)
+        ));
+        assert!(approx_eq(
+            g.sample(0.75f32),
+            RGBAColorF32::new(0f32, 0.5f32, 0.5f32)
+        ));
+    }
+
+    #[test]
+    fn test_hsl_takes_shortest_hue_arc() {
+        //
+        // magenta (300) -> red (0) should pass through hue 330, not through green
+        let g = ColorGradient::two_color(
+            RGBAColorF32::new(1f32, 0f32, 1f32),
+            RGBAColorF32::new(1f32, 0f32, 0f32),
+        )
+        .with_interpolation(GradientInterpolation::Hsl);
+
+        let mid = g.sample(0.5f32);
+        assert!(mid.g.abs() <= 0.001f32);
+        assert!((mid.r - 1f32).abs() <= 0.001f32);
+    }
+
+    #[test]
+    fn test_palettes() {
+        let g = ColorGradient::from_palette(&crate::colormap::ColorMap::pf3());
+        assert!(approx_eq(
+            g.sample(0f32),
+            RGBAColorF32::new(0f32, 0f32, 0f32)
+        ));
+
+        let g = ColorGradient::turbo();
+        assert_eq!(g.stops().len(), TURBO_SRGB_FLOATS.len());
+    }
+}
@@ -42,6 +42,7 @@ define_color_component!(u8, 255, color_u32_to_color_u8);
 define_color_component!(f32, 1_f32, color_u32_to_color_f32);
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(C)]
 pub struct TColorRGBA<T>
 where
     T: Copy + Clone + std::fmt::Debug + Num + NumColorComponent,
@@ -11,6 +11,7 @@ pub mod mat4;
 pub mod vertex_types;
 
 pub mod color_conversion;
+pub mod color_gradient;
 pub mod colormap;
 pub mod minmax;
 pub mod projection;
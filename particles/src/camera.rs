@@ -0,0 +1,220 @@
+use math::mat4::Mat4F32;
+use math::projection;
+use math::rectangle::RectangleF32;
+use math::utility::clamp;
+use math::vec2::Vec2F32;
+use sys::input::*;
+
+/// 2D camera looking at the simulation world.
+///
+/// World space has the Y axis pointing up and is measured in world units, screen space
+/// has the origin in the top left corner of the window, Y pointing down and is measured
+/// in pixels.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera2D {
+    /// World point at the center of the viewport.
+    center: Vec2F32,
+    /// Screen pixels per world unit.
+    zoom: f32,
+    /// Viewport size in pixels.
+    viewport: Vec2F32,
+    /// Last pointer position while panning.
+    drag_pos: Option<(i32, i32)>,
+}
+
+impl Camera2D {
+    pub const MIN_ZOOM: f32 = 0.05f32;
+    pub const MAX_ZOOM: f32 = 20f32;
+    /// Zoom multiplier applied for each wheel step.
+    pub const ZOOM_STEP: f32 = 1.1f32;
+    /// Mouse button that pans the camera while held down.
+    pub const PAN_BUTTON: MouseButtonId = MouseButtonId::Button3;
+
+    pub fn new(viewport_width: i32, viewport_height: i32) -> Self {
+        let viewport = Vec2F32::new(viewport_width as f32, viewport_height as f32);
+        Camera2D {
+            center: viewport * 0.5f32,
+            zoom: 1f32,
+            viewport,
+            drag_pos: None,
+        }
+    }
+
+    pub fn center(&self) -> Vec2F32 {
+        self.center
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn viewport(&self) -> Vec2F32 {
+        self.viewport
+    }
+
+    pub fn is_panning(&self) -> bool {
+        self.drag_pos.is_some()
+    }
+
+    /// Keeps the world point at the center of the viewport when the window is resized.
+    pub fn set_viewport(&mut self, width: i32, height: i32) {
+        self.viewport = Vec2F32::new(width.max(1) as f32, height.max(1) as f32);
+    }
+
+    pub fn look_at(&mut self, world_pos: Vec2F32) {
+        self.center = world_pos;
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = clamp(Self::MIN_ZOOM, zoom, Self::MAX_ZOOM);
+    }
+
+    /// Centers the camera on a world of the given size and zooms so that all of it is visible.
+    pub fn fit(&mut self, world_size: Vec2F32) {
+        self.center = world_size * 0.5f32;
+        self.set_zoom(
+            (self.viewport.x / world_size.x.max(1f32))
+                .min(self.viewport.y / world_size.y.max(1f32)),
+        );
+    }
+
+    /// Moves the camera so that the scene follows a pointer that moved (dx, dy) pixels.
+    pub fn pan_screen(&mut self, dx: f32, dy: f32) {
+        self.center.x -= dx / self.zoom;
+        self.center.y += dy / self.zoom;
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the world point under `screen_pos` fixed.
+    pub fn zoom_at(&mut self, factor: f32, screen_pos: Vec2F32) {
+        let anchor = self.screen_to_world(screen_pos);
+        self.set_zoom(self.zoom * factor);
+        let moved = self.screen_to_world(screen_pos);
+        self.center += anchor - moved;
+    }
+
+    pub fn world_to_screen(&self, world_pos: Vec2F32) -> Vec2F32 {
+        Vec2F32::new(
+            (world_pos.x - self.center.x) * self.zoom + self.viewport.x * 0.5f32,
+            self.viewport.y * 0.5f32 - (world_pos.y - self.center.y) * self.zoom,
+        )
+    }
+
+    pub fn screen_to_world(&self, screen_pos: Vec2F32) -> Vec2F32 {
+        Vec2F32::new(
+            (screen_pos.x - self.viewport.x * 0.5f32) / self.zoom + self.center.x,
+            (self.viewport.y * 0.5f32 - screen_pos.y) / self.zoom + self.center.y,
+        )
+    }
+
+    /// World space rectangle covered by the viewport (x, y is the bottom left corner).
+    pub fn visible_world_rect(&self) -> RectangleF32 {
+        let half_extent = self.viewport * (0.5f32 / self.zoom);
+        RectangleF32::new(
+            self.center.x - half_extent.x,
+            self.center.y - half_extent.y,
+            half_extent.x * 2f32,
+            half_extent.y * 2f32,
+        )
+    }
+
+    /// Maps world space to clip space.
+    pub fn projection(&self) -> Mat4F32 {
+        let r = self.visible_world_rect();
+        projection::orthographic(r.x, r.y + r.h, r.x + r.w, r.y, -1f32, 1f32)
+    }
+
+    /// Returns true if the event was used by the camera.
+    pub fn handle_mouse_button(&mut self, mbe: &MouseButtonEventData) -> bool {
+        if mbe.button != Self::PAN_BUTTON {
+            return false;
+        }
+
+        self.drag_pos = match mbe.type_ {
+            ActionType::Press => Some((mbe.pointer_x, mbe.pointer_y)),
+            ActionType::Release => None,
+        };
+
+        true
+    }
+
+    /// Returns true if the event was used by the camera.
+    pub fn handle_mouse_motion(&mut self, mme: &MouseMotionEventData) -> bool {
+        match self.drag_pos {
+            Some((x, y)) => {
+                self.pan_screen((mme.pointer_x - x) as f32, (mme.pointer_y - y) as f32);
+                self.drag_pos = Some((mme.pointer_x, mme.pointer_y));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn handle_mouse_wheel(&mut self, mwe: &MouseWheelEventData) {
+        self.zoom_at(
+            Self::ZOOM_STEP.powi(mwe.delta),
+            Vec2F32::new(mwe.pointer_x as f32, mwe.pointer_y as f32),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: Vec2F32, b: Vec2F32) -> bool {
+        (a.x - b.x).abs() <= 0.001f32 && (a.y - b.y).abs() <= 0.001f32
+    }
+
+    #[test]
+    fn test_world_screen_roundtrip() {
+        let mut cam = Camera2D::new(800, 600);
+        cam.look_at(Vec2F32::new(1000f32, -250f32));
+        cam.set_zoom(2.5f32);
+
+        let p = Vec2F32::new(1033f32, -200f32);
+        assert!(approx_eq(cam.screen_to_world(cam.world_to_screen(p)), p));
+
+        //
+        // camera center is the middle of the viewport, world Y points up
+        assert!(approx_eq(
+            cam.world_to_screen(cam.center()),
+            Vec2F32::new(400f32, 300f32)
+        ));
+        assert!(cam.world_to_screen(Vec2F32::new(1000f32, -240f32)).y < 300f32);
+    }
+
+    #[test]
+    fn test_zoom_keeps_anchor() {
+        let mut cam = Camera2D::new(800, 600);
+        let cursor = Vec2F32::new(100f32, 500f32);
+        let anchor = cam.screen_to_world(cursor);
+
+        cam.zoom_at(3f32, cursor);
+        assert!(approx_eq(cam.screen_to_world(cursor), anchor));
+        assert!((cam.zoom() - 3f32).abs() <= 0.001f32);
+
+        cam.zoom_at(1000f32, cursor);
+        assert!((cam.zoom() - Camera2D::MAX_ZOOM).abs() <= 0.001f32);
+    }
+
+    #[test]
+    fn test_pan_follows_pointer() {
+        let mut cam = Camera2D::new(800, 600);
+        let p = Vec2F32::new(10f32, 20f32);
+        let before = cam.world_to_screen(p);
+
+        cam.pan_screen(15f32, -7f32);
+        let after = cam.world_to_screen(p);
+        assert!(approx_eq(after - before, Vec2F32::new(15f32, -7f32)));
+    }
+
+    #[test]
+    fn test_fit_world() {
+        let mut cam = Camera2D::new(800, 600);
+        cam.fit(Vec2F32::new(1600f32, 900f32));
+
+        let r = cam.visible_world_rect();
+        assert!(r.w >= 1600f32 - 0.001f32 && r.h >= 900f32 - 0.001f32);
+        assert!(approx_eq(cam.center(), Vec2F32::new(800f32, 450f32)));
+    }
+}
@@ -0,0 +1,413 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use sys::input::*;
+
+/// Named commands that can be bound to keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Pause,
+    /// Pauses the simulation and advances it by a single fixed step.
+    Step,
+    /// Respawns all particles.
+    Reset,
+    /// Doubles the time scale.
+    SpeedUp,
+    /// Halves the time scale.
+    SlowDown,
+    /// Toggles slow motion, on top of the time scale.
+    SlowMotion,
+    /// Runs a fixed number of steps as fast as possible, or stops a run in progress.
+    FastForward,
+    Screenshot,
+    ToggleHud,
+    ToggleDebugDraw,
+    /// Writes the profiler statistics and the recent timeline to files.
+    DumpProfile,
+    /// Switches between the post-processing chain and drawing straight to the window.
+    TogglePostProcess,
+    /// Switches between a window and covering the monitor.
+    ToggleFullscreen,
+    ToolAttract,
+    ToolRepel,
+    ToolSpawn,
+    ToolDrag,
+}
+
+impl Action {
+    pub const ALL: [Action; 18] = [
+        Action::Quit,
+        Action::Pause,
+        Action::Step,
+        Action::Reset,
+        Action::SpeedUp,
+        Action::SlowDown,
+        Action::SlowMotion,
+        Action::FastForward,
+        Action::Screenshot,
+        Action::ToggleHud,
+        Action::ToggleDebugDraw,
+        Action::DumpProfile,
+        Action::TogglePostProcess,
+        Action::ToggleFullscreen,
+        Action::ToolAttract,
+        Action::ToolRepel,
+        Action::ToolSpawn,
+        Action::ToolDrag,
+    ];
+
+    /// Name used in the bindings file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+            Action::Step => "step",
+            Action::Reset => "reset",
+            Action::SpeedUp => "speed_up",
+            Action::SlowDown => "slow_down",
+            Action::SlowMotion => "slow_motion",
+            Action::FastForward => "fast_forward",
+            Action::Screenshot => "screenshot",
+            Action::ToggleHud => "toggle_hud",
+            Action::ToggleDebugDraw => "toggle_debug_draw",
+            Action::DumpProfile => "dump_profile",
+            Action::TogglePostProcess => "toggle_post_process",
+            Action::ToggleFullscreen => "toggle_fullscreen",
+            Action::ToolAttract => "tool_attract",
+            Action::ToolRepel => "tool_repel",
+            Action::ToolSpawn => "tool_spawn",
+            Action::ToolDrag => "tool_drag",
+        }
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Action, String> {
+        Action::ALL
+            .iter()
+            .copied()
+            .find(|a| a.name() == s.trim())
+            .ok_or_else(|| format!("Unknown action '{}'", s.trim()))
+    }
+}
+
+/// A key together with the modifiers that must be held down.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyBinding {
+    pub key: KeySymbol,
+    pub shift: bool,
+    pub control: bool,
+}
+
+impl KeyBinding {
+    pub fn key(key: KeySymbol) -> KeyBinding {
+        KeyBinding {
+            key,
+            shift: false,
+            control: false,
+        }
+    }
+
+    pub fn ctrl(key: KeySymbol) -> KeyBinding {
+        KeyBinding {
+            control: true,
+            ..KeyBinding::key(key)
+        }
+    }
+
+    pub fn shift(key: KeySymbol) -> KeyBinding {
+        KeyBinding {
+            shift: true,
+            ..KeyBinding::key(key)
+        }
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.control {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+impl std::str::FromStr for KeyBinding {
+    type Err = String;
+
+    /// Parses bindings like "F1", "ctrl+S" or "Ctrl+Shift+KpAdd".
+    fn from_str(s: &str) -> Result<KeyBinding, String> {
+        let mut parts = s.split('+').map(|p| p.trim()).collect::<Vec<_>>();
+        let key = parts
+            .pop()
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| format!("Missing key in binding '{}'", s.trim()))?;
+
+        let mut binding = KeyBinding::key(key.parse()?);
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => binding.control = true,
+                "shift" => binding.shift = true,
+                _ => return Err(format!("Unknown modifier '{}' in '{}'", modifier, s.trim())),
+            }
+        }
+
+        Ok(binding)
+    }
+}
+
+/// Maps key presses to actions.
+///
+/// The bindings file has one `action = binding, binding, ..` line per action, `#` starts a
+/// comment. Actions that are not listed keep their default bindings, an action listed
+/// with no bindings is unbound.
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<KeyBinding, Action>,
+}
+
+impl KeyBindings {
+    fn default_bindings() -> HashMap<Action, Vec<KeyBinding>> {
+        use KeySymbol::*;
+
+        [
+            (Action::Quit, vec![KeyBinding::key(Escape)]),
+            (
+                Action::Pause,
+                vec![KeyBinding::key(Space), KeyBinding::key(KeyP)],
+            ),
+            (Action::Step, vec![KeyBinding::key(KeyN)]),
+            (Action::Reset, vec![KeyBinding::key(KeyR)]),
+            (
+                Action::SpeedUp,
+                vec![KeyBinding::key(KpAdd), KeyBinding::key(PageUp)],
+            ),
+            (
+                Action::SlowDown,
+                vec![KeyBinding::key(KpMinus), KeyBinding::key(PageDown)],
+            ),
+            (Action::SlowMotion, vec![KeyBinding::key(KeyM)]),
+            (Action::FastForward, vec![KeyBinding::key(KeyF)]),
+            (
+                Action::Screenshot,
+                vec![KeyBinding::key(F12), KeyBinding::key(PrintScreen)],
+            ),
+            (Action::ToggleHud, vec![KeyBinding::key(F1)]),
+            (Action::ToggleDebugDraw, vec![KeyBinding::key(F2)]),
+            (Action::DumpProfile, vec![KeyBinding::key(F3)]),
+            (Action::TogglePostProcess, vec![KeyBinding::key(F4)]),
+            (Action::ToggleFullscreen, vec![KeyBinding::key(F11)]),
+            (Action::ToolAttract, vec![KeyBinding::key(Key1)]),
+            (Action::ToolRepel, vec![KeyBinding::key(Key2)]),
+            (Action::ToolSpawn, vec![KeyBinding::key(Key3)]),
+            (Action::ToolDrag, vec![KeyBinding::key(Key4)]),
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    }
+
+    pub fn defaults() -> KeyBindings {
+        Self::from_actions(Self::default_bindings()).expect("Default key bindings conflict")
+    }
+
+    /// Inverts the action -> bindings map, failing if a binding triggers more than one action.
+    fn from_actions(actions: HashMap<Action, Vec<KeyBinding>>) -> Result<KeyBindings, String> {
+        let mut bindings = HashMap::new();
+        let mut conflicts = Vec::new();
+
+        //
+        // walk the actions in a fixed order so the error message is stable
+        Action::ALL
+            .iter()
+            .filter_map(|a| actions.get(a).map(|keys| (*a, keys)))
+            .for_each(|(action, keys)| {
+                keys.iter().for_each(|key| {
+                    if let Some(prev) = bindings.insert(*key, action) {
+                        if prev != action {
+                            conflicts.push(format!(
+                                "{} is bound to both '{}' and '{}'",
+                                key,
+                                prev.name(),
+                                action.name()
+                            ));
+                        }
+                    }
+                });
+            });
+
+        if conflicts.is_empty() {
+            Ok(KeyBindings { bindings })
+        } else {
+            Err(format!(
+                "Conflicting key bindings: {}",
+                conflicts.join(", ")
+            ))
+        }
+    }
+
+    /// Parses the contents of a bindings file, on top of the default bindings.
+    pub fn parse(src: &str) -> Result<KeyBindings, String> {
+        let mut actions = Self::default_bindings();
+        let mut overridden = HashSet::new();
+
+        for (line_idx, line) in src.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let err = |e: String| format!("line {}: {}", line_idx + 1, e);
+
+            let mut kv = line.splitn(2, '=');
+            let action = kv.next().unwrap_or("").parse::<Action>().map_err(err)?;
+            let value = kv
+                .next()
+                .ok_or_else(|| err(format!("Expected '{} = <keys>'", action.name())))?;
+
+            let keys = value
+                .split(',')
+                .map(|k| k.trim())
+                .filter(|k| !k.is_empty())
+                .map(|k| k.parse::<KeyBinding>().map_err(err))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let entry = actions.entry(action).or_default();
+            if overridden.insert(action) {
+                entry.clear();
+            }
+            entry.extend(keys);
+        }
+
+        Self::from_actions(actions)
+    }
+
+    /// Loads the bindings from a file. A missing file gives the default bindings.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<KeyBindings, String> {
+        let path = path.as_ref();
+        match std::fs::read_to_string(path) {
+            Ok(src) => Self::parse(&src).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::defaults()),
+            Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+        }
+    }
+
+    /// Action triggered by a key event, only key presses trigger actions.
+    pub fn action_for(&self, ke: &KeyEventData) -> Option<Action> {
+        if ke.type_ != ActionType::Press {
+            return None;
+        }
+
+        self.bindings
+            .get(&KeyBinding {
+                key: ke.keycode,
+                shift: ke.shift,
+                control: ke.control,
+            })
+            .copied()
+    }
+
+    /// Bindings of an action, in no particular order.
+    pub fn bindings_of(&self, action: Action) -> Vec<KeyBinding> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| **a == action)
+            .map(|(k, _)| *k)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(key: KeySymbol, shift: bool, control: bool) -> KeyEventData {
+        KeyEventData {
+            keycode: key,
+            shift,
+            control,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_binding() {
+        assert_eq!(
+            "ctrl+shift+S".parse::<KeyBinding>(),
+            Ok(KeyBinding {
+                key: KeySymbol::KeyS,
+                shift: true,
+                control: true
+            })
+        );
+        assert_eq!(
+            "F1".parse::<KeyBinding>(),
+            Ok(KeyBinding::key(KeySymbol::F1))
+        );
+        assert!("alt+F1".parse::<KeyBinding>().is_err());
+        assert!("ctrl+".parse::<KeyBinding>().is_err());
+        assert_eq!(KeyBinding::ctrl(KeySymbol::KeyS).to_string(), "Ctrl+KeyS");
+    }
+
+    #[test]
+    fn test_defaults() {
+        let kb = KeyBindings::defaults();
+        assert_eq!(
+            kb.action_for(&press(KeySymbol::Escape, false, false)),
+            Some(Action::Quit)
+        );
+        //
+        // modifiers must match exactly, releases are ignored
+        assert_eq!(kb.action_for(&press(KeySymbol::Escape, true, false)), None);
+        let release = KeyEventData {
+            type_: ActionType::Release,
+            ..press(KeySymbol::Escape, false, false)
+        };
+        assert_eq!(kb.action_for(&release), None);
+    }
+
+    #[test]
+    fn test_parse_overrides_defaults() {
+        let kb = KeyBindings::parse(
+            "# comment\n\
+             quit = ctrl+Q, Q  # trailing comment\n\
+             \n\
+             reset =\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            kb.action_for(&press(KeySymbol::KeyQ, false, true)),
+            Some(Action::Quit)
+        );
+        assert_eq!(kb.action_for(&press(KeySymbol::Escape, false, false)), None);
+        assert!(kb.bindings_of(Action::Reset).is_empty());
+        assert_eq!(
+            kb.action_for(&press(KeySymbol::Space, false, false)),
+            Some(Action::Pause)
+        );
+    }
+
+    #[test]
+    fn test_conflicts_and_errors() {
+        let err = KeyBindings::parse("quit = Space").unwrap_err();
+        assert!(err.contains("Space") && err.contains("pause") && err.contains("quit"));
+
+        //
+        // rebinding the action that used the key resolves the conflict
+        assert!(KeyBindings::parse("quit = Space\npause = P").is_ok());
+
+        assert!(KeyBindings::parse("jump = Space")
+            .unwrap_err()
+            .starts_with("line 1"));
+        assert!(KeyBindings::parse("\nquit Escape")
+            .unwrap_err()
+            .starts_with("line 2"));
+        assert!(KeyBindings::parse("quit = NoSuchKey").is_err());
+    }
+}
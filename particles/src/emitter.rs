@@ -0,0 +1,374 @@
+use math::color_gradient::ColorGradient;
+use math::colormap::ColorMap;
+use math::colors::RGBAColorF32;
+use math::utility::saturate;
+use rendering::BlendState;
+
+/// How a particle group is composited with what was drawn before it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Classic "over" blending, the tint is straight (non premultiplied) alpha.
+    Alpha,
+    /// Same as `Alpha` but the tint is already premultiplied, so a tint with a low alpha
+    /// and bright colors glows instead of fading out.
+    Premultiplied,
+    /// Colors accumulate, order independent. Good for fire, sparks and glows.
+    Additive,
+    /// No blending, texels with an alpha below `OPAQUE_ALPHA_CUTOFF` are discarded.
+    Opaque,
+}
+
+impl BlendMode {
+    /// Alpha below which the texels of opaque particles are discarded.
+    pub const OPAQUE_ALPHA_CUTOFF: f32 = 0.5f32;
+
+    /// Groups are drawn in ascending order of this value: opaque first, then the order
+    /// dependent modes and finally the additive ones.
+    pub fn draw_priority(self) -> u32 {
+        match self {
+            BlendMode::Opaque => 0,
+            BlendMode::Alpha | BlendMode::Premultiplied => 1,
+            BlendMode::Additive => 2,
+        }
+    }
+
+    /// Texels with an alpha below this value are discarded, 0 keeps all of them.
+    pub fn alpha_cutoff(self) -> f32 {
+        match self {
+            BlendMode::Opaque => Self::OPAQUE_ALPHA_CUTOFF,
+            BlendMode::Alpha | BlendMode::Premultiplied | BlendMode::Additive => 0f32,
+        }
+    }
+
+    /// The mode after this one, for cycling through all of them.
+    pub fn next(self) -> BlendMode {
+        match self {
+            BlendMode::Alpha => BlendMode::Premultiplied,
+            BlendMode::Premultiplied => BlendMode::Additive,
+            BlendMode::Additive => BlendMode::Opaque,
+            BlendMode::Opaque => BlendMode::Alpha,
+        }
+    }
+
+    /// True if the result depends on the order the particles are drawn in.
+    pub fn is_order_dependent(self) -> bool {
+        match self {
+            BlendMode::Alpha | BlendMode::Premultiplied => true,
+            BlendMode::Additive | BlendMode::Opaque => false,
+        }
+    }
+
+    /// The fragment shader outputs premultiplied colors, the blend functions assume so.
+    pub fn blend_state(self) -> BlendState {
+        match self {
+            BlendMode::Opaque => BlendState::disabled(),
+            BlendMode::Alpha | BlendMode::Premultiplied => BlendState::premultiplied(),
+            BlendMode::Additive => BlendState::additive(),
+        }
+    }
+}
+
+/// Draw order of the particles inside an alpha blended group.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SortMode {
+    /// Particles are drawn in the order they were spawned.
+    None,
+    /// Farthest particles first (larger particles are considered closer to the viewer).
+    BackToFront,
+    /// Ascending per-particle layer, back to front inside a layer.
+    Layer,
+}
+
+/// How the base color of a particle is picked every frame.
+#[derive(Clone, Debug)]
+pub enum ColorMode {
+    /// Only the emitter tint is used.
+    Constant,
+    /// Gradient sampled with the normalized age of the particle.
+    OverLifetime(ColorGradient),
+    /// Gradient sampled with the particle speed, remapped from [min_speed, max_speed].
+    BySpeed {
+        gradient: ColorGradient,
+        min_speed: f32,
+        max_speed: f32,
+    },
+}
+
+/// Alpha ramp at the start and end of a particle's life, expressed as fractions of the
+/// lifetime. A value of 0 disables the corresponding ramp.
+#[derive(Copy, Clone, Debug)]
+pub struct AlphaFade {
+    pub fade_in: f32,
+    pub fade_out: f32,
+}
+
+impl AlphaFade {
+    pub fn none() -> Self {
+        AlphaFade {
+            fade_in: 0f32,
+            fade_out: 0f32,
+        }
+    }
+
+    fn alpha(&self, life: f32) -> f32 {
+        let fade_in = if self.fade_in > 0f32 {
+            saturate(life / self.fade_in)
+        } else {
+            1f32
+        };
+
+        let fade_out = if self.fade_out > 0f32 {
+            saturate((1f32 - life) / self.fade_out)
+        } else {
+            1f32
+        };
+
+        fade_in.min(fade_out)
+    }
+}
+
+/// Sprite animation, played from consecutive layers of the sprites texture array.
+#[derive(Copy, Clone, Debug)]
+pub struct Flipbook {
+    /// Layer of the first frame.
+    pub first_frame: u32,
+    pub frames: u32,
+    /// Frames per second range, each particle picks a rate from it. A rate of 0 keeps the
+    /// particle on its start frame.
+    pub fps: (f32, f32),
+    /// Particles start on a random frame instead of the first one.
+    pub random_start: bool,
+    /// Cross-fade between the current and the next frame.
+    pub blend_frames: bool,
+}
+
+impl Flipbook {
+    /// Single, static frame.
+    pub fn still(frame: u32) -> Self {
+        Flipbook {
+            first_frame: frame,
+            frames: 1,
+            fps: (0f32, 0f32),
+            random_start: false,
+            blend_frames: false,
+        }
+    }
+
+    /// Returns the texture layers of the current and the next frame and the weight of the
+    /// next frame, for a particle of the given age (seconds). The animation loops.
+    pub fn frame(&self, age: f32, fps: f32, start_frame: u32) -> (u32, u32, f32) {
+        let frames = self.frames.max(1);
+        let pos = start_frame as f32 + age.max(0f32) * fps;
+        let current = pos.floor() as u32 % frames;
+        let next = (current + 1) % frames;
+        let blend = if self.blend_frames { pos.fract() } else { 0f32 };
+
+        (self.first_frame + current, self.first_frame + next, blend)
+    }
+}
+
+/// Parameters shared by all the particles spawned by an emitter.
+#[derive(Clone, Debug)]
+pub struct EmitterConfig {
+    pub particles: u32,
+    /// Radius range, in pixels. Mass is derived from the radius.
+    pub radius: (f32, f32),
+    /// Lifetime range, in seconds.
+    pub lifetime: (f32, f32),
+    /// Multiplied with the color picked by `color`.
+    pub tint: RGBAColorF32,
+    pub color: ColorMode,
+    pub alpha_fade: AlphaFade,
+    pub blend: BlendMode,
+    /// Only used by the order dependent blend modes.
+    pub sort: SortMode,
+    /// Each particle gets a random layer in [0, layers).
+    pub layers: u32,
+    pub flipbook: Flipbook,
+}
+
+impl std::default::Default for EmitterConfig {
+    fn default() -> Self {
+        EmitterConfig {
+            particles: 512,
+            radius: (16f32, 64f32),
+            lifetime: (2f32, 4f32),
+            tint: RGBAColorF32::new(1f32, 1f32, 1f32),
+            color: ColorMode::Constant,
+            alpha_fade: AlphaFade::none(),
+            blend: BlendMode::Alpha,
+            sort: SortMode::None,
+            layers: 1,
+            flipbook: Flipbook {
+                first_frame: 0,
+                frames: 3,
+                fps: (0f32, 0f32),
+                random_start: true,
+                blend_frames: false,
+            },
+        }
+    }
+}
+
+impl EmitterConfig {
+    /// Color of a particle, given its age and lifetime (seconds) and its current speed.
+    /// The color is premultiplied unless the emitter uses `BlendMode::Premultiplied`, in
+    /// which case the tint and gradients are expected to already be premultiplied.
+    pub fn particle_color(&self, age: f32, lifetime: f32, speed: f32) -> RGBAColorF32 {
+        let life = if lifetime > 0f32 {
+            saturate(age / lifetime)
+        } else {
+            0f32
+        };
+
+        let base = match self.color {
+            ColorMode::Constant => RGBAColorF32::new(1f32, 1f32, 1f32),
+            ColorMode::OverLifetime(ref gradient) => gradient.sample(life),
+            ColorMode::BySpeed {
+                ref gradient,
+                min_speed,
+                max_speed,
+            } => {
+                let range = max_speed - min_speed;
+                gradient.sample(if range > 0f32 {
+                    (speed - min_speed) / range
+                } else {
+                    0f32
+                })
+            }
+        };
+
+        let mut color = base * self.tint;
+        let fade = self.alpha_fade.alpha(life);
+
+        if self.blend == BlendMode::Premultiplied {
+            color * fade
+        } else {
+            color.a *= fade;
+            color.r *= color.a;
+            color.g *= color.a;
+            color.b *= color.a;
+            color
+        }
+    }
+
+    /// The emitters used by the simulation on startup.
+    pub fn default_emitters() -> Vec<EmitterConfig> {
+        vec![
+            EmitterConfig {
+                color: ColorMode::OverLifetime(ColorGradient::turbo()),
+                alpha_fade: AlphaFade {
+                    fade_in: 0.1f32,
+                    fade_out: 0.3f32,
+                },
+                sort: SortMode::BackToFront,
+                flipbook: Flipbook {
+                    first_frame: 0,
+                    frames: 3,
+                    fps: (2f32, 6f32),
+                    random_start: true,
+                    blend_frames: true,
+                },
+                ..Default::default()
+            },
+            EmitterConfig {
+                color: ColorMode::BySpeed {
+                    gradient: ColorGradient::from_palette(&ColorMap::pf1()[1..]),
+                    min_speed: 0f32,
+                    max_speed: 1500f32,
+                },
+                alpha_fade: AlphaFade {
+                    fade_in: 0f32,
+                    fade_out: 0.2f32,
+                },
+                blend: BlendMode::Additive,
+                ..Default::default()
+            },
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flipbook_frames() {
+        let fb = Flipbook {
+            first_frame: 4,
+            frames: 3,
+            fps: (2f32, 2f32),
+            random_start: false,
+            blend_frames: true,
+        };
+
+        assert_eq!(fb.frame(0f32, 2f32, 0), (4, 5, 0f32));
+        assert_eq!(fb.frame(0.75f32, 2f32, 0), (5, 6, 0.5f32));
+        //
+        // wraps around to the first frame
+        assert_eq!(fb.frame(1.25f32, 2f32, 0), (6, 4, 0.5f32));
+        assert_eq!(fb.frame(0f32, 2f32, 2), (6, 4, 0f32));
+
+        let fb = Flipbook {
+            blend_frames: false,
+            ..fb
+        };
+        assert_eq!(fb.frame(0.75f32, 2f32, 0), (5, 6, 0f32));
+
+        assert_eq!(Flipbook::still(1).frame(10f32, 0f32, 0), (1, 1, 0f32));
+    }
+
+    #[test]
+    fn test_particle_color() {
+        let emitter = EmitterConfig {
+            tint: RGBAColorF32::new_with_alpha(1f32, 0.5f32, 0.25f32, 0.5f32),
+            alpha_fade: AlphaFade {
+                fade_in: 0f32,
+                fade_out: 0.5f32,
+            },
+            ..Default::default()
+        };
+
+        //
+        // straight alpha tint, premultiplied by the faded alpha
+        assert_eq!(
+            emitter.particle_color(0f32, 2f32, 0f32),
+            RGBAColorF32::new_with_alpha(0.5f32, 0.25f32, 0.125f32, 0.5f32)
+        );
+        assert_eq!(
+            emitter.particle_color(1.5f32, 2f32, 0f32),
+            RGBAColorF32::new_with_alpha(0.25f32, 0.125f32, 0.0625f32, 0.25f32)
+        );
+
+        //
+        // premultiplied tint, every channel is faded
+        let emitter = EmitterConfig {
+            blend: BlendMode::Premultiplied,
+            ..emitter
+        };
+        assert_eq!(
+            emitter.particle_color(0f32, 2f32, 0f32),
+            RGBAColorF32::new_with_alpha(1f32, 0.5f32, 0.25f32, 0.5f32)
+        );
+        assert_eq!(
+            emitter.particle_color(1.5f32, 2f32, 0f32),
+            RGBAColorF32::new_with_alpha(0.5f32, 0.25f32, 0.125f32, 0.25f32)
+        );
+    }
+
+    #[test]
+    fn test_blend_modes() {
+        assert!(BlendMode::Opaque.draw_priority() < BlendMode::Alpha.draw_priority());
+        assert_eq!(
+            BlendMode::Alpha.draw_priority(),
+            BlendMode::Premultiplied.draw_priority()
+        );
+        assert!(BlendMode::Premultiplied.draw_priority() < BlendMode::Additive.draw_priority());
+
+        assert_eq!(
+            BlendMode::Opaque.alpha_cutoff(),
+            BlendMode::OPAQUE_ALPHA_CUTOFF
+        );
+        assert_eq!(BlendMode::Alpha.alpha_cutoff(), 0f32);
+    }
+}
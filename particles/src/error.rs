@@ -0,0 +1,43 @@
+use rendering::RenderError;
+use sys::WindowError;
+
+/// Errors that stop the application from starting.
+#[derive(Debug)]
+pub enum AppError {
+    Window(WindowError),
+    Render(RenderError),
+    /// Invalid command line option or configuration file.
+    Config(String),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AppError::Window(e) => e.fmt(f),
+            AppError::Render(e) => e.fmt(f),
+            AppError::Config(msg) => write!(f, "Configuration error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Window(e) => Some(e),
+            AppError::Render(e) => Some(e),
+            AppError::Config(_) => None,
+        }
+    }
+}
+
+impl std::convert::From<WindowError> for AppError {
+    fn from(e: WindowError) -> Self {
+        AppError::Window(e)
+    }
+}
+
+impl std::convert::From<RenderError> for AppError {
+    fn from(e: RenderError) -> Self {
+        AppError::Render(e)
+    }
+}
@@ -1,16 +1,152 @@
 #![allow(dead_code)]
 
+use error::AppError;
+use rendering::gl_debug::{self, DebugSeverity};
+use std::path::PathBuf;
 use sys::input::*;
-use sys::SimpleWindow;
+use sys::{FullscreenMode, LogFilter, LogFormat, Logger, WindowBuilder};
 
+mod camera;
+mod commands;
+mod emitter;
+mod error;
 mod particles;
+mod profiler;
+mod screenshot;
+mod sim_clock;
+mod stats;
+mod tools;
 
-fn main() -> std::result::Result<(), String> {
-    let mut app_window = SimpleWindow::new()?;
+/// Log filter, same syntax as the `--log` option.
+const LOG_ENV_VAR: &str = "PARTICLES_LOG";
+/// Log format, same values as the `--log-format` option.
+const LOG_FORMAT_ENV_VAR: &str = "PARTICLES_LOG_FORMAT";
+/// Enables GL debug output, same values as the `--gl-debug` option.
+const GL_DEBUG_ENV_VAR: &str = "PARTICLES_GL_DEBUG";
+/// Window size, same values as the `--windowed` option.
+const WINDOWED_ENV_VAR: &str = "PARTICLES_WINDOWED";
+/// Monitor index, same value as the `--monitor` option.
+const MONITOR_ENV_VAR: &str = "PARTICLES_MONITOR";
+/// Program binary cache directory, same values as the `--program-cache` option.
+const PROGRAM_CACHE_ENV_VAR: &str = "PARTICLES_PROGRAM_CACHE";
+/// Used by `--program-cache` without a directory.
+const PROGRAM_CACHE_DIR: &str = "cache/programs";
 
-    let world_size = dbg!(app_window.size());
-    let particle_sim = particles::ParticlesSim::new(world_size.0, world_size.1)?;
+/// Value of the `--<name>` or `--<name>=<value>` command line option (an empty string
+/// for the former), falls back to the environment variable `env_var`.
+fn option_value(name: &str, env_var: &str) -> Option<String> {
+    let flag = format!("--{}", name);
+    let prefix = format!("--{}=", name);
+
+    std::env::args()
+        .skip(1)
+        .find_map(|arg| {
+            if arg == flag {
+                Some(String::new())
+            } else {
+                arg.strip_prefix(prefix.as_str()).map(|s| s.to_string())
+            }
+        })
+        .or_else(|| std::env::var(env_var).ok())
+}
+
+/// `--log=<filter>` takes a default level and per module levels, like
+/// `warn,rendering=debug`. `--log-format=json` writes JSON lines instead of plain text.
+/// Invalid options are reported after installing a logger with the defaults.
+fn init_logging() -> Result<(), AppError> {
+    let filter = option_value("log", LOG_ENV_VAR)
+        .map(|filter| filter.parse::<LogFilter>())
+        .transpose();
+    let format = option_value("log-format", LOG_FORMAT_ENV_VAR)
+        .map(|format| format.parse::<LogFormat>())
+        .transpose();
+
+    Logger::new(
+        filter.clone().ok().flatten().unwrap_or_default(),
+        format.clone().ok().flatten().unwrap_or(LogFormat::Text),
+    )
+    .install()
+    .map_err(AppError::Config)?;
+
+    filter.and(format).map(|_| ()).map_err(AppError::Config)
+}
+
+/// Minimum severity of the GL debug messages to report, `None` if debug output is off.
+/// `--gl-debug` uses the low severity, `--gl-debug=<severity>` picks one of
+/// notification, low, medium or high.
+fn gl_debug_severity() -> Result<Option<DebugSeverity>, AppError> {
+    match option_value("gl-debug", GL_DEBUG_ENV_VAR) {
+        None => Ok(None),
+        Some(ref s) if s.is_empty() => Ok(Some(DebugSeverity::Low)),
+        Some(s) => s.parse().map(Some).map_err(AppError::Config),
+    }
+}
+
+/// The window covers the monitor unless `--windowed` or `--windowed=<width>x<height>` is
+/// given, `--monitor=<index>` picks the monitor.
+fn window_builder() -> Result<WindowBuilder, AppError> {
+    let mut builder = WindowBuilder::new();
+    builder.set_title("particles");
+
+    match option_value("windowed", WINDOWED_ENV_VAR) {
+        None => {
+            builder.set_fullscreen(FullscreenMode::Borderless);
+        }
+        Some(ref s) if s.is_empty() => {}
+        Some(s) => {
+            let size = s
+                .split_once('x')
+                .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+                .ok_or_else(|| AppError::Config(format!("Invalid window size '{}'", s)))?;
+            builder.set_size(size.0, size.1);
+        }
+    }
+
+    if let Some(monitor) = option_value("monitor", MONITOR_ENV_VAR) {
+        let index = monitor
+            .parse()
+            .map_err(|_| AppError::Config(format!("Invalid monitor index '{}'", monitor)))?;
+        builder.set_monitor(index);
+    }
+
+    Ok(builder)
+}
+
+/// Directory for the linked program binaries, `None` if the cache is off. `--program-cache`
+/// uses the default directory, `--program-cache=<dir>` picks one.
+fn program_cache_dir() -> Option<PathBuf> {
+    match option_value("program-cache", PROGRAM_CACHE_ENV_VAR) {
+        None => None,
+        Some(ref s) if s.is_empty() => Some(PathBuf::from(PROGRAM_CACHE_DIR)),
+        Some(s) => Some(PathBuf::from(s)),
+    }
+}
+
+fn run() -> Result<(), AppError> {
+    init_logging()?;
+
+    let debug_severity = gl_debug_severity()?;
+    let mut app_window = window_builder()?
+        .set_debug_context(debug_severity.is_some())
+        .build()?;
+
+    if let Some(min_severity) = debug_severity {
+        gl_debug::enable_debug_output(min_severity, gl_debug::default_logger())?;
+    }
+
+    let world_size = app_window.size();
+    log::info!("Window size {}x{}", world_size.0, world_size.1);
+    let particle_sim =
+        particles::ParticlesSim::new(world_size.0, world_size.1, program_cache_dir().as_deref())?;
     app_window.message_loop(Box::new(move |e: &Event| particle_sim.main_loop(e)));
 
     Ok(())
 }
+
+fn main() {
+    if let Err(e) = run() {
+        log::error!("{}", e);
+        log::logger().flush();
+        std::process::exit(1);
+    }
+}
@@ -1,21 +1,46 @@
-use math::projection;
+use crate::camera::Camera2D;
+use crate::commands::{Action, KeyBindings};
+use crate::emitter::{BlendMode, EmitterConfig, SortMode};
+use crate::error::AppError;
+use crate::profiler::{Profiler, Timeline};
+use crate::sim_clock::SimClock;
+use crate::tools::{MouseTool, PointerInteraction, ToolState};
+use math::colors::RGBAColorF32;
+use math::rectangle::RectangleF32;
 use math::vec2::*;
-use math::{mat2x3, mat4::Mat4F32};
+use math::{mat2x3, mat4, mat4::Mat4F32};
 use rand::{thread_rng, Rng};
+use rendering::debug_draw::DebugDrawList;
+use rendering::font::FontAtlas;
+use rendering::gl_debug::{check_gl_errors, label_object, ObjectKind};
+use rendering::ui::{Ui, UiDrawList, UiStyle};
 use rendering::*;
 use std::cell::{Cell, RefCell};
-use std::time::Instant;
+use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use sys::input::*;
 
-fn slice_bytes_len<T>(s: &[T]) -> usize {
-    s.len() * std::mem::size_of::<T>()
-}
 #[derive(Copy, Clone, Debug)]
+#[repr(C)]
 struct VertexPT {
     pos: Vec2F32,
     uv: Vec2F32,
 }
 
+unsafe impl Pod for VertexPT {}
+
+impl Vertex for VertexPT {
+    /// Position at location 0, texture coordinates at location 1.
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new()
+            .add_attribute(0, AttribFormat::Float2)
+            .add_attribute(1, AttribFormat::Float2)
+            .build()
+            .expect("VertexPT layout")
+    }
+}
+
 mod physics {
     pub const ROTATION_STEP: f32 = 1.0f32;
     pub const MAX_PARTICLES: u32 = 1024;
@@ -23,16 +48,23 @@ mod physics {
     pub const AIR_DENSITY: f32 = 1.23f32; // kg/m^3
     pub const DRAG_COEFF: f32 = 0.6f32;
     pub const WIND_SPEED: f32 = 10f32; // m/sec
+    /// World size, relative to the size of the window at startup.
+    pub const WORLD_SCALE: f32 = 1.5f32;
 }
 
 #[derive(Copy, Clone, Debug)]
 #[repr(C)]
 struct ParticleGPU {
     transform: Mat4F32,
+    color: RGBAColorF32,
     texid: u32,
-    pad: [u32; 3],
+    texid_next: u32,
+    frame_blend: f32,
+    pad: u32,
 }
 
+unsafe impl Pod for ParticleGPU {}
+
 // changes every frame
 #[derive(Copy, Clone, Debug)]
 struct ParticlePhysics {
@@ -41,12 +73,28 @@ struct ParticlePhysics {
     position: Vec2F32,
     velocity: Vec2F32,
     forces: Vec2F32,
+    age: f32, // seconds since the particle was (re)spawned
 }
 
 impl ParticlePhysics {
-    fn compute_loads(&mut self, gravity: Vec2F32) {
+    fn spawn<R: Rng>(world_size: Vec2F32, rng: &mut R) -> Self {
+        ParticlePhysics {
+            speed: 0f32,
+            position: Vec2F32 {
+                x: rng.gen_range(0f32, world_size.x),
+                y: world_size.y,
+            },
+            velocity: Vec2F32::default(),
+            forces: Vec2F32::default(),
+            rotation: rng.gen_range(0f32, 2f32 * std::f32::consts::PI),
+            age: 0f32,
+        }
+    }
+
+    fn compute_loads(&mut self, gravity: Vec2F32, external: Vec2F32) {
         self.forces = Vec2F32::default();
         self.forces += gravity;
+        self.forces += external;
 
         // //
         // // air drag
@@ -96,106 +144,332 @@ impl ParticlePhysics {
 struct Particle {
     radius: f32,
     mass: f32,
-    gravity: Vec2F32,
-    texid: u32,
+    frame_rate: f32,
+    start_frame: u32,
+    lifetime: f32,
+    emitter: usize,
+    depth: f32, // 0 is closest to the viewer
+    layer: u32,
+}
+
+/// Uniform value in [range.0, range.1), or range.0 if the range is empty.
+fn gen_in_range<R: Rng>(rng: &mut R, range: (f32, f32)) -> f32 {
+    if range.1 > range.0 {
+        rng.gen_range(range.0, range.1)
+    } else {
+        range.0
+    }
+}
+
+impl Particle {
+    /// Picks the attributes of a particle spawned by `emitter`.
+    fn new<R: Rng>(emitter_idx: usize, emitter: &EmitterConfig, rng: &mut R) -> Particle {
+        //
+        // corelate mass with radius so larger balls are heavier
+        const PARTICLE_MASS_MULTIPLIER: f32 = 0.001f32;
+        let radius = gen_in_range(rng, emitter.radius);
+        let radius_range = emitter.radius.1 - emitter.radius.0;
+
+        Particle {
+            radius,
+            mass: radius * PARTICLE_MASS_MULTIPLIER,
+            frame_rate: gen_in_range(rng, emitter.flipbook.fps),
+            start_frame: if emitter.flipbook.random_start {
+                rng.gen_range(0u32, emitter.flipbook.frames.max(1))
+            } else {
+                0
+            },
+            lifetime: gen_in_range(rng, emitter.lifetime),
+            emitter: emitter_idx,
+            depth: if radius_range > 0f32 {
+                1f32 - (radius - emitter.radius.0) / radius_range
+            } else {
+                0f32
+            },
+            layer: rng.gen_range(0u32, emitter.layers.max(1)),
+        }
+    }
+}
+
+/// A run of consecutive instances in the instance buffer, drawn with the same blend mode.
+#[derive(Copy, Clone, Debug)]
+struct ParticleBatch {
+    blend: BlendMode,
+    first: u32,
+    count: u32,
 }
 
 struct PhysicsState {
     particle_prev_state: Vec<ParticlePhysics>,
     particle_curr_state: Vec<ParticlePhysics>,
     particles: Vec<Particle>,
+    emitters: Vec<EmitterConfig>,
     world_size: Vec2F32,
-    accumulated_time: f32,
-    delta_step: f32,
+    clock: SimClock,
+    interaction: PointerInteraction,
+    spawn_budget: f32,
+    /// Vertical force applied to every particle.
+    gravity: f32,
+    /// Horizontal force applied to every particle.
+    wind: f32,
 }
 
 impl PhysicsState {
     const TARGET_FPS: i32 = 120;
+    /// Wall clock time a frame can spend on fast forward steps, so the window stays responsive.
+    const FAST_FORWARD_BUDGET: Duration = Duration::from_millis(12);
 
-    fn new(world_size: Vec2F32, particles: u32) -> Self {
+    fn new(world_size: Vec2F32, emitters: Vec<EmitterConfig>) -> Self {
         let mut rng = thread_rng();
 
-        let particles_phys = (0..particles)
-            .map(|_| ParticlePhysics {
-                speed: 0f32,
-                position: Vec2F32 {
-                    x: rng.gen_range(0f32, world_size.x),
-                    y: world_size.y,
-                },
-                velocity: Vec2F32::default(),
-                forces: Vec2F32::default(),
-                rotation: rng.gen_range(0f32, 2f32 * std::f32::consts::PI),
+        let particles = emitters
+            .iter()
+            .enumerate()
+            .flat_map(|(emitter_idx, emitter)| {
+                (0..emitter.particles).map(move |_| (emitter_idx, emitter))
             })
+            .take(physics::MAX_PARTICLES as usize)
+            .map(|(emitter_idx, emitter)| Particle::new(emitter_idx, emitter, &mut rng))
+            .collect::<Vec<_>>();
+
+        let particles_phys = (0..particles.len())
+            .map(|_| ParticlePhysics::spawn(world_size, &mut rng))
             .collect::<Vec<_>>();
 
         Self {
-            particles: (0..particles)
-                .map(|_| {
-                    //
-                    // corelate mass with radius so larger balls are heavier
-                    const PARTICLE_MASS_MULTIPLIER: f32 = 0.001f32;
-                    let radius = rng.gen_range(16f32, 64f32);
-
-                    Particle {
-                        radius,
-                        mass: radius * PARTICLE_MASS_MULTIPLIER,
-                        texid: rng.gen_range(0u32, 3u32),
-                        gravity: Vec2F32::new(0f32, physics::GRAVITY_ACCEL),
-                    }
-                })
-                .collect(),
+            particles,
+            emitters,
             particle_prev_state: particles_phys.clone(),
             particle_curr_state: particles_phys,
             world_size: world_size,
-            accumulated_time: 0f32,
-            delta_step: 1f32 / Self::TARGET_FPS as f32,
+            clock: SimClock::new(1f32 / Self::TARGET_FPS as f32),
+            interaction: PointerInteraction::None,
+            spawn_budget: 0f32,
+            gravity: physics::GRAVITY_ACCEL,
+            wind: 0f32,
+        }
+    }
+
+    /// Respawns all the particles, the clock and the forces are kept.
+    fn reset(&mut self) {
+        let emitters = std::mem::take(&mut self.emitters);
+        let fresh = PhysicsState::new(self.world_size, emitters);
+
+        *self = PhysicsState {
+            clock: self.clock,
+            gravity: self.gravity,
+            wind: self.wind,
+            ..fresh
+        };
+    }
+
+    /// Index of the particle closest to `pos`, if `pos` falls inside it.
+    fn pick_particle(&self, pos: Vec2F32) -> Option<usize> {
+        self.particle_curr_state
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| (idx, (p.position - pos).len()))
+            .filter(|&(idx, dist)| dist <= self.particles[idx].radius)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(idx, _)| idx)
+    }
+
+    fn set_interaction(&mut self, interaction: PointerInteraction) {
+        if let PointerInteraction::Fling { particle, velocity } = interaction {
+            if let Some(p) = self.particle_curr_state.get_mut(particle) {
+                p.velocity = velocity;
+                p.speed = velocity.len();
+            }
+
+            self.interaction = PointerInteraction::None;
+        } else {
+            self.interaction = interaction;
+        }
+    }
+
+    /// Respawns the particle closest to the end of its life somewhere inside the disc.
+    fn recycle_particle(&mut self, center: Vec2F32, radius: f32) {
+        let grabbed = match self.interaction {
+            PointerInteraction::Drag { particle, .. } => Some(particle),
+            _ => None,
+        };
+
+        let oldest = (0..self.particles.len())
+            .filter(|&idx| Some(idx) != grabbed)
+            .max_by(|&a, &b| {
+                let life =
+                    |idx: usize| self.particle_curr_state[idx].age / self.particles[idx].lifetime;
+                life(a)
+                    .partial_cmp(&life(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+
+        if let Some(idx) = oldest {
+            let mut rng = thread_rng();
+            let theta = rng.gen_range(0f32, 2f32 * std::f32::consts::PI);
+            let dist = radius * rng.gen_range(0f32, 1f32).sqrt();
+            let dir = Vec2F32::new(theta.cos(), theta.sin());
+
+            let emitter_idx = self.particles[idx].emitter;
+            self.particles[idx] = Particle::new(emitter_idx, &self.emitters[emitter_idx], &mut rng);
+
+            let p = &mut self.particle_curr_state[idx];
+            *p = ParticlePhysics::spawn(self.world_size, &mut rng);
+            p.position = center + dir * dist;
+            p.velocity = dir * rng.gen_range(0f32, 150f32);
+            p.speed = p.velocity.len();
+            self.particle_prev_state[idx] = *p;
         }
     }
 
     fn integrate(&mut self, dt: f32) {
+        let interaction = self.interaction;
+        let gravity = Vec2F32::new(self.wind, self.gravity);
+
         (0..self.particle_curr_state.len()).for_each(|idx| {
             self.particle_prev_state[idx] = self.particle_curr_state[idx];
 
             let p = &mut self.particle_curr_state[idx];
             let pdata = &self.particles[idx];
-            p.compute_loads(pdata.gravity);
+
+            let external = match interaction {
+                PointerInteraction::Drag {
+                    particle,
+                    target,
+                    velocity,
+                } if particle == idx => {
+                    //
+                    // held by the pointer, does not age or move on its own
+                    p.position = target;
+                    p.velocity = velocity;
+                    p.speed = velocity.len();
+                    return;
+                }
+
+                PointerInteraction::Force {
+                    center,
+                    radius,
+                    strength,
+                } => {
+                    let to_center = center - p.position;
+                    let dist = to_center.len();
+                    if dist > 1f32 && dist < radius {
+                        //
+                        // scaled by mass so all particles get the same acceleration
+                        to_center * (strength * (1f32 - dist / radius) * pdata.mass / dist)
+                    } else {
+                        Vec2F32::default()
+                    }
+                }
+
+                _ => Vec2F32::default(),
+            };
+
+            p.compute_loads(gravity, external);
             p.update_body_euler(dt, pdata.mass);
             p.update_rotation(dt);
+            p.age += dt;
 
-            if p.position.x > self.world_size.x || p.position.y < 0f32 {
+            if p.position.x > self.world_size.x || p.position.y < 0f32 || p.age >= pdata.lifetime {
                 //
-                // reset particle
+                // reset particle, picking new attributes in case the emitter was changed
                 let mut rng = thread_rng();
-
-                *p = ParticlePhysics {
-                    speed: 0f32,
-                    position: Vec2F32 {
-                        x: rng.gen_range(0f32, self.world_size.x),
-                        y: self.world_size.y,
-                    },
-                    velocity: Vec2F32::default(),
-                    forces: Vec2F32::default(),
-                    rotation: rng.gen_range(0f32, 2f32 * std::f32::consts::PI),
-                };
+                let emitter_idx = pdata.emitter;
+                self.particles[idx] =
+                    Particle::new(emitter_idx, &self.emitters[emitter_idx], &mut rng);
+                *p = ParticlePhysics::spawn(self.world_size, &mut rng);
                 //
                 // also reset previous state otherwise it leads to incorrect positioning
                 // for the first time the reset particle is drawn
                 self.particle_prev_state[idx] = *p;
             }
         });
+
+        if let PointerInteraction::Spawn {
+            center,
+            radius,
+            rate,
+        } = interaction
+        {
+            self.spawn_budget += rate * dt;
+            while self.spawn_budget >= 1f32 {
+                self.spawn_budget -= 1f32;
+                self.recycle_particle(center, radius);
+            }
+        } else {
+            self.spawn_budget = 0f32;
+        }
     }
 
-    fn update(&mut self, frame_time: f32) -> f32 {
-        self.accumulated_time += frame_time;
+    /// Returns the particle indices in the order they must be drawn, along with the
+    /// batches covering them.
+    fn draw_order(&self) -> (Vec<usize>, Vec<ParticleBatch>) {
+        let mut emitters = (0..self.emitters.len()).collect::<Vec<_>>();
+        emitters.sort_by_key(|&e| self.emitters[e].blend.draw_priority());
+
+        let mut order = Vec::with_capacity(self.particles.len());
+        let mut batches = Vec::with_capacity(emitters.len());
+
+        emitters.iter().for_each(|&emitter_idx| {
+            let emitter = &self.emitters[emitter_idx];
+            let first = order.len();
+
+            order.extend(
+                self.particles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, p)| p.emitter == emitter_idx)
+                    .map(|(idx, _)| idx),
+            );
 
-        while self.accumulated_time >= self.delta_step {
-            //
-            // update sim with delta step
-            self.integrate(self.delta_step);
-            self.accumulated_time -= self.delta_step;
+            let group = &mut order[first..];
+            let back_to_front = |a: &usize, b: &usize| {
+                self.particles[*b]
+                    .depth
+                    .partial_cmp(&self.particles[*a].depth)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            };
+
+            if emitter.blend.is_order_dependent() {
+                match emitter.sort {
+                    SortMode::None => {}
+                    SortMode::BackToFront => group.sort_by(back_to_front),
+                    SortMode::Layer => group.sort_by(|a, b| {
+                        self.particles[*a]
+                            .layer
+                            .cmp(&self.particles[*b].layer)
+                            .then_with(|| back_to_front(a, b))
+                    }),
+                }
+            }
+
+            if !group.is_empty() {
+                batches.push(ParticleBatch {
+                    blend: emitter.blend,
+                    first: first as u32,
+                    count: group.len() as u32,
+                });
+            }
+        });
+
+        (order, batches)
+    }
+
+    /// Advances the simulation by a frame, returns the interpolation factor between the
+    /// previous and the current state.
+    fn update(&mut self, frame_time: f32) -> f32 {
+        let steps = self.clock.advance(frame_time);
+        (0..steps).for_each(|_| self.integrate(self.clock.step()));
+
+        if self.clock.is_fast_forwarding() {
+            let start = Instant::now();
+            while start.elapsed() < Self::FAST_FORWARD_BUDGET && self.clock.take_fast_forward_step()
+            {
+                self.integrate(self.clock.step());
+            }
         }
 
-        self.accumulated_time / self.delta_step
+        self.clock.alpha()
     }
 }
 
@@ -205,20 +479,26 @@ mod sprites {
 }
 
 struct RenderingState {
-    vertexbuffer: UniqueBuffer,
-    indexbuffer: UniqueBuffer,
-    instancebuffer: UniqueBuffer,
+    vertexbuffer: TypedBuffer<VertexPT>,
+    indexbuffer: TypedBuffer<u16>,
+    instancebuffer: TypedBuffer<ParticleGPU>,
+    frame_constants: UniformBuffer<FrameConstants>,
     vertexarray: UniqueVertexArray,
-    vertshader: UniqueShaderProgram,
-    fragshader: UniqueShaderProgram,
+    vertshader: Rc<UniqueShaderProgram>,
+    fragshader: Rc<UniqueShaderProgram>,
     pipeline: UniquePipeline,
     sprites: UniqueTexture,
-    sampler: UniqueSampler,
+    sampler: Rc<UniqueSampler>,
     elements: u32,
 }
 
 impl RenderingState {
-    fn load_cacodemons() -> Result<UniqueTexture, String> {
+    /// `InstanceOffset` in particles.vert, index of the first instance of a batch.
+    const INSTANCE_OFFSET_LOCATION: i32 = 0;
+    /// `AlphaCutoff` in particles.frag.
+    const ALPHA_CUTOFF_LOCATION: i32 = 0;
+
+    fn load_cacodemons() -> Result<UniqueTexture, RenderError> {
         // let
         let texarray = UniqueTexture::new(unsafe {
             let mut tex = 0u32;
@@ -234,7 +514,7 @@ impl RenderingState {
 
             tex
         })
-        .ok_or_else(|| "Failed to create sprites texture array!".to_string())?;
+        .ok_or(RenderError::ResourceCreation("sprites texture array"))?;
 
         let mut sprite_buffer: Vec<u8> = vec![
             0u8;
@@ -243,19 +523,34 @@ impl RenderingState {
                 * 4usize
         ];
 
-        (0..3).for_each(|sprite_idx| {
+        (0..3).try_for_each(|sprite_idx| {
             use png::Decoder;
             use std::fs::File;
 
             let sprite_path = format!("data/sprites/cacodemons/cacodemon{}.png", sprite_idx + 1);
-            let sprite_file = File::open(sprite_path).unwrap();
+            let sprite_file = File::open(&sprite_path).map_err(|e| RenderError::Io {
+                path: sprite_path.clone().into(),
+                source: e,
+            })?;
+            let invalid =
+                |msg: String| RenderError::InvalidAsset(format!("{}: {}", sprite_path, msg));
 
             let decoder = Decoder::new(sprite_file);
-            let (info, mut reader) = decoder.read_info().unwrap();
-            debug_assert!(info.width == sprites::CACODEMON_SPRITE_WIDTH as u32);
-            debug_assert!(info.height == sprites::CACODEMON_SPRITE_HEIGHT as u32);
+            let (info, mut reader) = decoder.read_info().map_err(|e| invalid(e.to_string()))?;
+            if info.width != sprites::CACODEMON_SPRITE_WIDTH as u32
+                || info.height != sprites::CACODEMON_SPRITE_HEIGHT as u32
+                || info.color_type != png::ColorType::RGBA
+            {
+                return Err(invalid(format!(
+                    "expected a {}x{} RGBA image",
+                    sprites::CACODEMON_SPRITE_WIDTH,
+                    sprites::CACODEMON_SPRITE_HEIGHT
+                )));
+            }
 
-            reader.next_frame(&mut sprite_buffer).unwrap();
+            reader
+                .next_frame(&mut sprite_buffer)
+                .map_err(|e| invalid(e.to_string()))?;
 
             unsafe {
                 gl::TextureSubImage3D(
@@ -272,7 +567,9 @@ impl RenderingState {
                     sprite_buffer.as_ptr() as *const gl::types::GLvoid,
                 );
             }
-        });
+
+            Ok(())
+        })?;
 
         Ok(texarray)
     }
@@ -298,7 +595,10 @@ impl RenderingState {
         (vertices, indices)
     }
 
-    pub fn new() -> Result<RenderingState, String> {
+    pub fn new(
+        samplers: &mut SamplerCache,
+        shaders: &mut ShaderCache,
+    ) -> Result<RenderingState, RenderError> {
         let quad_verts: [VertexPT; 4] = [
             VertexPT {
                 pos: Vec2F32::new(-1f32, -1f32),
@@ -320,77 +620,35 @@ impl RenderingState {
 
         let quad_indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
 
-        let vertexbuffer = UniqueBuffer::new(unsafe {
-            let mut buff = 0u32;
-            gl::CreateBuffers(1, &mut buff);
-            gl::NamedBufferStorage(
-                buff,
-                slice_bytes_len(&quad_verts) as isize,
-                quad_verts.as_ptr() as *const _,
-                0,
-            );
-            buff
-        })
-        .ok_or_else(|| "Failed to create vertex buffer".to_string())?;
-
-        let indexbuffer = UniqueBuffer::new(unsafe {
-            let mut buff = 0u32;
-            gl::CreateBuffers(1, &mut buff);
-            gl::NamedBufferStorage(
-                buff,
-                slice_bytes_len(&quad_indices) as isize,
-                quad_indices.as_ptr() as *const _,
-                0,
-            );
-            buff
-        })
-        .ok_or_else(|| "Failed to create index buffer".to_string())?;
-
-        let instancebuffer = UniqueBuffer::new(unsafe {
-            let mut buff = 0u32;
-            gl::CreateBuffers(1, &mut buff);
-            gl::NamedBufferStorage(
-                buff,
-                physics::MAX_PARTICLES as isize * std::mem::size_of::<ParticleGPU>() as isize,
-                std::ptr::null(),
-                gl::MAP_WRITE_BIT,
-            );
-            buff
-        })
-        .ok_or_else(|| "Failed to create instance buffer".to_string())?;
-
-        let vertexarray = UniqueVertexArray::new(unsafe {
-            let mut vao = 0u32;
-            gl::CreateVertexArrays(1, &mut vao);
-            gl::VertexArrayVertexBuffer(
-                vao,
-                0,
-                *vertexbuffer,
-                0,
-                std::mem::size_of::<VertexPT>() as i32,
-            );
-            gl::VertexArrayElementBuffer(vao, *indexbuffer);
-
-            gl::VertexArrayAttribFormat(vao, 0, 2, gl::FLOAT, gl::FALSE, 0);
-            gl::VertexArrayAttribBinding(vao, 0, 0);
-            gl::EnableVertexArrayAttrib(vao, 0);
-
-            gl::VertexArrayAttribFormat(vao, 1, 2, gl::FLOAT, gl::FALSE, 8);
-            gl::VertexArrayAttribBinding(vao, 1, 0);
-            gl::EnableVertexArrayAttrib(vao, 1);
-
-            vao
-        })
-        .ok_or_else(|| "Failed to create vertex array!".to_string())?;
+        let vertexbuffer = TypedBuffer::from_slice(&quad_verts, None)?;
+        let indexbuffer = TypedBuffer::from_slice(&quad_indices, None)?;
+        let instancebuffer =
+            TypedBuffer::with_capacity(physics::MAX_PARTICLES as usize, Some(BufferAccess::Write))?;
 
-        let vertshader = create_shader_program_from_string(
-            include_str!("../../data/shaders/particles.vert"),
+        let vertshader = shaders.get(
+            "particles.vert",
             ShaderType::Vertex,
+            &ShaderPermutation::new(),
         )?;
 
-        let fragshader = create_shader_program_from_string(
-            include_str!("../../data/shaders/particles.frag"),
+        let layout = VertexPT::layout();
+        layout.validate(**vertshader)?;
+        let frame_constants = UniformBuffer::new(
+            UniformBinding::FRAME,
+            &FrameConstants {
+                projection: mat4::consts::identity(),
+                world_size: Vec2F32::same(0f32),
+                time: 0f32,
+            },
+        )?;
+        frame_constants.attach(**vertshader)?;
+        let vertexarray =
+            layout.create_vertex_array(&[vertexbuffer.handle()], Some(indexbuffer.handle()))?;
+
+        let fragshader = shaders.get(
+            "particles.frag",
             ShaderType::Fragment,
+            &ShaderPermutation::new(),
         )?;
 
         let pipeline = PipelineBuilder::new()
@@ -399,12 +657,37 @@ impl RenderingState {
             .build()?;
 
         let sprites = Self::load_cacodemons()?;
-        let sampler = SamplerBuilder::new().build()?;
+        let sampler = samplers.get(&SamplerDescription::default())?;
+
+        label_object(
+            ObjectKind::Buffer,
+            vertexbuffer.handle(),
+            "particle quad vertices",
+        );
+        label_object(
+            ObjectKind::Buffer,
+            indexbuffer.handle(),
+            "particle quad indices",
+        );
+        label_object(
+            ObjectKind::Buffer,
+            instancebuffer.handle(),
+            "particle instances",
+        );
+        label_object(
+            ObjectKind::Buffer,
+            frame_constants.handle(),
+            "frame constants",
+        );
+        label_object(ObjectKind::VertexArray, *vertexarray, "particles");
+        label_object(ObjectKind::ProgramPipeline, *pipeline, "particles");
+        label_object(ObjectKind::Texture, *sprites, "cacodemon sprites");
 
         Ok(RenderingState {
             vertexbuffer,
             indexbuffer,
             instancebuffer,
+            frame_constants,
             vertexarray,
             vertshader,
             fragshader,
@@ -419,133 +702,881 @@ impl RenderingState {
 pub struct ParticlesSim {
     phys: RefCell<PhysicsState>,
     draw: RenderingState,
+    batches: RefCell<Vec<ParticleBatch>>,
+    camera: RefCell<Camera2D>,
+    tools: RefCell<ToolState>,
+    bindings: KeyBindings,
+    screenshot_requested: Cell<bool>,
+    show_hud: Cell<bool>,
+    show_debug_draw: Cell<bool>,
+    ui: RefCell<Ui>,
+    ui_renderer: UiRenderer,
+    debug_draw: RefCell<DebugDrawList>,
+    debug_renderer: DebugDrawRenderer,
+    /// Text next to the particles, in screen space.
+    labels: RefCell<UiDrawList>,
+    profiler: Profiler,
+    gpu_timer: RefCell<GpuTimer>,
+    render_states: RefCell<RenderStateCache>,
+    post: RefCell<PostProcessor>,
     prev_time: Cell<Instant>,
     curr_time: Cell<Instant>,
 }
 
 impl ParticlesSim {
-    pub fn new(width: i32, height: i32) -> Result<ParticlesSim, String> {
-        let draw = RenderingState::new()?;
+    const KEY_BINDINGS_FILE: &'static str = "data/config/keybindings.cfg";
+    const SHADER_DIR: &'static str = "data/shaders";
+    /// Steps run by the fast forward command, 10 simulated seconds.
+    const FAST_FORWARD_STEPS: u32 = 10 * PhysicsState::TARGET_FPS as u32;
+    /// Frames kept for the frame time graphs.
+    const PROFILER_SAMPLES: usize = 240;
+    /// Events kept for the timeline dump, about 10 seconds worth of frames.
+    const PROFILER_EVENTS: usize = 16384;
+    const PANEL_WIDTH: f32 = 360f32;
+    const FONT_FILE: &'static str = "data/fonts/DejaVuSansMono.ttf";
+    const FONT_SIZE: f32 = 16f32;
+
+    /// `program_cache` is the directory where linked program binaries are kept for later
+    /// launches on the same driver, every program is compiled from source if it is `None`.
+    pub fn new(
+        width: i32,
+        height: i32,
+        program_cache: Option<&Path>,
+    ) -> Result<ParticlesSim, AppError> {
+        let bindings = KeyBindings::load(Self::KEY_BINDINGS_FILE).map_err(AppError::Config)?;
+        let mut samplers = SamplerCache::new();
+        let mut preprocessor = ShaderPreprocessor::new();
+        preprocessor.add_search_path(Self::SHADER_DIR);
+        let mut shaders = ShaderCache::new(preprocessor);
+        if let Some(dir) = program_cache {
+            match ProgramBinaryCache::open(dir) {
+                Ok(binaries) => {
+                    shaders.set_binary_cache(Some(binaries));
+                }
+                Err(e) => log::warn!("Program binary cache disabled: {}", e),
+            }
+        }
+        let draw = RenderingState::new(&mut samplers, &mut shaders)?;
+        let ui = match FontAtlas::load_ttf(Self::FONT_FILE, Self::FONT_SIZE, "") {
+            Ok(font) => Ui::with_font(
+                UiStyle {
+                    text_scale: 1f32,
+                    ..UiStyle::default()
+                },
+                font,
+            ),
+            Err(e) => {
+                log::warn!("{}, using the builtin font", e);
+                Ui::new(UiStyle::default())
+            }
+        };
+        let ui_renderer = UiRenderer::new(ui.font(), &mut samplers, &mut shaders)?;
+        let labels = UiDrawList::new(ui.font());
+        let debug_renderer = DebugDrawRenderer::new(&mut shaders)?;
+        let post = PostProcessor::new(width, height, &mut samplers, &mut shaders)?;
+        let world_size = Vec2F32::new(width as f32, height as f32) * physics::WORLD_SCALE;
+        let mut camera = Camera2D::new(width, height);
+        camera.fit(world_size);
+
         Ok(ParticlesSim {
             phys: RefCell::new(PhysicsState::new(
-                Vec2F32::new(width as f32, height as f32),
-                physics::MAX_PARTICLES,
+                world_size,
+                EmitterConfig::default_emitters(),
             )),
             draw,
+            batches: RefCell::new(Vec::new()),
+            camera: RefCell::new(camera),
+            tools: RefCell::new(ToolState::new()),
+            bindings,
+            screenshot_requested: Cell::new(false),
+            show_hud: Cell::new(true),
+            show_debug_draw: Cell::new(false),
+            ui: RefCell::new(ui),
+            ui_renderer,
+            debug_draw: RefCell::new(DebugDrawList::new()),
+            debug_renderer,
+            labels: RefCell::new(labels),
+            profiler: Profiler::new(Self::PROFILER_SAMPLES, Self::PROFILER_EVENTS),
+            gpu_timer: RefCell::new(GpuTimer::new()),
+            render_states: RefCell::new(RenderStateCache::new()),
+            post: RefCell::new(post),
             prev_time: Cell::new(Instant::now()),
             curr_time: Cell::new(Instant::now()),
         })
     }
 
-    fn handler_loop_event(&self, _evt: LoopEventData) {
+    fn handler_loop_event(&self, evt: LoopEventData) {
         let new_time = Instant::now();
         const MAX_FRAME_TIME: f32 = 0.25f32;
-        let frame_time =
-            MAX_FRAME_TIME.min((new_time - self.curr_time.get()).as_millis() as f32 * 0.001f32); // frame time is in seconds
-        self.curr_time.set(new_time);
+        let prev_frame_start = self.curr_time.replace(new_time);
+        let elapsed = (new_time - prev_frame_start).as_secs_f32();
+        let frame_time = MAX_FRAME_TIME.min(elapsed); // frame time is in seconds
 
-        let bounds = self.phys.borrow().world_size;
-        let proj_matrix = projection::orthographic(0f32, 0f32, bounds.x, bounds.y, -1f32, 1f32);
+        self.profiler.record(
+            "frame",
+            Timeline::Cpu,
+            prev_frame_start,
+            elapsed as f64 * 1.0e6f64,
+        );
+        self.profiler.begin_frame();
 
-        self.update(frame_time, &proj_matrix);
+        let proj_matrix = self.camera.borrow().projection();
+
+        self.update(frame_time);
         self.draw();
+        self.collect_gpu_timings();
+
+        let _overlay_scope = self.profiler.scope("overlay");
+
+        if self.show_debug_draw.get() {
+            let mut debug_draw = self.debug_draw.borrow_mut();
+            debug_draw.clear();
+            self.build_debug_draw(&mut debug_draw);
+            self.debug_renderer.render(&debug_draw, &proj_matrix);
+
+            let mut labels = self.labels.borrow_mut();
+            labels.clear();
+            self.build_labels(&mut labels, self.ui.borrow().font());
+            self.ui_renderer
+                .render(&labels, evt.surface_width, evt.surface_height);
+        }
+
+        if self.show_hud.get() {
+            let mut ui = self.ui.borrow_mut();
+            ui.begin_frame();
+            self.build_ui(&mut ui, evt.surface_width as f32);
+            ui.end_frame();
+            self.ui_renderer
+                .render(ui.draw_list(), evt.surface_width, evt.surface_height);
+        }
+
+        if self.screenshot_requested.replace(false) {
+            match crate::screenshot::save_screenshot(evt.surface_width, evt.surface_height) {
+                Ok(file_name) => log::info!("Screenshot saved to {}", file_name),
+                Err(e) => log::error!("Screenshot failed: {}", e),
+            }
+        }
     }
 
-    fn draw(&self) {
-        const CLEAR_COLOR: [f32; 4] = [0f32, 0f32, 0f32, 1f32];
+    /// Hands the GPU timings of the previous frames to the profiler.
+    fn collect_gpu_timings(&self) {
+        self.gpu_timer
+            .borrow_mut()
+            .collect()
+            .into_iter()
+            .for_each(|t| {
+                self.profiler.record_for_frame(
+                    t.name,
+                    Timeline::Gpu,
+                    t.frame,
+                    t.submitted,
+                    t.micros,
+                )
+            });
+    }
 
-        unsafe {
-            gl::ClearNamedFramebufferfv(0, gl::COLOR, 0, CLEAR_COLOR.as_ptr());
-            gl::ClearNamedFramebufferfi(0, gl::DEPTH_STENCIL, 0, 1f32, 0);
+    fn dump_profile(&self) {
+        match self.profiler.dump() {
+            Ok((csv_file, trace_file)) => {
+                log::info!("Profile saved to {} and {}", csv_file, trace_file)
+            }
+            Err(e) => log::error!("Profile dump failed: {}", e),
+        }
+    }
 
-            gl::BindTextureUnit(0, *self.draw.sprites);
-            gl::BindSampler(0, *self.draw.sampler);
-            gl::BindVertexArray(*self.draw.vertexarray);
-            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, *self.draw.instancebuffer);
-            gl::BindProgramPipeline(*self.draw.pipeline);
-            gl::Enable(gl::BLEND);
-            gl::BlendEquation(gl::FUNC_ADD);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
-            gl::DrawElementsInstanced(
-                gl::TRIANGLES,
-                self.draw.elements as i32,
-                gl::UNSIGNED_SHORT,
-                std::ptr::null(),
-                self.phys.borrow().particles.len() as i32,
+    /// World space overlay: reference grid, world bounds, particle shapes and velocities,
+    /// the bounding box of all the particles and the area of the current tool.
+    fn build_debug_draw(&self, dl: &mut DebugDrawList) {
+        const GRID_CELL: f32 = 128f32;
+        /// Velocity arrows show the distance covered in this many seconds.
+        const VELOCITY_SCALE: f32 = 0.25f32;
+
+        let grid_color = RGBAColorF32::new_with_alpha(0.5f32, 0.5f32, 0.5f32, 0.25f32);
+        let bounds_color = RGBAColorF32::new(1f32, 1f32, 0f32);
+        let shape_color = RGBAColorF32::new_with_alpha(0f32, 1f32, 0.25f32, 0.6f32);
+        let velocity_color = RGBAColorF32::new(0f32, 0.75f32, 1f32);
+        let aabb_color = RGBAColorF32::new(1f32, 0.5f32, 0f32);
+        let tool_color = RGBAColorF32::new_with_alpha(1f32, 0.25f32, 1f32, 0.8f32);
+
+        let phys = self.phys.borrow();
+        dl.grid(
+            self.camera.borrow().visible_world_rect(),
+            GRID_CELL,
+            grid_color,
+        );
+        dl.rect(
+            RectangleF32::new(0f32, 0f32, phys.world_size.x, phys.world_size.y),
+            bounds_color,
+        );
+
+        let mut aabb: Option<(Vec2F32, Vec2F32)> = None;
+        phys.particle_curr_state
+            .iter()
+            .zip(phys.particles.iter())
+            .for_each(|(state, particle)| {
+                dl.circle_segments(state.position, particle.radius, 12, shape_color);
+                dl.arrow(
+                    state.position,
+                    state.position + state.velocity * VELOCITY_SCALE,
+                    velocity_color,
+                );
+
+                let extent = Vec2F32::same(particle.radius);
+                let (lo, hi) = (state.position - extent, state.position + extent);
+                aabb = Some(aabb.map_or((lo, hi), |(min, max)| {
+                    (
+                        Vec2F32::new(min.x.min(lo.x), min.y.min(lo.y)),
+                        Vec2F32::new(max.x.max(hi.x), max.y.max(hi.y)),
+                    )
+                }));
+            });
+
+        if let Some((min, max)) = aabb {
+            dl.rect(
+                RectangleF32::from_points(min.x, min.y, max.x, max.y),
+                aabb_color,
             );
+        }
 
-            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, 0);
-            gl::BindVertexArray(0);
-            gl::BindProgramPipeline(0);
+        let tools = self.tools.borrow();
+        let tool_radius = match tools.tool() {
+            MouseTool::Attract | MouseTool::Repel => tools.force_radius,
+            MouseTool::Spawn => ToolState::SPAWN_RADIUS,
+            MouseTool::Drag => 0f32,
+        };
+
+        if tool_radius > 0f32 {
+            dl.circle(tools.cursor(), tool_radius, tool_color);
         }
+        dl.cross(tools.cursor(), 16f32, tool_color);
+    }
+
+    /// Describes the particle under the cursor.
+    fn build_labels(&self, labels: &mut UiDrawList, font: &FontAtlas) {
+        let label_color = RGBAColorF32::new(1f32, 1f32, 0.6f32);
+        let label_bg = RGBAColorF32::new_with_alpha(0f32, 0f32, 0f32, 0.6f32);
+
+        let phys = self.phys.borrow();
+        let camera = self.camera.borrow();
+        let particle = match phys.pick_particle(self.tools.borrow().cursor()) {
+            Some(particle) => particle,
+            None => return,
+        };
+
+        let state = &phys.particle_curr_state[particle];
+        let fixed = &phys.particles[particle];
+        let text = format!(
+            "#{} emitter {}\nr = {:.1}, v = {:.1}\nage {:.1} / {:.1} s",
+            particle, fixed.emitter, fixed.radius, state.speed, state.age, fixed.lifetime
+        );
+
+        let pos = camera.world_to_screen(
+            state.position
+                + Vec2F32::new(fixed.radius, fixed.radius) * std::f32::consts::FRAC_1_SQRT_2,
+        );
+        let size = font.measure(&text, 1f32);
+        labels.rect_filled(
+            RectangleF32::new(pos.x - 4f32, pos.y - 4f32, size.x + 8f32, size.y + 8f32),
+            label_bg,
+        );
+        labels.text(font, pos, &text, 1f32, label_color);
     }
 
-    fn update(&self, delta: f32, proj_view: &Mat4F32) {
-        let frame_interp = self.phys.borrow_mut().update(delta);
+    fn build_ui(&self, ui: &mut Ui, screen_width: f32) {
+        let margin = 10f32;
+
+        if ui.begin_panel("Stats", Vec2F32::new(margin, margin), Self::PANEL_WIDTH) {
+            let tracks = self.profiler.tracks();
+            let millis = |name: &str, timeline: Timeline| {
+                tracks
+                    .iter()
+                    .find(|t| t.name == name && t.timeline == timeline)
+                    .map(|t| {
+                        (
+                            t.stats.values().iter().map(|v| v * 0.001f32).collect(),
+                            t.stats.max() * 0.001f32,
+                        )
+                    })
+                    .unwrap_or_else(|| (Vec::new(), 0f32))
+            };
+
+            let (frame_ms, frame_max) = millis("frame", Timeline::Cpu);
+            let avg_frame = frame_ms.iter().sum::<f32>() / frame_ms.len().max(1) as f32;
+
+            ui.label(&format!(
+                "FPS: {:.0} ({:.2} ms)",
+                if avg_frame > 0f32 {
+                    1000f32 / avg_frame
+                } else {
+                    0f32
+                },
+                avg_frame
+            ));
+            ui.plot_lines(
+                &format!("frame ms, max {:.2}", frame_max),
+                &frame_ms,
+                0f32,
+                33.3f32,
+                48f32,
+            );
+
+            let (update_ms, update_max) = millis("update", Timeline::Cpu);
+            ui.plot_lines(
+                &format!("update ms, max {:.2}", update_max),
+                &update_ms,
+                0f32,
+                16.6f32,
+                48f32,
+            );
+
+            let (draw_ms, draw_max) = millis("draw", Timeline::Gpu);
+            ui.plot_lines(
+                &format!("GPU draw ms, max {:.2}", draw_max),
+                &draw_ms,
+                0f32,
+                16.6f32,
+                48f32,
+            );
+
+            tracks.iter().filter(|t| t.name != "frame").for_each(|t| {
+                ui.label_colored(
+                    &format!("{} {:?}: {:.0} us", t.name, t.timeline, t.stats.average()),
+                    ui.style().text_dim,
+                )
+            });
+
+            if ui.button("Dump profile") {
+                self.dump_profile();
+            }
+            ui.separator();
+
+            let mut phys = self.phys.borrow_mut();
+            ui.label(&format!(
+                "Particles: {} / {}",
+                phys.particles.len(),
+                physics::MAX_PARTICLES
+            ));
+            let requested: u32 = phys.emitters.iter().map(|e| e.particles).sum();
+            if requested > physics::MAX_PARTICLES {
+                ui.label_colored(
+                    &format!("{} requested, capacity exceeded", requested),
+                    ui.style().text_dim,
+                );
+            }
+            ui.label(&format!("Sim time: {:.1} s", phys.clock.sim_time()));
+
+            let mut paused = phys.clock.is_paused();
+            if ui.checkbox("Paused", &mut paused) {
+                phys.clock.set_paused(paused);
+            }
+
+            let mut slow_motion = phys.clock.is_slow_motion();
+            if ui.checkbox("Slow motion", &mut slow_motion) {
+                phys.clock.toggle_slow_motion();
+            }
+
+            let mut time_scale = phys.clock.time_scale();
+            if ui.slider_f32(
+                "Time scale",
+                &mut time_scale,
+                SimClock::MIN_TIME_SCALE,
+                SimClock::MAX_TIME_SCALE,
+            ) {
+                phys.clock.set_time_scale(time_scale);
+            }
+
+            if ui.button("Step") {
+                phys.clock.request_step();
+            }
+
+            if phys.clock.is_fast_forwarding() {
+                if ui.button(&format!(
+                    "Cancel fast forward ({})",
+                    phys.clock.fast_forward_remaining()
+                )) {
+                    phys.clock.cancel_fast_forward();
+                }
+            } else if ui.button("Fast forward 10 s") {
+                phys.clock.fast_forward(Self::FAST_FORWARD_STEPS);
+            }
+        }
+        ui.end_panel();
 
-        if let Some(vbmap) = UniqueBufferMapping::new(
-            *self.draw.instancebuffer,
-            gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_BUFFER_BIT,
+        if ui.begin_panel(
+            "Forces",
+            Vec2F32::new(margin * 2f32 + Self::PANEL_WIDTH, margin),
+            Self::PANEL_WIDTH,
         ) {
-            let num_particles = self.phys.borrow().particles.len();
+            {
+                let mut phys = self.phys.borrow_mut();
+                ui.slider_f32("Gravity", &mut phys.gravity, -50f32, 50f32);
+                ui.slider_f32("Wind", &mut phys.wind, -50f32, 50f32);
+            }
+            ui.separator();
 
-            let instances = unsafe {
-                std::slice::from_raw_parts_mut(vbmap.memory() as *mut ParticleGPU, num_particles)
-            };
+            let mut tools = self.tools.borrow_mut();
+            ui.label(&format!("Tool: {:?}", tools.tool()));
+            ui.slider_f32("Tool radius", &mut tools.force_radius, 16f32, 1024f32);
+            ui.slider_f32("Tool strength", &mut tools.force_strength, 0f32, 20000f32);
+            ui.slider_f32("Spawn rate", &mut tools.spawn_rate, 0f32, 1000f32);
+        }
+        ui.end_panel();
 
-            let phys = self.phys.borrow();
+        if ui.begin_panel(
+            "Post processing",
+            Vec2F32::new(margin * 3f32 + Self::PANEL_WIDTH * 2f32, margin),
+            Self::PANEL_WIDTH,
+        ) {
+            let mut post = self.post.borrow_mut();
+            let settings = post.settings_mut();
+
+            ui.checkbox("Enabled", &mut settings.enabled);
+            ui.separator();
+
+            ui.checkbox("Bloom", &mut settings.bloom);
+            ui.slider_f32("Bloom threshold", &mut settings.bloom_threshold, 0f32, 4f32);
+            ui.slider_f32("Bloom intensity", &mut settings.bloom_intensity, 0f32, 4f32);
+            ui.slider_u32("Blur passes", &mut settings.bloom_blur_passes, 1, 8);
+            ui.separator();
+
+            ui.checkbox("Motion trails", &mut settings.trails);
+            ui.slider_f32(
+                "Trail persistence",
+                &mut settings.trail_persistence,
+                0f32,
+                0.99f32,
+            );
+            ui.separator();
+
+            ui.checkbox("Color grading", &mut settings.color_grading);
+            if ui.button(&format!("Palette: {:?}", settings.palette)) {
+                settings.palette = settings.palette.next();
+            }
+            ui.slider_f32(
+                "Grading strength",
+                &mut settings.grading_strength,
+                0f32,
+                1f32,
+            );
+            ui.separator();
+
+            if ui.button(&format!("Tone mapping: {:?}", settings.tone_mapper)) {
+                settings.tone_mapper = settings.tone_mapper.next();
+            }
+            ui.slider_f32("Exposure", &mut settings.exposure, 0.1f32, 8f32);
+        }
+        ui.end_panel();
+
+        if ui.begin_panel(
+            "Emitters",
+            Vec2F32::new(
+                (screen_width - Self::PANEL_WIDTH - margin).max(margin),
+                margin,
+            ),
+            Self::PANEL_WIDTH,
+        ) {
+            {
+                let mut phys = self.phys.borrow_mut();
+                let mut requested: u32 = phys.emitters.iter().map(|e| e.particles).sum();
+                for (idx, emitter) in phys.emitters.iter_mut().enumerate() {
+                    ui.label_colored(&format!("Emitter {}", idx), ui.style().accent);
+
+                    if ui.button(&format!("Blend: {:?}##{}", emitter.blend, idx)) {
+                        emitter.blend = emitter.blend.next();
+                    }
+
+                    //
+                    // an emitter can only take the capacity the other emitters leave
+                    let others = requested - emitter.particles;
+                    ui.slider_u32(
+                        &format!("Particles##{}", idx),
+                        &mut emitter.particles,
+                        0,
+                        physics::MAX_PARTICLES.saturating_sub(others),
+                    );
+                    requested = others + emitter.particles;
+                    Self::range_sliders(ui, "Radius", idx, &mut emitter.radius, 1f32, 128f32);
+                    Self::range_sliders(ui, "Lifetime", idx, &mut emitter.lifetime, 0.1f32, 10f32);
+                    Self::range_sliders(ui, "FPS", idx, &mut emitter.flipbook.fps, 0f32, 30f32);
+                    ui.slider_f32(
+                        &format!("Fade in##{}", idx),
+                        &mut emitter.alpha_fade.fade_in,
+                        0f32,
+                        1f32,
+                    );
+                    ui.slider_f32(
+                        &format!("Fade out##{}", idx),
+                        &mut emitter.alpha_fade.fade_out,
+                        0f32,
+                        1f32,
+                    );
+                    ui.separator();
+                }
+            }
+
+            ui.label_colored("Counts and sizes apply on respawn", ui.style().text_dim);
+            if ui.button("Respawn all") {
+                self.phys.borrow_mut().reset();
+            }
+        }
+        ui.end_panel();
+    }
 
-            instances
-                .iter_mut()
-                .enumerate()
-                .for_each(|(idx, gpu_particle)| {
-                    let fixed_data = &phys.particles[idx];
+    /// A pair of sliders editing a (min, max) range, keeping min <= max.
+    fn range_sliders(
+        ui: &mut Ui,
+        label: &str,
+        idx: usize,
+        range: &mut (f32, f32),
+        min: f32,
+        max: f32,
+    ) {
+        if ui.slider_f32(&format!("{} min##{}", label, idx), &mut range.0, min, max) {
+            range.1 = range.1.max(range.0);
+        }
 
-                    let current_pos = Vec2F32 {
-                        y: phys.world_size.y - phys.particle_curr_state[idx].position.y,
-                        ..phys.particle_curr_state[idx].position
-                    };
+        if ui.slider_f32(&format!("{} max##{}", label, idx), &mut range.1, min, max) {
+            range.0 = range.0.min(range.1);
+        }
+    }
 
-                    let previous_pos = Vec2F32 {
-                        y: phys.world_size.y - phys.particle_prev_state[idx].position.y,
-                        ..phys.particle_prev_state[idx].position
-                    };
+    fn run_action(&self, action: Action) {
+        match action {
+            Action::Quit => sys::request_quit(),
+            Action::ToggleFullscreen => sys::request_fullscreen_toggle(),
+            Action::Pause => self.phys.borrow_mut().clock.toggle_pause(),
+            Action::Step => self.phys.borrow_mut().clock.request_step(),
+            Action::Reset => self.phys.borrow_mut().reset(),
+            Action::SpeedUp => self.phys.borrow_mut().clock.scale_time(2f32),
+            Action::SlowDown => self.phys.borrow_mut().clock.scale_time(0.5f32),
+            Action::SlowMotion => self.phys.borrow_mut().clock.toggle_slow_motion(),
+            Action::FastForward => {
+                let mut phys = self.phys.borrow_mut();
+                if phys.clock.is_fast_forwarding() {
+                    phys.clock.cancel_fast_forward();
+                } else {
+                    phys.clock.fast_forward(Self::FAST_FORWARD_STEPS);
+                }
+            }
+            Action::Screenshot => self.screenshot_requested.set(true),
+            Action::ToggleHud => self.show_hud.set(!self.show_hud.get()),
+            Action::ToggleDebugDraw => self.show_debug_draw.set(!self.show_debug_draw.get()),
+            Action::DumpProfile => self.dump_profile(),
+            Action::TogglePostProcess => {
+                let mut post = self.post.borrow_mut();
+                let settings = post.settings_mut();
+                settings.enabled = !settings.enabled;
+            }
+            Action::ToolAttract => self.tools.borrow_mut().select(MouseTool::Attract),
+            Action::ToolRepel => self.tools.borrow_mut().select(MouseTool::Repel),
+            Action::ToolSpawn => self.tools.borrow_mut().select(MouseTool::Spawn),
+            Action::ToolDrag => self.tools.borrow_mut().select(MouseTool::Drag),
+        }
+    }
 
-                    let translation =
-                        current_pos * frame_interp + (1f32 - frame_interp) * previous_pos;
+    fn draw(&self) {
+        const CLEAR_COLOR: [f32; 4] = [0f32, 0f32, 0f32, 1f32];
 
-                    let previous_rot = phys.particle_prev_state[idx].rotation;
-                    let current_rot = phys.particle_curr_state[idx].rotation;
+        let _scope = self.profiler.scope("draw");
+        let mut gpu_timer = self.gpu_timer.borrow_mut();
+        gpu_timer.begin("draw", self.profiler.frame());
+
+        let mut render_states = self.render_states.borrow_mut();
+        render_states.set_rasterizer(&RasterizerState::solid());
+        render_states.set_depth_stencil(&DepthStencilState::disabled());
+
+        let viewport = self.camera.borrow().viewport();
+        let screen = DefaultFramebuffer::new(viewport.x as i32, viewport.y as i32);
+        let mut post = self.post.borrow_mut();
+        let post_enabled = post.settings().enabled;
+
+        if post_enabled {
+            post.begin_scene(CLEAR_COLOR);
+        } else {
+            screen.bind();
+            screen.clear_color(0, CLEAR_COLOR);
+            screen.clear_depth_stencil(1f32, 0);
+        }
 
-                    let rotation =
-                        current_rot * frame_interp + (1f32 - frame_interp) * previous_rot;
-                    let particle_scale = fixed_data.radius;
+        let frame_constants = {
+            let phys = self.phys.borrow();
+            FrameConstants {
+                projection: self.camera.borrow().projection(),
+                world_size: phys.world_size,
+                time: phys.clock.sim_time() as f32,
+            }
+        };
+        if let Err(e) = self.draw.frame_constants.update(&frame_constants) {
+            log::error!("Failed to update the frame constants: {}", e);
+        }
+        self.draw.frame_constants.bind();
 
-                    use mat2x3::transforms;
+        unsafe {
+            gl::BindTextureUnit(0, *self.draw.sprites);
+            gl::BindSampler(0, **self.draw.sampler);
+            gl::BindVertexArray(*self.draw.vertexarray);
+            gl::BindBufferBase(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                self.draw.instancebuffer.handle(),
+            );
+            gl::BindProgramPipeline(*self.draw.pipeline);
 
-                    let world_transform = transforms::translate(translation.x, translation.y)
-                        * transforms::rotate(rotation)
-                        * transforms::uniform_scale(particle_scale);
+            self.batches.borrow().iter().for_each(|batch| {
+                render_states.set_blend(&batch.blend.blend_state());
+                gl::ProgramUniform1ui(
+                    **self.draw.vertshader,
+                    RenderingState::INSTANCE_OFFSET_LOCATION,
+                    batch.first,
+                );
+                gl::ProgramUniform1f(
+                    **self.draw.fragshader,
+                    RenderingState::ALPHA_CUTOFF_LOCATION,
+                    batch.blend.alpha_cutoff(),
+                );
 
-                    gpu_particle.transform = (*proj_view * world_transform.into()).transpose();
-                    gpu_particle.texid = fixed_data.texid;
-                });
+                gl::DrawElementsInstanced(
+                    gl::TRIANGLES,
+                    self.draw.elements as i32,
+                    gl::UNSIGNED_SHORT,
+                    std::ptr::null(),
+                    batch.count as i32,
+                );
+            });
+
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, 0, 0);
+            gl::BindVertexArray(0);
+            gl::BindProgramPipeline(0);
+        }
+
+        gpu_timer.end();
+        check_gl_errors("ParticlesSim::draw");
+
+        if post_enabled {
+            let _scope = self.profiler.scope("post");
+            gpu_timer.begin("post", self.profiler.frame());
+            post.finish(&mut render_states, &screen);
+            gpu_timer.end();
+        }
+    }
+
+    fn update(&self, delta: f32) {
+        let _scope = self.profiler.scope("update");
+
+        let frame_interp = {
+            let _scope = self.profiler.scope("simulate");
+            let interaction = self.tools.borrow_mut().interaction();
+            self.phys.borrow_mut().set_interaction(interaction);
+            self.phys.borrow_mut().update(delta)
+        };
+
+        let _upload_scope = self.profiler.scope("upload");
+        let mut gpu_timer = self.gpu_timer.borrow_mut();
+        gpu_timer.begin("upload", self.profiler.frame());
+
+        match self.draw.instancebuffer.map_write() {
+            Err(e) => log::error!("Failed to map the particle instance buffer: {}", e),
+            Ok(mut instances) => {
+                let phys = self.phys.borrow();
+                let (draw_order, batches) = phys.draw_order();
+                self.batches.replace(batches);
+
+                instances
+                    .iter_mut()
+                    .zip(draw_order.iter())
+                    .for_each(|(gpu_particle, &idx)| {
+                        let fixed_data = &phys.particles[idx];
+
+                        let current_pos = phys.particle_curr_state[idx].position;
+                        let previous_pos = phys.particle_prev_state[idx].position;
+
+                        let translation =
+                            current_pos * frame_interp + (1f32 - frame_interp) * previous_pos;
+
+                        let previous_rot = phys.particle_prev_state[idx].rotation;
+                        let current_rot = phys.particle_curr_state[idx].rotation;
+
+                        let rotation =
+                            current_rot * frame_interp + (1f32 - frame_interp) * previous_rot;
+                        let particle_scale = fixed_data.radius;
+
+                        let age = phys.particle_curr_state[idx].age * frame_interp
+                            + (1f32 - frame_interp) * phys.particle_prev_state[idx].age;
+                        let speed = phys.particle_curr_state[idx].speed * frame_interp
+                            + (1f32 - frame_interp) * phys.particle_prev_state[idx].speed;
+
+                        use mat2x3::transforms;
+
+                        let world_transform = transforms::translate(translation.x, translation.y)
+                            * transforms::rotate(rotation)
+                            * transforms::uniform_scale(particle_scale);
+
+                        gpu_particle.transform = Mat4F32::from(world_transform).transpose();
+                        let emitter = &phys.emitters[fixed_data.emitter];
+                        gpu_particle.color =
+                            emitter.particle_color(age, fixed_data.lifetime, speed);
+
+                        let (texid, texid_next, frame_blend) = emitter.flipbook.frame(
+                            age,
+                            fixed_data.frame_rate,
+                            fixed_data.start_frame,
+                        );
+                        gpu_particle.texid = texid;
+                        gpu_particle.texid_next = texid_next;
+                        gpu_particle.frame_blend = frame_blend;
+                    });
+            }
         }
+
+        gpu_timer.end();
     }
 
     fn handler_resize_event(&self, re: WindowConfigureEventData) {
-        self.phys.borrow_mut().world_size = Vec2F32::new(re.width as f32, re.height as f32);
+        self.camera.borrow_mut().set_viewport(re.width, re.height);
+        if let Err(e) = self.post.borrow_mut().resize(re.width, re.height) {
+            log::error!("Failed to resize the post-processing targets: {}", e);
+        }
 
         unsafe {
             gl::ViewportIndexedf(0, 0f32, 0f32, re.width as f32, re.height as f32);
         }
     }
 
+    fn handler_input_event(&self, ie: &InputEventData) {
+        if let InputEventData::Key(ke) = ie {
+            if let Some(action) = self.bindings.action_for(ke) {
+                self.run_action(action);
+            }
+            return;
+        }
+
+        let mut camera = self.camera.borrow_mut();
+        let mut tools = self.tools.borrow_mut();
+
+        match ie {
+            InputEventData::MouseButton(mbe) => {
+                if !camera.handle_mouse_button(mbe) {
+                    let phys = self.phys.borrow();
+                    tools.handle_mouse_button(mbe, &camera, |pos| phys.pick_particle(pos));
+                }
+            }
+            InputEventData::MouseMotion(mme) => {
+                if !camera.handle_mouse_motion(mme) {
+                    tools.handle_mouse_motion(mme, &camera);
+                }
+            }
+            InputEventData::MouseWheel(mwe) => camera.handle_mouse_wheel(mwe),
+            InputEventData::Key(_) => {}
+        }
+    }
+
     pub fn main_loop(&self, evt: &Event) {
         match evt {
             Event::Loop(el) => self.handler_loop_event(*el),
             Event::Configure(ec) => self.handler_resize_event(*ec),
+            Event::Input(ie) => {
+                if self.show_hud.get() && self.ui.borrow_mut().handle_event(evt) {
+                    return;
+                }
+                self.handler_input_event(ie)
+            }
             _ => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Emitters with `counts` particles each and the given blend and sort modes.
+    fn state(emitters: &[(BlendMode, SortMode, u32)]) -> PhysicsState {
+        PhysicsState::new(
+            Vec2F32::new(1024f32, 1024f32),
+            emitters
+                .iter()
+                .map(|&(blend, sort, particles)| EmitterConfig {
+                    particles,
+                    blend,
+                    sort,
+                    ..Default::default()
+                })
+                .collect(),
+        )
+    }
+
+    fn set_depth_and_layer(phys: &mut PhysicsState, values: &[(f32, u32)]) {
+        phys.particles
+            .iter_mut()
+            .zip(values.iter())
+            .for_each(|(p, &(depth, layer))| {
+                p.depth = depth;
+                p.layer = layer;
+            });
+    }
+
+    #[test]
+    fn test_batches_by_draw_priority() {
+        let phys = state(&[
+            (BlendMode::Additive, SortMode::None, 2),
+            (BlendMode::Alpha, SortMode::None, 3),
+            (BlendMode::Opaque, SortMode::None, 1),
+            (BlendMode::Alpha, SortMode::None, 0),
+            (BlendMode::Premultiplied, SortMode::None, 2),
+        ]);
+
+        let (order, batches) = phys.draw_order();
+        assert_eq!(order, vec![5, 2, 3, 4, 6, 7, 0, 1]);
+
+        //
+        // contiguous ranges, one per non empty emitter
+        let ranges = batches
+            .iter()
+            .map(|b| (b.blend, b.first, b.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ranges,
+            vec![
+                (BlendMode::Opaque, 0, 1),
+                (BlendMode::Alpha, 1, 3),
+                (BlendMode::Premultiplied, 4, 2),
+                (BlendMode::Additive, 6, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sorting() {
+        let mut phys = state(&[
+            (BlendMode::Alpha, SortMode::BackToFront, 3),
+            (BlendMode::Premultiplied, SortMode::Layer, 4),
+            (BlendMode::Additive, SortMode::BackToFront, 3),
+        ]);
+        set_depth_and_layer(
+            &mut phys,
+            &[
+                (0.2f32, 0),
+                (0.9f32, 0),
+                (0.5f32, 0),
+                (0.1f32, 1),
+                (0.3f32, 0),
+                (0.8f32, 1),
+                (0.6f32, 0),
+                (0.1f32, 0),
+                (0.9f32, 0),
+                (0.5f32, 0),
+            ],
+        );
+
+        let (order, _) = phys.draw_order();
+        //
+        // back to front: the largest depth first
+        assert_eq!(&order[..3], &[1, 2, 0]);
+        //
+        // by layer, back to front inside a layer
+        assert_eq!(&order[3..7], &[6, 4, 5, 3]);
+        //
+        // additive particles are order independent, never sorted
+        assert_eq!(&order[7..], &[7, 8, 9]);
+    }
+}
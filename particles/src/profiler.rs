@@ -0,0 +1,313 @@
+use crate::stats::RollingStats;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use sys::json_escape;
+
+/// Where a measurement was taken.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Timeline {
+    Cpu,
+    Gpu,
+}
+
+impl Timeline {
+    fn name(self) -> &'static str {
+        match self {
+            Timeline::Cpu => "cpu",
+            Timeline::Gpu => "gpu",
+        }
+    }
+}
+
+/// Rolling statistics of a named measurement, durations are in microseconds.
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub name: &'static str,
+    pub timeline: Timeline,
+    pub stats: RollingStats,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TraceEvent {
+    name: &'static str,
+    timeline: Timeline,
+    frame: u64,
+    /// Microseconds since the profiler was created.
+    start: f64,
+    duration: f64,
+}
+
+struct ProfilerData {
+    frame: u64,
+    tracks: Vec<Track>,
+    events: VecDeque<TraceEvent>,
+}
+
+/// Collects CPU and GPU timings of the sections of a frame. Keeps rolling statistics
+/// for every section and the most recent events, for a timeline view.
+pub struct Profiler {
+    epoch: Instant,
+    samples: usize,
+    max_events: usize,
+    data: RefCell<ProfilerData>,
+}
+
+/// Records the time between its creation and its destruction.
+pub struct CpuScope<'a> {
+    profiler: &'a Profiler,
+    name: &'static str,
+    start: Instant,
+}
+
+impl<'a> std::ops::Drop for CpuScope<'a> {
+    fn drop(&mut self) {
+        let duration = self.start.elapsed().as_secs_f64() * 1.0e6f64;
+        self.profiler
+            .record(self.name, Timeline::Cpu, self.start, duration);
+    }
+}
+
+impl Profiler {
+    /// Keeps `samples` values per section and the events of the last `max_events`
+    /// measurements.
+    pub fn new(samples: usize, max_events: usize) -> Profiler {
+        Profiler {
+            epoch: Instant::now(),
+            samples,
+            max_events,
+            data: RefCell::new(ProfilerData {
+                frame: 0,
+                tracks: Vec::new(),
+                events: VecDeque::with_capacity(max_events),
+            }),
+        }
+    }
+
+    /// Starts a new frame, returns its number.
+    pub fn begin_frame(&self) -> u64 {
+        let mut data = self.data.borrow_mut();
+        data.frame += 1;
+        data.frame
+    }
+
+    pub fn frame(&self) -> u64 {
+        self.data.borrow().frame
+    }
+
+    /// Times the CPU work done until the returned guard is dropped.
+    pub fn scope(&self, name: &'static str) -> CpuScope<'_> {
+        CpuScope {
+            profiler: self,
+            name,
+            start: Instant::now(),
+        }
+    }
+
+    /// Adds a measurement that started at `start` and took `duration` microseconds.
+    pub fn record(&self, name: &'static str, timeline: Timeline, start: Instant, duration: f64) {
+        self.record_for_frame(name, timeline, self.frame(), start, duration);
+    }
+
+    /// Same as `record`, for measurements that arrive after the frame they belong to
+    /// (GPU timings).
+    pub fn record_for_frame(
+        &self,
+        name: &'static str,
+        timeline: Timeline,
+        frame: u64,
+        start: Instant,
+        duration: f64,
+    ) {
+        let mut data = self.data.borrow_mut();
+
+        let track_idx = match data
+            .tracks
+            .iter()
+            .position(|t| t.name == name && t.timeline == timeline)
+        {
+            Some(idx) => idx,
+            None => {
+                data.tracks.push(Track {
+                    name,
+                    timeline,
+                    stats: RollingStats::new(self.samples),
+                });
+                data.tracks.len() - 1
+            }
+        };
+        data.tracks[track_idx].stats.push(duration as f32);
+
+        if self.max_events > 0 {
+            if data.events.len() == self.max_events {
+                data.events.pop_front();
+            }
+
+            let start = if start > self.epoch {
+                (start - self.epoch).as_secs_f64() * 1.0e6f64
+            } else {
+                0f64
+            };
+
+            data.events.push_back(TraceEvent {
+                name,
+                timeline,
+                frame,
+                start,
+                duration,
+            });
+        }
+    }
+
+    /// Statistics of a section, if it was measured at least once.
+    pub fn track(&self, name: &str, timeline: Timeline) -> Option<Track> {
+        self.data
+            .borrow()
+            .tracks
+            .iter()
+            .find(|t| t.name == name && t.timeline == timeline)
+            .cloned()
+    }
+
+    /// All the sections, in the order they were first measured.
+    pub fn tracks(&self) -> Vec<Track> {
+        self.data.borrow().tracks.clone()
+    }
+
+    /// One line per section: name, timeline, sample count, last, average, min and max
+    /// duration in microseconds.
+    pub fn write_csv<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "section,timeline,samples,last_us,avg_us,min_us,max_us")?;
+
+        self.data.borrow().tracks.iter().try_for_each(|t| {
+            writeln!(
+                out,
+                "{},{},{},{:.3},{:.3},{:.3},{:.3}",
+                t.name,
+                t.timeline.name(),
+                t.stats.len(),
+                t.stats.last(),
+                t.stats.average(),
+                t.stats.min(),
+                t.stats.max()
+            )
+        })
+    }
+
+    /// The recent events in the Chrome trace event format (chrome://tracing, Perfetto).
+    /// CPU and GPU timings are shown as separate threads, GPU events start when the work
+    /// was submitted.
+    pub fn write_chrome_trace<W: Write>(&self, out: &mut W) -> std::io::Result<()> {
+        writeln!(out, "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[")?;
+        writeln!(
+            out,
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":1,\"args\":{{\"name\":\"CPU\"}}}},"
+        )?;
+        write!(
+            out,
+            "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":1,\"tid\":2,\"args\":{{\"name\":\"GPU\"}}}}"
+        )?;
+
+        self.data.borrow().events.iter().try_for_each(|e| {
+            write!(
+                out,
+                ",\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{:.3},\"dur\":{:.3},\"args\":{{\"frame\":{}}}}}",
+                json_escape(e.name),
+                e.timeline.name(),
+                match e.timeline {
+                    Timeline::Cpu => 1,
+                    Timeline::Gpu => 2,
+                },
+                e.start,
+                e.duration,
+                e.frame
+            )
+        })?;
+
+        writeln!(out, "\n]}}")
+    }
+
+    /// Writes `profile-<unix millis>.csv` and `profile-<unix millis>.json` in the current
+    /// directory, returns the names of the files.
+    pub fn dump(&self) -> Result<(String, String), String> {
+        let stamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+
+        let csv_file = format!("profile-{}.csv", stamp);
+        let trace_file = format!("profile-{}.json", stamp);
+
+        let write_file =
+            |file_name: &str, write: &dyn Fn(&mut BufWriter<File>) -> std::io::Result<()>| {
+                File::create(file_name)
+                    .and_then(|f| {
+                        let mut out = BufWriter::new(f);
+                        write(&mut out)?;
+                        out.flush()
+                    })
+                    .map_err(|e| format!("Failed to write {}: {}", file_name, e))
+            };
+
+        write_file(&csv_file, &|out| self.write_csv(out))?;
+        write_file(&trace_file, &|out| self.write_chrome_trace(out))?;
+
+        Ok((csv_file, trace_file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracks() {
+        let profiler = Profiler::new(4, 3);
+        assert_eq!(profiler.begin_frame(), 1);
+
+        {
+            let _scope = profiler.scope("update");
+        }
+        profiler.record("draw", Timeline::Gpu, Instant::now(), 250f64);
+        profiler.record("draw", Timeline::Gpu, Instant::now(), 750f64);
+        profiler.record("draw", Timeline::Cpu, Instant::now(), 10f64);
+
+        let tracks = profiler.tracks();
+        assert_eq!(tracks.len(), 3);
+        assert_eq!(tracks[0].name, "update");
+
+        let gpu_draw = profiler.track("draw", Timeline::Gpu).unwrap();
+        assert_eq!(gpu_draw.stats.len(), 2);
+        assert_eq!(gpu_draw.stats.average(), 500f32);
+        assert!(profiler.track("upload", Timeline::Gpu).is_none());
+    }
+
+    #[test]
+    fn test_output_formats() {
+        let profiler = Profiler::new(8, 2);
+        profiler.begin_frame();
+        profiler.record("a \"b\"", Timeline::Cpu, Instant::now(), 1.5f64);
+        profiler.record("c", Timeline::Gpu, Instant::now(), 2f64);
+        profiler.record("c", Timeline::Gpu, Instant::now(), 4f64);
+
+        let mut csv = Vec::new();
+        profiler.write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2], "c,gpu,2,4.000,3.000,2.000,4.000");
+
+        let mut trace = Vec::new();
+        profiler.write_chrome_trace(&mut trace).unwrap();
+        let trace = String::from_utf8(trace).unwrap();
+        //
+        // only the last 2 events are kept
+        assert_eq!(trace.matches("\"ph\":\"X\"").count(), 2);
+        assert!(!trace.contains("a \\\"b\\\""));
+        assert!(trace.contains("\"tid\":2,\"ts\":"));
+        assert!(trace.trim_end().ends_with("]}"));
+        assert_eq!(json_escape("a \"b\"\n"), "a \\\"b\\\"\\n");
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Reads the color buffer of the default framebuffer and saves it as a PNG file in the
+/// current directory. Returns the name of the file.
+pub fn save_screenshot(width: i32, height: i32) -> Result<String, String> {
+    if width <= 0 || height <= 0 {
+        return Err(format!("Invalid framebuffer size {}x{}", width, height));
+    }
+
+    let row_bytes = width as usize * 4;
+    let mut pixels = vec![0u8; row_bytes * height as usize];
+
+    unsafe {
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl::ReadBuffer(gl::BACK);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadnPixels(
+            0,
+            0,
+            width,
+            height,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.len() as i32,
+            pixels.as_mut_ptr() as *mut gl::types::GLvoid,
+        );
+    }
+
+    //
+    // OpenGL returns the rows bottom to top
+    let flipped = pixels
+        .chunks(row_bytes)
+        .rev()
+        .flat_map(|row| row.iter().copied())
+        .collect::<Vec<_>>();
+
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let file_name = format!("screenshot-{}.png", stamp);
+
+    let file =
+        File::create(&file_name).map_err(|e| format!("Failed to create {}: {}", file_name, e))?;
+
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::RGBA);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&flipped))
+        .map_err(|e| format!("Failed to write {}: {}", file_name, e))?;
+
+    Ok(file_name)
+}
@@ -0,0 +1,236 @@
+/// Turns wall clock frame times into a number of fixed simulation steps.
+///
+/// Frame time is scaled by the time scale and accumulated, every full step worth of time
+/// gives one step. What is left over is the interpolation factor between the last two
+/// simulation states. Pausing freezes the accumulator, so the interpolation factor stays
+/// the same and single steps move both states forward without the picture jumping.
+#[derive(Copy, Clone, Debug)]
+pub struct SimClock {
+    step: f32,
+    accumulated: f32,
+    time_scale: f32,
+    slow_motion: bool,
+    paused: bool,
+    /// Single steps requested while paused, not yet taken.
+    pending_steps: u32,
+    /// Steps left to run in fast forward mode.
+    fast_forward: u32,
+    /// Simulated time, in seconds.
+    sim_time: f64,
+}
+
+impl SimClock {
+    pub const MIN_TIME_SCALE: f32 = 1f32 / 16f32;
+    pub const MAX_TIME_SCALE: f32 = 8f32;
+    /// Applied on top of the time scale while slow motion is on.
+    pub const SLOW_MOTION_SCALE: f32 = 0.25f32;
+    /// Upper bound of steps taken in one frame, in case the simulation cannot keep up.
+    pub const MAX_STEPS_PER_FRAME: u32 = 64;
+
+    pub fn new(step: f32) -> SimClock {
+        SimClock {
+            step,
+            accumulated: 0f32,
+            time_scale: 1f32,
+            slow_motion: false,
+            paused: false,
+            pending_steps: 0,
+            fast_forward: 0,
+            sim_time: 0f64,
+        }
+    }
+
+    /// Duration of a simulation step, in seconds.
+    pub fn step(&self) -> f32 {
+        self.step
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.pending_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.set_paused(!self.paused);
+    }
+
+    /// Pauses the clock and queues a single step.
+    pub fn request_step(&mut self) {
+        self.paused = true;
+        self.pending_steps += 1;
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.time_scale = math::utility::clamp(Self::MIN_TIME_SCALE, scale, Self::MAX_TIME_SCALE);
+    }
+
+    pub fn scale_time(&mut self, factor: f32) {
+        self.set_time_scale(self.time_scale * factor);
+    }
+
+    pub fn is_slow_motion(&self) -> bool {
+        self.slow_motion
+    }
+
+    pub fn toggle_slow_motion(&mut self) {
+        self.slow_motion = !self.slow_motion;
+    }
+
+    /// Time scale with slow motion applied.
+    pub fn effective_time_scale(&self) -> f32 {
+        if self.slow_motion {
+            self.time_scale * Self::SLOW_MOTION_SCALE
+        } else {
+            self.time_scale
+        }
+    }
+
+    /// Runs `steps` steps as fast as possible, regular time keeping is suspended meanwhile.
+    pub fn fast_forward(&mut self, steps: u32) {
+        self.fast_forward = steps;
+    }
+
+    pub fn cancel_fast_forward(&mut self) {
+        self.fast_forward = 0;
+    }
+
+    pub fn is_fast_forwarding(&self) -> bool {
+        self.fast_forward > 0
+    }
+
+    /// Steps left to run in fast forward mode.
+    pub fn fast_forward_remaining(&self) -> u32 {
+        self.fast_forward
+    }
+
+    /// Takes one fast forward step, returns false once all of them were taken.
+    pub fn take_fast_forward_step(&mut self) -> bool {
+        if self.fast_forward == 0 {
+            return false;
+        }
+
+        self.fast_forward -= 1;
+        self.sim_time += self.step as f64;
+        true
+    }
+
+    /// Advances the clock by a frame, returns the number of steps to simulate.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        if self.fast_forward > 0 {
+            return 0;
+        }
+
+        let steps = if self.paused {
+            std::mem::replace(&mut self.pending_steps, 0)
+        } else {
+            self.accumulated += frame_time.max(0f32) * self.effective_time_scale();
+
+            let mut steps = 0;
+            while self.accumulated >= self.step {
+                self.accumulated -= self.step;
+                steps += 1;
+            }
+
+            if steps > Self::MAX_STEPS_PER_FRAME {
+                //
+                // falling behind, drop the time that does not fit in this frame
+                steps = Self::MAX_STEPS_PER_FRAME;
+            }
+
+            steps
+        };
+
+        self.sim_time += steps as f64 * self.step as f64;
+        steps
+    }
+
+    /// Interpolation factor between the previous and the current simulation state.
+    pub fn alpha(&self) -> f32 {
+        self.accumulated / self.step
+    }
+
+    /// Simulated time since the clock was created, in seconds.
+    pub fn sim_time(&self) -> f64 {
+        self.sim_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f32 = 0.25f32;
+
+    #[test]
+    fn test_accumulates_steps() {
+        let mut clock = SimClock::new(STEP);
+        assert_eq!(clock.advance(0.6f32), 2);
+        assert!((clock.alpha() - 0.4f32).abs() <= 0.001f32);
+        assert_eq!(clock.advance(0.2f32), 1);
+        assert!((clock.sim_time() - 0.75f64).abs() <= 0.001f64);
+
+        assert_eq!(clock.advance(100f32), SimClock::MAX_STEPS_PER_FRAME);
+        assert!(clock.alpha() < 1f32);
+    }
+
+    #[test]
+    fn test_pause_and_single_step() {
+        let mut clock = SimClock::new(STEP);
+        clock.advance(0.1f32);
+        let alpha = clock.alpha();
+
+        clock.toggle_pause();
+        assert_eq!(clock.advance(1f32), 0);
+        assert_eq!(clock.alpha(), alpha);
+
+        clock.request_step();
+        clock.request_step();
+        assert_eq!(clock.advance(1f32), 2);
+        assert_eq!(clock.advance(1f32), 0);
+        assert_eq!(clock.alpha(), alpha);
+
+        clock.toggle_pause();
+        assert_eq!(clock.advance(0.2f32), 1);
+    }
+
+    #[test]
+    fn test_time_scale() {
+        let mut clock = SimClock::new(STEP);
+        clock.set_time_scale(2f32);
+        assert_eq!(clock.advance(0.5f32), 4);
+
+        clock.toggle_slow_motion();
+        assert!((clock.effective_time_scale() - 0.5f32).abs() <= 0.001f32);
+        assert_eq!(clock.advance(0.5f32), 1);
+
+        clock.scale_time(1000f32);
+        assert_eq!(clock.time_scale(), SimClock::MAX_TIME_SCALE);
+        clock.scale_time(0f32);
+        assert_eq!(clock.time_scale(), SimClock::MIN_TIME_SCALE);
+    }
+
+    #[test]
+    fn test_fast_forward() {
+        let mut clock = SimClock::new(STEP);
+        clock.fast_forward(3);
+        assert_eq!(clock.advance(1f32), 0);
+
+        let mut taken = 0;
+        while clock.take_fast_forward_step() {
+            taken += 1;
+        }
+
+        assert_eq!(taken, 3);
+        assert!(!clock.is_fast_forwarding());
+        assert!((clock.sim_time() - 0.75f64).abs() <= 0.001f64);
+        assert_eq!(clock.advance(0.25f32), 1);
+    }
+}
@@ -0,0 +1,83 @@
+use std::collections::VecDeque;
+
+/// Keeps the last `capacity` samples of a measurement (frame times, counters, ..).
+#[derive(Clone, Debug)]
+pub struct RollingStats {
+    samples: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RollingStats {
+    pub fn new(capacity: usize) -> RollingStats {
+        RollingStats {
+            samples: VecDeque::with_capacity(capacity.max(1)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, sample: f32) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Samples, oldest first.
+    pub fn values(&self) -> Vec<f32> {
+        self.samples.iter().copied().collect()
+    }
+
+    pub fn last(&self) -> f32 {
+        self.samples.back().copied().unwrap_or(0f32)
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.samples.is_empty() {
+            0f32
+        } else {
+            self.samples.iter().sum::<f32>() / self.samples.len() as f32
+        }
+    }
+
+    pub fn min(&self) -> f32 {
+        self.samples
+            .iter()
+            .copied()
+            .fold(None, |m: Option<f32>, s| Some(m.map_or(s, |m| m.min(s))))
+            .unwrap_or(0f32)
+    }
+
+    pub fn max(&self) -> f32 {
+        self.samples
+            .iter()
+            .copied()
+            .fold(None, |m: Option<f32>, s| Some(m.map_or(s, |m| m.max(s))))
+            .unwrap_or(0f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_window() {
+        let mut stats = RollingStats::new(3);
+        assert_eq!(stats.average(), 0f32);
+
+        (1..=5).for_each(|v| stats.push(v as f32));
+        assert_eq!(stats.values(), vec![3f32, 4f32, 5f32]);
+        assert_eq!(stats.last(), 5f32);
+        assert_eq!(stats.average(), 4f32);
+        assert_eq!(stats.min(), 3f32);
+        assert_eq!(stats.max(), 5f32);
+    }
+}
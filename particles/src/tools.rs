@@ -0,0 +1,323 @@
+use crate::camera::Camera2D;
+use math::vec2::Vec2F32;
+use std::time::Instant;
+use sys::input::*;
+
+/// What the left mouse button does.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MouseTool {
+    /// Pulls particles towards the cursor. Shift reverses the force.
+    Attract,
+    /// Pushes particles away from the cursor. Shift reverses the force.
+    Repel,
+    /// Paints new particles around the cursor.
+    Spawn,
+    /// Grabs the particle under the cursor, releasing the button flings it.
+    Drag,
+}
+
+/// What the active tool asks the physics simulation to do during the next steps.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PointerInteraction {
+    None,
+    /// Radial acceleration around `center`, negative strength pushes particles away.
+    Force {
+        center: Vec2F32,
+        radius: f32,
+        strength: f32,
+    },
+    /// Recycles particles into new ones around `center`, `rate` is in particles/second.
+    Spawn {
+        center: Vec2F32,
+        radius: f32,
+        rate: f32,
+    },
+    /// Pins a particle to `target`, moving it with `velocity`.
+    Drag {
+        particle: usize,
+        target: Vec2F32,
+        velocity: Vec2F32,
+    },
+    /// A dragged particle was let go, it keeps `velocity`.
+    Fling {
+        particle: usize,
+        velocity: Vec2F32,
+    },
+}
+
+/// Mouse tools, all positions are in world space.
+pub struct ToolState {
+    tool: MouseTool,
+    pressed: bool,
+    shift: bool,
+    control: bool,
+    cursor: Vec2F32,
+    cursor_velocity: Vec2F32,
+    last_motion: Instant,
+    grabbed: Option<usize>,
+    flung: Option<(usize, Vec2F32)>,
+    pub force_radius: f32,
+    pub force_strength: f32,
+    pub spawn_rate: f32,
+}
+
+impl ToolState {
+    /// Default radius of influence of the attract/repel tools, in world units.
+    pub const FORCE_RADIUS: f32 = 256f32;
+    /// Default acceleration at the cursor, in world units/s^2.
+    pub const FORCE_STRENGTH: f32 = 4000f32;
+    pub const SPAWN_RADIUS: f32 = 32f32;
+    /// Default number of particles spawned per second while painting.
+    pub const SPAWN_RATE: f32 = 120f32;
+    /// Control multiplies the force strength and the spawn rate by this value.
+    pub const BOOST: f32 = 4f32;
+    /// Pointer must have moved within this interval for a release to fling the particle.
+    const FLING_WINDOW: f32 = 0.1f32;
+
+    pub fn new() -> ToolState {
+        ToolState {
+            tool: MouseTool::Attract,
+            pressed: false,
+            shift: false,
+            control: false,
+            cursor: Vec2F32::default(),
+            cursor_velocity: Vec2F32::default(),
+            last_motion: Instant::now(),
+            grabbed: None,
+            flung: None,
+            force_radius: Self::FORCE_RADIUS,
+            force_strength: Self::FORCE_STRENGTH,
+            spawn_rate: Self::SPAWN_RATE,
+        }
+    }
+
+    pub fn tool(&self) -> MouseTool {
+        self.tool
+    }
+
+    pub fn cursor(&self) -> Vec2F32 {
+        self.cursor
+    }
+
+    pub fn select(&mut self, tool: MouseTool) {
+        if tool != self.tool {
+            self.release();
+            self.tool = tool;
+        }
+    }
+
+    /// `pick` returns the particle under a world space position, used by the drag tool.
+    pub fn handle_mouse_button<F>(&mut self, mbe: &MouseButtonEventData, camera: &Camera2D, pick: F)
+    where
+        F: FnOnce(Vec2F32) -> Option<usize>,
+    {
+        if mbe.button != MouseButtonId::Button1 {
+            return;
+        }
+
+        self.shift = mbe.shift;
+        self.control = mbe.control;
+        self.cursor =
+            camera.screen_to_world(Vec2F32::new(mbe.pointer_x as f32, mbe.pointer_y as f32));
+
+        match mbe.type_ {
+            ActionType::Press => {
+                self.pressed = true;
+                self.cursor_velocity = Vec2F32::default();
+                self.last_motion = Instant::now();
+
+                if self.tool == MouseTool::Drag {
+                    self.grabbed = pick(self.cursor);
+                }
+            }
+            ActionType::Release => self.release(),
+        }
+    }
+
+    pub fn handle_mouse_motion(&mut self, mme: &MouseMotionEventData, camera: &Camera2D) {
+        let cursor =
+            camera.screen_to_world(Vec2F32::new(mme.pointer_x as f32, mme.pointer_y as f32));
+
+        let now = Instant::now();
+        let elapsed = (now - self.last_motion).as_secs_f32();
+        if elapsed > 0f32 {
+            //
+            // smooth the estimate, pointer events arrive at an irregular rate
+            let velocity = (cursor - self.cursor) * (1f32 / elapsed);
+            self.cursor_velocity = self.cursor_velocity * 0.5f32 + velocity * 0.5f32;
+        }
+
+        self.cursor = cursor;
+        self.last_motion = now;
+        self.shift = mme.shift;
+        self.control = mme.control;
+    }
+
+    /// Returns the interaction for the coming simulation steps. A fling is reported once.
+    pub fn interaction(&mut self) -> PointerInteraction {
+        if let Some((particle, velocity)) = self.flung.take() {
+            return PointerInteraction::Fling { particle, velocity };
+        }
+
+        if !self.pressed {
+            return PointerInteraction::None;
+        }
+
+        let boost = if self.control { Self::BOOST } else { 1f32 };
+
+        match self.tool {
+            MouseTool::Attract | MouseTool::Repel => {
+                let attract = (self.tool == MouseTool::Attract) != self.shift;
+                PointerInteraction::Force {
+                    center: self.cursor,
+                    radius: self.force_radius,
+                    strength: self.force_strength * boost * if attract { 1f32 } else { -1f32 },
+                }
+            }
+            MouseTool::Spawn => PointerInteraction::Spawn {
+                center: self.cursor,
+                radius: Self::SPAWN_RADIUS,
+                rate: self.spawn_rate * boost,
+            },
+            MouseTool::Drag => self.grabbed.map_or(PointerInteraction::None, |particle| {
+                PointerInteraction::Drag {
+                    particle,
+                    target: self.cursor,
+                    velocity: self.cursor_velocity,
+                }
+            }),
+        }
+    }
+
+    fn release(&mut self) {
+        if let Some(particle) = self.grabbed.take() {
+            let velocity = if self.last_motion.elapsed().as_secs_f32() <= Self::FLING_WINDOW {
+                self.cursor_velocity
+            } else {
+                Vec2F32::default()
+            };
+
+            self.flung = Some((particle, velocity));
+        }
+
+        self.pressed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tool with the left button held down at `cursor`.
+    fn pressed(tool: MouseTool, cursor: Vec2F32) -> ToolState {
+        let mut tools = ToolState::new();
+        tools.select(tool);
+        tools.pressed = true;
+        tools.cursor = cursor;
+        tools
+    }
+
+    fn force_strength(tools: &mut ToolState) -> f32 {
+        match tools.interaction() {
+            PointerInteraction::Force { strength, .. } => strength,
+            other => panic!("expected a force, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_force_direction_and_boost() {
+        let mut tools = pressed(MouseTool::Attract, Vec2F32::new(10f32, 20f32));
+        assert_eq!(
+            tools.interaction(),
+            PointerInteraction::Force {
+                center: Vec2F32::new(10f32, 20f32),
+                radius: ToolState::FORCE_RADIUS,
+                strength: ToolState::FORCE_STRENGTH,
+            }
+        );
+
+        tools.shift = true;
+        assert_eq!(force_strength(&mut tools), -ToolState::FORCE_STRENGTH);
+
+        let mut tools = pressed(MouseTool::Repel, Vec2F32::default());
+        assert_eq!(force_strength(&mut tools), -ToolState::FORCE_STRENGTH);
+        tools.shift = true;
+        assert_eq!(force_strength(&mut tools), ToolState::FORCE_STRENGTH);
+
+        tools.control = true;
+        assert_eq!(
+            force_strength(&mut tools),
+            ToolState::FORCE_STRENGTH * ToolState::BOOST
+        );
+
+        tools.pressed = false;
+        assert_eq!(tools.interaction(), PointerInteraction::None);
+    }
+
+    #[test]
+    fn test_spawn_boost() {
+        let mut tools = pressed(MouseTool::Spawn, Vec2F32::default());
+        tools.control = true;
+        assert_eq!(
+            tools.interaction(),
+            PointerInteraction::Spawn {
+                center: Vec2F32::default(),
+                radius: ToolState::SPAWN_RADIUS,
+                rate: ToolState::SPAWN_RATE * ToolState::BOOST,
+            }
+        );
+    }
+
+    #[test]
+    fn test_fling_reported_once() {
+        let velocity = Vec2F32::new(100f32, -50f32);
+        let mut tools = pressed(MouseTool::Drag, Vec2F32::new(1f32, 2f32));
+        tools.grabbed = Some(3);
+        tools.cursor_velocity = velocity;
+        tools.last_motion = Instant::now();
+
+        assert_eq!(
+            tools.interaction(),
+            PointerInteraction::Drag {
+                particle: 3,
+                target: Vec2F32::new(1f32, 2f32),
+                velocity,
+            }
+        );
+
+        tools.release();
+        assert_eq!(
+            tools.interaction(),
+            PointerInteraction::Fling {
+                particle: 3,
+                velocity,
+            }
+        );
+        assert_eq!(tools.interaction(), PointerInteraction::None);
+    }
+
+    #[test]
+    fn test_select_releases_grab() {
+        let mut tools = pressed(MouseTool::Drag, Vec2F32::default());
+        tools.grabbed = Some(7);
+        tools.last_motion = Instant::now();
+
+        tools.select(MouseTool::Attract);
+        assert_eq!(tools.tool(), MouseTool::Attract);
+        assert!(matches!(
+            tools.interaction(),
+            PointerInteraction::Fling { particle: 7, .. }
+        ));
+        assert_eq!(tools.interaction(), PointerInteraction::None);
+
+        //
+        // selecting the active tool keeps the grab
+        let mut tools = pressed(MouseTool::Drag, Vec2F32::default());
+        tools.grabbed = Some(7);
+        tools.select(MouseTool::Drag);
+        assert!(matches!(
+            tools.interaction(),
+            PointerInteraction::Drag { particle: 7, .. }
+        ));
+    }
+}
@@ -0,0 +1,190 @@
+use math::colors::RGBAColorF32;
+use math::rectangle::RectangleF32;
+use math::vec2::Vec2F32;
+use math::vertex_types::VertexPC;
+
+/// Line segments queued during a frame, drawn by `DebugDrawRenderer` in one call. The
+/// coordinate system is up to the caller, the renderer takes the matching projection.
+#[derive(Clone, Debug, Default)]
+pub struct DebugDrawList {
+    pub vertices: Vec<VertexPC>,
+}
+
+impl DebugDrawList {
+    /// Segments used for circles with the default tesselation.
+    pub const CIRCLE_SEGMENTS: u32 = 32;
+    /// Length of the arrow head relative to the length of the arrow.
+    const ARROW_HEAD_RATIO: f32 = 0.25f32;
+    /// Half angle of the arrow head, 25 degrees.
+    const ARROW_HEAD_ANGLE: f32 = 0.436_332_3f32;
+
+    pub fn new() -> DebugDrawList {
+        DebugDrawList::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vertices.is_empty()
+    }
+
+    /// Number of queued segments.
+    pub fn len(&self) -> usize {
+        self.vertices.len() / 2
+    }
+
+    pub fn line(&mut self, a: Vec2F32, b: Vec2F32, color: RGBAColorF32) {
+        self.vertices.push(VertexPC { pos: a, color });
+        self.vertices.push(VertexPC { pos: b, color });
+    }
+
+    /// Connects the points, closing the loop if `closed` is set.
+    pub fn polyline(&mut self, points: &[Vec2F32], closed: bool, color: RGBAColorF32) {
+        points
+            .windows(2)
+            .for_each(|seg| self.line(seg[0], seg[1], color));
+
+        if closed && points.len() > 2 {
+            self.line(points[points.len() - 1], points[0], color);
+        }
+    }
+
+    pub fn circle(&mut self, center: Vec2F32, radius: f32, color: RGBAColorF32) {
+        self.circle_segments(center, radius, Self::CIRCLE_SEGMENTS, color);
+    }
+
+    pub fn circle_segments(
+        &mut self,
+        center: Vec2F32,
+        radius: f32,
+        segments: u32,
+        color: RGBAColorF32,
+    ) {
+        let segments = segments.max(3);
+        let step = std::f32::consts::PI * 2f32 / segments as f32;
+        let point = |i: u32| {
+            let theta = (i % segments) as f32 * step;
+            center + Vec2F32::new(theta.cos(), theta.sin()) * radius
+        };
+
+        (0..segments).for_each(|i| self.line(point(i), point(i + 1), color));
+    }
+
+    pub fn rect(&mut self, r: RectangleF32, color: RGBAColorF32) {
+        let corners = [
+            Vec2F32::new(r.x, r.y),
+            Vec2F32::new(r.x + r.w, r.y),
+            Vec2F32::new(r.x + r.w, r.y + r.h),
+            Vec2F32::new(r.x, r.y + r.h),
+        ];
+        self.polyline(&corners, true, color);
+    }
+
+    /// Arrow from `from` to `to`, the head scales with the length of the arrow.
+    pub fn arrow(&mut self, from: Vec2F32, to: Vec2F32, color: RGBAColorF32) {
+        let d = to - from;
+        let len = d.len();
+        if len <= f32::EPSILON {
+            return;
+        }
+
+        self.line(from, to, color);
+
+        let back = d * (-Self::ARROW_HEAD_RATIO);
+        let (sin, cos) = Self::ARROW_HEAD_ANGLE.sin_cos();
+        let rotate =
+            |v: Vec2F32, sin: f32| Vec2F32::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos);
+
+        self.line(to, to + rotate(back, sin), color);
+        self.line(to, to + rotate(back, -sin), color);
+    }
+
+    /// Small axis aligned cross, marks a point.
+    pub fn cross(&mut self, pos: Vec2F32, size: f32, color: RGBAColorF32) {
+        let h = size * 0.5f32;
+        self.line(
+            pos - Vec2F32::new(h, 0f32),
+            pos + Vec2F32::new(h, 0f32),
+            color,
+        );
+        self.line(
+            pos - Vec2F32::new(0f32, h),
+            pos + Vec2F32::new(0f32, h),
+            color,
+        );
+    }
+
+    /// Grid lines every `cell` units, aligned to multiples of `cell` and clipped to `area`.
+    pub fn grid(&mut self, area: RectangleF32, cell: f32, color: RGBAColorF32) {
+        if cell <= 0f32 {
+            return;
+        }
+
+        let x1 = area.x + area.w;
+        let y1 = area.y + area.h;
+
+        let mut x = (area.x / cell).ceil() * cell;
+        while x <= x1 {
+            self.line(Vec2F32::new(x, area.y), Vec2F32::new(x, y1), color);
+            x += cell;
+        }
+
+        let mut y = (area.y / cell).ceil() * cell;
+        while y <= y1 {
+            self.line(Vec2F32::new(area.x, y), Vec2F32::new(x1, y), color);
+            y += cell;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shapes() {
+        let white = RGBAColorF32::new(1f32, 1f32, 1f32);
+        let mut dl = DebugDrawList::new();
+
+        dl.rect(RectangleF32::new(0f32, 0f32, 2f32, 1f32), white);
+        assert_eq!(dl.len(), 4);
+
+        dl.circle_segments(Vec2F32::same(0f32), 1f32, 8, white);
+        assert_eq!(dl.len(), 12);
+        //
+        // closed loop, the last segment ends where the first one starts
+        assert!((dl.vertices[23].pos - dl.vertices[8].pos).len() < 0.0001f32);
+
+        dl.arrow(Vec2F32::same(0f32), Vec2F32::same(0f32), white);
+        assert_eq!(dl.len(), 12);
+        dl.arrow(Vec2F32::same(0f32), Vec2F32::new(4f32, 0f32), white);
+        assert_eq!(dl.len(), 15);
+
+        //
+        // arrow head points back, symmetric around the shaft
+        let head = (dl.vertices[27].pos, dl.vertices[29].pos);
+        assert!((head.0.x - 3.09f32).abs() < 0.01f32 && head.0.x == head.1.x);
+        assert!((head.0.y + head.1.y).abs() < 0.0001f32);
+
+        dl.clear();
+        assert!(dl.is_empty());
+    }
+
+    #[test]
+    fn test_grid() {
+        let mut dl = DebugDrawList::new();
+        dl.grid(
+            RectangleF32::new(-5f32, 1f32, 20f32, 9f32),
+            10f32,
+            RGBAColorF32::default(),
+        );
+
+        //
+        // vertical lines at x = 0 and 10, horizontal at y = 10
+        assert_eq!(dl.len(), 3);
+        assert_eq!(dl.vertices[0].pos, Vec2F32::new(0f32, 1f32));
+        assert_eq!(dl.vertices[5].pos, Vec2F32::new(15f32, 10f32));
+    }
+}
@@ -0,0 +1,113 @@
+use crate::debug_draw::DebugDrawList;
+use crate::error::RenderError;
+use crate::gl_debug::{check_gl_errors, label_object, ObjectKind};
+use crate::render_state::{BlendState, DepthStencilState, RasterizerState};
+use crate::renderer_gl::*;
+use crate::shader_preprocessor::{ShaderCache, ShaderPermutation};
+use crate::typed_buffer::TypedBuffer;
+use crate::vertex_layout::Vertex;
+use math::mat4::Mat4F32;
+use math::vertex_types::VertexPC;
+use std::rc::Rc;
+
+/// Draws the segments of a `DebugDrawList` as GL lines, in a single draw call.
+pub struct DebugDrawRenderer {
+    vertexbuffer: TypedBuffer<VertexPC>,
+    vertexarray: UniqueVertexArray,
+    vertshader: Rc<UniqueShaderProgram>,
+    /// Not referenced after creation, but has to live as long as the pipeline.
+    #[allow(dead_code)]
+    fragshader: Rc<UniqueShaderProgram>,
+    pipeline: UniquePipeline,
+}
+
+impl DebugDrawRenderer {
+    /// Segments past this limit are dropped.
+    pub const MAX_VERTICES: usize = 1 << 18;
+
+    pub fn new(shaders: &mut ShaderCache) -> Result<DebugDrawRenderer, RenderError> {
+        let vertexbuffer =
+            TypedBuffer::with_capacity(Self::MAX_VERTICES, Some(BufferAccess::Write))?;
+
+        let vertshader = shaders.get_with_source(
+            "debug_draw.vert",
+            include_str!("../../data/shaders/debug_draw.vert"),
+            ShaderType::Vertex,
+            &ShaderPermutation::new(),
+        )?;
+
+        let layout = VertexPC::layout();
+        layout.validate(**vertshader)?;
+        let vertexarray = layout.create_vertex_array(&[vertexbuffer.handle()], None)?;
+
+        let fragshader = shaders.get_with_source(
+            "debug_draw.frag",
+            include_str!("../../data/shaders/debug_draw.frag"),
+            ShaderType::Fragment,
+            &ShaderPermutation::new(),
+        )?;
+
+        let pipeline = PipelineBuilder::new()
+            .add_vertex_shader(&vertshader)
+            .add_fragment_shader(&fragshader)
+            .build()?;
+
+        label_object(
+            ObjectKind::Buffer,
+            vertexbuffer.handle(),
+            "debug draw vertices",
+        );
+        label_object(ObjectKind::VertexArray, *vertexarray, "debug draw");
+        label_object(ObjectKind::ProgramPipeline, *pipeline, "debug draw");
+
+        Ok(DebugDrawRenderer {
+            vertexbuffer,
+            vertexarray,
+            vertshader,
+            fragshader,
+            pipeline,
+        })
+    }
+
+    /// Draws the queued segments on top of the bound framebuffer, with alpha blending and
+    /// without depth testing. `view_projection` maps the coordinates used by the draw list
+    /// to clip space.
+    pub fn render(&self, draw_list: &DebugDrawList, view_projection: &Mat4F32) {
+        if draw_list.is_empty() {
+            return;
+        }
+
+        //
+        // whole segments only
+        let vertices = &draw_list.vertices[..draw_list.vertices.len().min(Self::MAX_VERTICES) & !1];
+
+        if let Err(e) = self.vertexbuffer.write(vertices) {
+            log::error!("Failed to upload the debug draw vertices: {}", e);
+            return;
+        }
+
+        let _saved_state = OpenGLStateSnapshot::new();
+
+        BlendState::alpha().apply();
+        DepthStencilState::disabled().apply();
+        RasterizerState::solid().apply();
+
+        unsafe {
+            gl::ProgramUniformMatrix4fv(
+                **self.vertshader,
+                0,
+                1,
+                gl::TRUE,
+                view_projection.as_ptr(),
+            );
+
+            gl::BindVertexArray(*self.vertexarray);
+            gl::BindProgramPipeline(*self.pipeline);
+            gl::DrawArrays(gl::LINES, 0, vertices.len() as i32);
+            gl::BindVertexArray(0);
+            gl::BindProgramPipeline(0);
+        }
+
+        check_gl_errors("DebugDrawRenderer::render");
+    }
+}
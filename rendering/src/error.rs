@@ -0,0 +1,81 @@
+use crate::renderer_gl::ShaderType;
+use std::path::PathBuf;
+
+/// Errors from creating GPU resources and loading the assets they are made from.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The shader source did not compile, `log` has the compiler messages.
+    ShaderCompile { stage: ShaderType, log: String },
+    /// The compiled shader did not link into a separable program.
+    ShaderLink { stage: ShaderType, log: String },
+    /// The driver failed to create an object, names the kind of object.
+    ResourceCreation(&'static str),
+    /// Reading an asset from disk failed.
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The asset was read but its contents are invalid or unsupported.
+    InvalidAsset(String),
+    /// The context lacks a needed feature.
+    Unsupported(&'static str),
+    /// A framebuffer cannot be rendered to, `reason` explains why.
+    IncompleteFramebuffer { label: String, reason: String },
+    /// A vertex layout is inconsistent, or does not match the inputs of a shader.
+    VertexLayoutMismatch(String),
+    /// A buffer could not be mapped, or not with the requested access or range.
+    BufferMapping(String),
+    /// A uniform block written on the CPU does not match its declaration in a shader.
+    UniformBlockMismatch(String),
+    /// The inputs of a shader stage are not written by the stage before it.
+    InterfaceMismatch(String),
+    /// An `#include` could not be resolved, or a define is malformed.
+    ShaderPreprocess(String),
+}
+
+impl RenderError {
+    pub(crate) fn io<P: Into<PathBuf>>(path: P, source: std::io::Error) -> RenderError {
+        RenderError::Io {
+            path: path.into(),
+            source,
+        }
+    }
+}
+
+impl std::fmt::Display for RenderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RenderError::ShaderCompile { stage, log } => {
+                write!(f, "{:?} shader failed to compile:\n{}", stage, log)
+            }
+            RenderError::ShaderLink { stage, log } => {
+                write!(f, "{:?} shader failed to link:\n{}", stage, log)
+            }
+            RenderError::ResourceCreation(what) => write!(f, "Failed to create {}", what),
+            RenderError::Io { path, source } => {
+                write!(f, "Failed to read {}: {}", path.display(), source)
+            }
+            RenderError::InvalidAsset(msg) => write!(f, "Invalid asset: {}", msg),
+            RenderError::Unsupported(what) => write!(f, "Unsupported: {}", what),
+            RenderError::IncompleteFramebuffer { label, reason } => {
+                write!(f, "Framebuffer '{}' is incomplete: {}", label, reason)
+            }
+            RenderError::VertexLayoutMismatch(msg) => write!(f, "Vertex layout mismatch: {}", msg),
+            RenderError::BufferMapping(msg) => write!(f, "Buffer mapping failed: {}", msg),
+            RenderError::UniformBlockMismatch(msg) => write!(f, "Uniform block mismatch: {}", msg),
+            RenderError::InterfaceMismatch(msg) => {
+                write!(f, "Shader interface mismatch: {}", msg)
+            }
+            RenderError::ShaderPreprocess(msg) => write!(f, "Shader preprocessing failed: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RenderError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,712 @@
+use crate::error::RenderError;
+use crate::font8x8;
+use ab_glyph::{Font, FontVec, PxScale, ScaleFont};
+use math::vec2::Vec2F32;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Placement of a glyph in the atlas and its metrics, in pixels at scale 1.
+#[derive(Copy, Clone, Debug)]
+pub struct Glyph {
+    /// Texture coordinates of the top left and bottom right corners.
+    pub uv_min: Vec2F32,
+    pub uv_max: Vec2F32,
+    pub size: Vec2F32,
+    /// Top left corner of the glyph, relative to the pen position on the baseline.
+    pub offset: Vec2F32,
+    /// Horizontal distance to the pen position of the next glyph.
+    pub advance: f32,
+}
+
+/// Glyph rectangle in atlas pixels, converted to texture coordinates once the size of
+/// the atlas is final.
+#[derive(Copy, Clone, Debug)]
+struct GlyphRect {
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    offset: Vec2F32,
+    advance: f32,
+}
+
+/// Places rectangles left to right in rows ("shelves") as high as the tallest rectangle
+/// in the row. The height grows as needed.
+struct ShelfPacker {
+    width: i32,
+    x: i32,
+    y: i32,
+    shelf_height: i32,
+}
+
+impl ShelfPacker {
+    const PADDING: i32 = 1;
+
+    fn new(width: i32) -> ShelfPacker {
+        ShelfPacker {
+            width,
+            x: Self::PADDING,
+            y: Self::PADDING,
+            shelf_height: 0,
+        }
+    }
+
+    fn pack(&mut self, w: i32, h: i32) -> Option<(i32, i32)> {
+        if w + 2 * Self::PADDING > self.width {
+            return None;
+        }
+
+        if self.x + w + Self::PADDING > self.width {
+            self.x = Self::PADDING;
+            self.y += self.shelf_height + Self::PADDING;
+            self.shelf_height = 0;
+        }
+
+        let pos = (self.x, self.y);
+        self.x += w + Self::PADDING;
+        self.shelf_height = self.shelf_height.max(h);
+        Some(pos)
+    }
+
+    fn height(&self) -> i32 {
+        self.y + self.shelf_height + Self::PADDING
+    }
+}
+
+/// Single channel (coverage) glyph atlas and the metrics needed to lay out text with it.
+///
+/// Every atlas also holds a small solid block, so untextured shapes can be drawn with the
+/// same texture as the text and end up in the same batch.
+#[derive(Clone, Debug)]
+pub struct FontAtlas {
+    width: i32,
+    height: i32,
+    pixels: Vec<u8>,
+    line_height: f32,
+    ascent: f32,
+    glyphs: HashMap<char, Glyph>,
+    white_uv: Vec2F32,
+}
+
+impl FontAtlas {
+    /// Size of the solid block, it is sampled in the center so filtering does not matter.
+    const WHITE_BLOCK_SIZE: i32 = 3;
+    const TTF_ATLAS_WIDTH: i32 = 512;
+    /// Characters rasterized from TrueType fonts, in addition to the ones asked for.
+    const DEFAULT_CHARSET: &'static str = " !\"#$%&'()*+,-./0123456789:;<=>?@ABCDEFGHIJKLMNOPQRSTUVWXYZ[\\]^_`abcdefghijklmnopqrstuvwxyz{|}~°±µ×÷αβγδθλπσωΔΣΩ←↑→↓•…";
+
+    fn finish(
+        width: i32,
+        height: i32,
+        pixels: Vec<u8>,
+        line_height: f32,
+        ascent: f32,
+        rects: Vec<(char, GlyphRect)>,
+        white: (i32, i32),
+    ) -> FontAtlas {
+        let to_uv = |x: f32, y: f32| Vec2F32::new(x / width as f32, y / height as f32);
+
+        let glyphs = rects
+            .into_iter()
+            .map(|(c, r)| {
+                (
+                    c,
+                    Glyph {
+                        uv_min: to_uv(r.x as f32, r.y as f32),
+                        uv_max: to_uv((r.x + r.w) as f32, (r.y + r.h) as f32),
+                        size: Vec2F32::new(r.w as f32, r.h as f32),
+                        offset: r.offset,
+                        advance: r.advance,
+                    },
+                )
+            })
+            .collect();
+
+        let half_block = Self::WHITE_BLOCK_SIZE as f32 * 0.5f32;
+
+        FontAtlas {
+            width,
+            height,
+            pixels,
+            line_height,
+            ascent,
+            glyphs,
+            white_uv: to_uv(white.0 as f32 + half_block, white.1 as f32 + half_block),
+        }
+    }
+
+    fn fill_white_block(pixels: &mut [u8], atlas_width: i32, pos: (i32, i32)) {
+        (0..Self::WHITE_BLOCK_SIZE).for_each(|y| {
+            let row = ((pos.1 + y) * atlas_width + pos.0) as usize;
+            pixels[row..row + Self::WHITE_BLOCK_SIZE as usize]
+                .iter_mut()
+                .for_each(|p| *p = 0xFF);
+        });
+    }
+
+    /// The embedded 8x8 pixel font, printable ASCII only. Looks best at integer scales.
+    pub fn builtin() -> FontAtlas {
+        const COLUMNS: i32 = 16;
+        let size = font8x8::GLYPH_SIZE;
+        let width = COLUMNS * size;
+        let height = (font8x8::GLYPHS.len() as i32 + COLUMNS - 1) / COLUMNS * size;
+        let mut pixels = vec![0u8; (width * height) as usize];
+
+        let rects = font8x8::GLYPHS
+            .iter()
+            .enumerate()
+            .map(|(glyph_idx, glyph)| {
+                let cell_x = (glyph_idx as i32 % COLUMNS) * size;
+                let cell_y = (glyph_idx as i32 / COLUMNS) * size;
+
+                glyph.iter().enumerate().for_each(|(row, bits)| {
+                    (0..size).for_each(|col| {
+                        if bits & (1 << col) != 0 {
+                            let offset = (cell_y + row as i32) * width + cell_x + col;
+                            pixels[offset as usize] = 0xFF;
+                        }
+                    });
+                });
+
+                (
+                    std::char::from_u32(font8x8::FIRST_CHAR + glyph_idx as u32).unwrap_or('?'),
+                    GlyphRect {
+                        x: cell_x,
+                        y: cell_y,
+                        w: size,
+                        h: size,
+                        offset: Vec2F32::new(0f32, -size as f32),
+                        advance: size as f32,
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+
+        //
+        // the last glyph (U+007F) is a solid block
+        let last = rects.len() as i32 - 1;
+        let white = (
+            (last % COLUMNS) * size + (size - Self::WHITE_BLOCK_SIZE) / 2,
+            (last / COLUMNS) * size + (size - Self::WHITE_BLOCK_SIZE) / 2,
+        );
+
+        Self::finish(
+            width,
+            height,
+            pixels,
+            size as f32,
+            size as f32,
+            rects,
+            white,
+        )
+    }
+
+    /// Rasterizes a TrueType/OpenType font, `pixel_height` is the distance between the
+    /// ascender and the descender. Printable ASCII and some symbols are always included,
+    /// `extra_chars` lists any other characters that are needed.
+    pub fn from_ttf(
+        data: Vec<u8>,
+        pixel_height: f32,
+        extra_chars: &str,
+    ) -> Result<FontAtlas, RenderError> {
+        let font = FontVec::try_from_vec(data)
+            .map_err(|e| RenderError::InvalidAsset(format!("Invalid font: {}", e)))?;
+        let scaled = font.as_scaled(PxScale::from(pixel_height));
+
+        let mut chars = Self::DEFAULT_CHARSET
+            .chars()
+            .chain(extra_chars.chars())
+            .filter(|c| !c.is_control())
+            .collect::<Vec<_>>();
+        chars.sort_unstable();
+        chars.dedup();
+
+        let mut packer = ShelfPacker::new(Self::TTF_ATLAS_WIDTH);
+        let white = packer
+            .pack(Self::WHITE_BLOCK_SIZE, Self::WHITE_BLOCK_SIZE)
+            .ok_or_else(|| RenderError::InvalidAsset("Font atlas too small".to_string()))?;
+
+        //
+        // place the glyphs first, the height of the atlas is known only afterwards
+        let outlined = chars
+            .into_iter()
+            .filter_map(|c| {
+                let glyph_id = font.glyph_id(c);
+                if glyph_id.0 == 0 && c != ' ' {
+                    //
+                    // not in the font, drawn with the fallback glyph
+                    return None;
+                }
+
+                let advance = scaled.h_advance(glyph_id);
+                let outline = scaled.outline_glyph(glyph_id.with_scale(scaled.scale()));
+
+                Some(match outline {
+                    Some(outline) => {
+                        let bounds = outline.px_bounds();
+                        let w = bounds.width() as i32;
+                        let h = bounds.height() as i32;
+                        let pos = packer.pack(w, h).ok_or_else(|| {
+                            RenderError::InvalidAsset(format!(
+                                "Glyph '{}' is {} pixels wide, the font atlas is {}",
+                                c,
+                                w,
+                                Self::TTF_ATLAS_WIDTH
+                            ))
+                        });
+
+                        pos.map(|pos| {
+                            (
+                                c,
+                                GlyphRect {
+                                    x: pos.0,
+                                    y: pos.1,
+                                    w,
+                                    h,
+                                    offset: Vec2F32::new(bounds.min.x, bounds.min.y),
+                                    advance,
+                                },
+                                Some(outline),
+                            )
+                        })
+                    }
+                    None => Ok((
+                        c,
+                        GlyphRect {
+                            x: white.0,
+                            y: white.1,
+                            w: 0,
+                            h: 0,
+                            offset: Vec2F32::same(0f32),
+                            advance,
+                        },
+                        None,
+                    )),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let width = Self::TTF_ATLAS_WIDTH;
+        let height = (packer.height().max(1) as u32).next_power_of_two() as i32;
+        let mut pixels = vec![0u8; (width * height) as usize];
+        Self::fill_white_block(&mut pixels, width, white);
+
+        let rects = outlined
+            .into_iter()
+            .map(|(c, rect, outline)| {
+                if let Some(outline) = outline {
+                    outline.draw(|x, y, coverage| {
+                        let (x, y) = (x as i32, y as i32);
+                        if x < rect.w && y < rect.h {
+                            let offset = ((rect.y + y) * width + rect.x + x) as usize;
+                            pixels[offset] = (coverage.min(1f32) * 255f32) as u8;
+                        }
+                    });
+                }
+
+                (c, rect)
+            })
+            .collect::<Vec<_>>();
+
+        let line_height = scaled.ascent() - scaled.descent() + scaled.line_gap();
+
+        Ok(Self::finish(
+            width,
+            height,
+            pixels,
+            line_height.ceil(),
+            scaled.ascent().ceil(),
+            rects,
+            white,
+        ))
+    }
+
+    pub fn load_ttf<P: AsRef<Path>>(
+        path: P,
+        pixel_height: f32,
+        extra_chars: &str,
+    ) -> Result<FontAtlas, RenderError> {
+        let data = std::fs::read(path.as_ref()).map_err(|e| RenderError::io(path.as_ref(), e))?;
+        Self::from_ttf(data, pixel_height, extra_chars)
+    }
+
+    /// Builds an atlas from a prebuilt glyph sheet described in the AngelCode BMFont text
+    /// format. Only single page fonts are supported, `pixels` holds the coverage of the
+    /// page, one byte per pixel, top row first.
+    pub fn from_bmfont(
+        descriptor: &str,
+        pixels: &[u8],
+        width: i32,
+        height: i32,
+    ) -> Result<FontAtlas, RenderError> {
+        Self::parse_bmfont(descriptor, pixels, width, height).map_err(RenderError::InvalidAsset)
+    }
+
+    fn parse_bmfont(
+        descriptor: &str,
+        pixels: &[u8],
+        width: i32,
+        height: i32,
+    ) -> Result<FontAtlas, String> {
+        if pixels.len() != (width * height) as usize {
+            return Err(format!(
+                "Glyph sheet size mismatch, expected {}x{} pixels",
+                width, height
+            ));
+        }
+
+        let mut line_height = None;
+        let mut base = None;
+        let mut rects = Vec::new();
+
+        for (line_idx, line) in descriptor.lines().enumerate() {
+            let mut tokens = bmfont_tokens(line);
+            let tag = match tokens.next() {
+                Some(tag) => tag,
+                None => continue,
+            };
+
+            let attrs = tokens
+                .filter_map(|t| {
+                    let mut kv = t.splitn(2, '=');
+                    Some((kv.next()?, kv.next()?.trim_matches('"')))
+                })
+                .collect::<HashMap<_, _>>();
+
+            let int_attr = |name: &str| -> Result<i32, String> {
+                attrs
+                    .get(name)
+                    .ok_or_else(|| format!("line {}: missing '{}'", line_idx + 1, name))?
+                    .parse::<i32>()
+                    .map_err(|e| format!("line {}: invalid '{}': {}", line_idx + 1, name, e))
+            };
+
+            match tag {
+                "common" => {
+                    if matches!(attrs.get("pages"), Some(pages) if *pages != "1") {
+                        return Err("Only single page BMFont files are supported".to_string());
+                    }
+                    line_height = Some(int_attr("lineHeight")?);
+                    base = Some(int_attr("base")?);
+                }
+                "char" => {
+                    let base = base
+                        .ok_or_else(|| format!("line {}: 'char' before 'common'", line_idx + 1))?;
+
+                    let c = std::char::from_u32(int_attr("id")? as u32)
+                        .ok_or_else(|| format!("line {}: invalid character id", line_idx + 1))?;
+                    let rect = GlyphRect {
+                        x: int_attr("x")?,
+                        y: int_attr("y")?,
+                        w: int_attr("width")?,
+                        h: int_attr("height")?,
+                        offset: Vec2F32::new(
+                            int_attr("xoffset")? as f32,
+                            (int_attr("yoffset")? - base) as f32,
+                        ),
+                        advance: int_attr("xadvance")? as f32,
+                    };
+
+                    if rect.x < 0
+                        || rect.y < 0
+                        || rect.x + rect.w > width
+                        || rect.y + rect.h > height
+                    {
+                        return Err(format!("line {}: glyph outside of the sheet", line_idx + 1));
+                    }
+
+                    rects.push((c, rect));
+                }
+                _ => {}
+            }
+        }
+
+        let line_height = line_height.ok_or_else(|| "Missing 'common' line".to_string())?;
+        let base = base.unwrap_or(line_height);
+
+        //
+        // the sheet has no solid block, add one in a strip below it
+        let atlas_height = height + Self::WHITE_BLOCK_SIZE + 2;
+        let mut atlas_pixels = pixels.to_vec();
+        atlas_pixels.resize((width * atlas_height) as usize, 0u8);
+        let white = (1, height + 1);
+        Self::fill_white_block(&mut atlas_pixels, width, white);
+
+        Ok(Self::finish(
+            width,
+            atlas_height,
+            atlas_pixels,
+            line_height as f32,
+            base as f32,
+            rects,
+            white,
+        ))
+    }
+
+    /// Loads a BMFont text descriptor and its glyph sheet (PNG, the page file is looked up
+    /// next to the descriptor). Coverage is taken from the alpha channel, or from the gray
+    /// level for sheets without alpha.
+    pub fn load_bmfont<P: AsRef<Path>>(path: P) -> Result<FontAtlas, RenderError> {
+        let path = path.as_ref();
+        let descriptor = std::fs::read_to_string(path).map_err(|e| RenderError::io(path, e))?;
+
+        let page_file = descriptor
+            .lines()
+            .filter(|l| l.starts_with("page "))
+            .flat_map(|l| bmfont_tokens(l).collect::<Vec<_>>())
+            .find_map(|t| t.strip_prefix("file="))
+            .map(|file| file.trim_matches('"').to_string())
+            .ok_or_else(|| {
+                RenderError::InvalidAsset(format!("{}: missing page file", path.display()))
+            })?;
+
+        let page_path = path.with_file_name(page_file);
+        let (pixels, width, height) = load_png_coverage(&page_path)?;
+        Self::from_bmfont(&descriptor, &pixels, width, height)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    /// Coverage, one byte per pixel, top row first.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Distance between consecutive baselines, at scale 1.
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    /// Distance from the top of a line to the baseline, at scale 1.
+    pub fn ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    /// Texture coordinate inside the solid block, used for untextured shapes.
+    pub fn white_uv(&self) -> Vec2F32 {
+        self.white_uv
+    }
+
+    /// Glyph for `c`, characters missing from the font are drawn as '?'.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    /// Size of the text block, lines are separated by '\n'.
+    pub fn measure(&self, text: &str, scale: f32) -> Vec2F32 {
+        let (width, lines) = text.split('\n').fold((0f32, 0), |(width, lines), line| {
+            let line_width = line
+                .chars()
+                .filter_map(|c| self.glyph(c))
+                .map(|g| g.advance)
+                .sum::<f32>();
+            (width.max(line_width), lines + 1)
+        });
+
+        Vec2F32::new(width, lines as f32 * self.line_height) * scale
+    }
+
+    /// Lays out the text with the top left corner of the first line at `pos` and calls
+    /// `emit_quad` with the corners and texture coordinates of every visible glyph, in
+    /// clockwise order starting with the top left corner.
+    pub fn layout<F>(&self, text: &str, pos: Vec2F32, scale: f32, mut emit_quad: F)
+    where
+        F: FnMut([Vec2F32; 4], [Vec2F32; 4]),
+    {
+        let mut pen = Vec2F32::new(pos.x, pos.y + self.ascent * scale);
+
+        text.chars().for_each(|c| {
+            if c == '\n' {
+                pen = Vec2F32::new(pos.x, pen.y + self.line_height * scale);
+                return;
+            }
+
+            let glyph = match self.glyph(c) {
+                Some(glyph) => glyph,
+                None => return,
+            };
+
+            if glyph.size.x > 0f32 && glyph.size.y > 0f32 && !c.is_whitespace() {
+                let p0 = pen + glyph.offset * scale;
+                let p1 = p0 + glyph.size * scale;
+
+                emit_quad(
+                    [p0, Vec2F32::new(p1.x, p0.y), p1, Vec2F32::new(p0.x, p1.y)],
+                    [
+                        glyph.uv_min,
+                        Vec2F32::new(glyph.uv_max.x, glyph.uv_min.y),
+                        glyph.uv_max,
+                        Vec2F32::new(glyph.uv_min.x, glyph.uv_max.y),
+                    ],
+                );
+            }
+
+            pen.x += glyph.advance * scale;
+        });
+    }
+}
+
+/// Splits a BMFont line on whitespace, quoted values may contain spaces.
+fn bmfont_tokens(line: &str) -> impl Iterator<Item = &str> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    let mut quoted = false;
+
+    line.char_indices().for_each(|(idx, c)| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+
+        if c.is_whitespace() && !quoted {
+            if let Some(s) = start.take() {
+                tokens.push(&line[s..idx]);
+            }
+        } else if start.is_none() {
+            start = Some(idx);
+        }
+    });
+
+    if let Some(s) = start {
+        tokens.push(&line[s..]);
+    }
+
+    tokens.into_iter()
+}
+
+fn load_png_coverage(path: &Path) -> Result<(Vec<u8>, i32, i32), RenderError> {
+    let file = std::fs::File::open(path).map_err(|e| RenderError::io(path, e))?;
+    let invalid = |msg: String| RenderError::InvalidAsset(format!("{}: {}", path.display(), msg));
+
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let (info, mut reader) = decoder.read_info().map_err(|e| invalid(e.to_string()))?;
+
+    let mut buffer = vec![0u8; info.buffer_size()];
+    reader
+        .next_frame(&mut buffer)
+        .map_err(|e| invalid(e.to_string()))?;
+
+    if info.bit_depth != png::BitDepth::Eight {
+        return Err(invalid("only 8 bit images are supported".to_string()));
+    }
+
+    let (channels, coverage_channel) = match info.color_type {
+        png::ColorType::Grayscale => (1, 0),
+        png::ColorType::GrayscaleAlpha => (2, 1),
+        png::ColorType::RGB => (3, 0),
+        png::ColorType::RGBA => (4, 3),
+        png::ColorType::Indexed => return Err(invalid("unexpected indexed image".to_string())),
+    };
+
+    let pixels = buffer
+        .chunks(channels)
+        .map(|px| px[coverage_channel])
+        .collect::<Vec<_>>();
+
+    Ok((pixels, info.width as i32, info.height as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_FONT: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../data/fonts/DejaVuSansMono.ttf"
+    );
+
+    #[test]
+    fn test_builtin_font() {
+        let font = FontAtlas::builtin();
+        assert_eq!((font.width(), font.height()), (128, 48));
+        assert_eq!(font.pixels().len(), 128 * 48);
+
+        let uv = font.white_uv();
+        let texel = (uv.y * font.height() as f32) as usize * font.width() as usize
+            + (uv.x * font.width() as f32) as usize;
+        assert_eq!(font.pixels()[texel], 0xFF);
+
+        //
+        // unknown characters fall back to '?'
+        let q = font.glyph('?').unwrap().uv_min;
+        assert_eq!(font.glyph('\u{263A}').unwrap().uv_min, q);
+    }
+
+    #[test]
+    fn test_layout() {
+        let font = FontAtlas::builtin();
+        let mut quads = Vec::new();
+        font.layout("a b\nc", Vec2F32::new(10f32, 20f32), 2f32, |pos, _| {
+            quads.push(pos[0])
+        });
+
+        assert_eq!(
+            quads,
+            vec![
+                Vec2F32::new(10f32, 20f32),
+                Vec2F32::new(42f32, 20f32),
+                Vec2F32::new(10f32, 36f32)
+            ]
+        );
+        assert_eq!(font.measure("a b\nc", 2f32), Vec2F32::new(48f32, 32f32));
+    }
+
+    #[test]
+    fn test_truetype_font() {
+        let font = FontAtlas::load_ttf(TEST_FONT, 16f32, "é").unwrap();
+        assert!(font.line_height() >= 16f32);
+        assert!(
+            (font.width() as u32).is_power_of_two() && (font.height() as u32).is_power_of_two()
+        );
+
+        let a = font.glyph('A').unwrap();
+        assert!(a.size.y > 8f32 && a.offset.y < 0f32);
+        assert!(font.glyph('é').unwrap().uv_min != font.glyph('?').unwrap().uv_min);
+
+        //
+        // monospaced, every glyph advances the pen by the same amount
+        let w = font.measure("iiii", 1f32).x;
+        assert!((font.measure("WWWW", 1f32).x - w).abs() < 0.01f32);
+
+        let mut glyphs = 0;
+        font.layout("Ünïcödé", Vec2F32::same(0f32), 1f32, |_, _| glyphs += 1);
+        assert_eq!(glyphs, 7);
+
+        //
+        // glyphs wider than the atlas are an error, not silently dropped
+        assert!(FontAtlas::load_ttf(TEST_FONT, 1024f32, "").is_err());
+    }
+
+    #[test]
+    fn test_bmfont() {
+        let descriptor = r#"info face="Test Font" size=8
+common lineHeight=10 base=8 scaleW=4 scaleH=4 pages=1
+page id=0 file="test font.png"
+chars count=1
+char id=65 x=1 y=0 width=3 height=4 xoffset=1 yoffset=4 xadvance=5 page=0 chnl=15"#;
+
+        let font = FontAtlas::from_bmfont(descriptor, &[0u8; 16], 4, 4).unwrap();
+        assert_eq!(font.line_height(), 10f32);
+        assert_eq!(font.height(), 4 + FontAtlas::WHITE_BLOCK_SIZE + 2);
+
+        let a = font.glyph('A').unwrap();
+        assert_eq!(a.offset, Vec2F32::new(1f32, -4f32));
+        assert_eq!(a.advance, 5f32);
+        assert_eq!(a.uv_min.x, 0.25f32);
+
+        assert!(FontAtlas::from_bmfont("common lineHeight=10 base=8 pages=2", &[], 0, 0).is_err());
+        assert!(FontAtlas::from_bmfont(
+            "common lineHeight=10 base=8\nchar id=65 x=3 y=0 width=3 height=4 xoffset=0 yoffset=0 xadvance=5",
+            &[0u8; 16],
+            4,
+            4
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,354 @@
+use crate::error::RenderError;
+use std::cell::{Cell, RefCell};
+use std::os::raw::c_void;
+
+/// Severity of a debug message, ordered from the least to the most severe.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl DebugSeverity {
+    const ALL: [DebugSeverity; 4] = [
+        DebugSeverity::Notification,
+        DebugSeverity::Low,
+        DebugSeverity::Medium,
+        DebugSeverity::High,
+    ];
+
+    fn from_gl(severity: gl::types::GLenum) -> DebugSeverity {
+        match severity {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            DebugSeverity::High => gl::DEBUG_SEVERITY_HIGH,
+            DebugSeverity::Medium => gl::DEBUG_SEVERITY_MEDIUM,
+            DebugSeverity::Low => gl::DEBUG_SEVERITY_LOW,
+            DebugSeverity::Notification => gl::DEBUG_SEVERITY_NOTIFICATION,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DebugSeverity::High => "high",
+            DebugSeverity::Medium => "medium",
+            DebugSeverity::Low => "low",
+            DebugSeverity::Notification => "notification",
+        }
+    }
+}
+
+impl std::str::FromStr for DebugSeverity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DebugSeverity, String> {
+        DebugSeverity::ALL
+            .iter()
+            .copied()
+            .find(|sev| sev.name().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| format!("Unknown debug severity '{}'", s.trim()))
+    }
+}
+
+/// A message from the driver, or an error picked up by `check_gl_errors`.
+#[derive(Copy, Clone, Debug)]
+pub struct DebugMessage<'a> {
+    pub source: &'static str,
+    pub kind: &'static str,
+    pub id: u32,
+    pub severity: DebugSeverity,
+    pub text: &'a str,
+}
+
+impl<'a> std::fmt::Display for DebugMessage<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "GL [{}] {} {} ({:#x}): {}",
+            self.severity.name(),
+            self.source,
+            self.kind,
+            self.id,
+            self.text.trim_end()
+        )
+    }
+}
+
+pub type DebugLogger = Box<dyn Fn(&DebugMessage)>;
+
+/// Forwards messages to the `log` crate, high severity messages are errors, medium ones
+/// warnings, low ones info and notifications debug.
+pub fn default_logger() -> DebugLogger {
+    Box::new(forward_to_log)
+}
+
+fn forward_to_log(msg: &DebugMessage) {
+    let level = match msg.severity {
+        DebugSeverity::High => log::Level::Error,
+        DebugSeverity::Medium => log::Level::Warn,
+        DebugSeverity::Low => log::Level::Info,
+        DebugSeverity::Notification => log::Level::Debug,
+    };
+
+    log::log!(level, "{}", msg);
+}
+
+thread_local! {
+    //
+    // GL contexts are current on a single thread and the callback is synchronous
+    static LOGGER: RefCell<Option<DebugLogger>> = const { RefCell::new(None) };
+    static MIN_SEVERITY: Cell<DebugSeverity> = const { Cell::new(DebugSeverity::Notification) };
+}
+
+fn source_name(source: gl::types::GLenum) -> &'static str {
+    match source {
+        gl::DEBUG_SOURCE_API => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    }
+}
+
+fn type_name(kind: gl::types::GLenum) -> &'static str {
+    match kind {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        gl::DEBUG_TYPE_PUSH_GROUP => "push group",
+        gl::DEBUG_TYPE_POP_GROUP => "pop group",
+        _ => "other",
+    }
+}
+
+fn error_name(error: gl::types::GLenum) -> &'static str {
+    match error {
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown error",
+    }
+}
+
+/// Passes a message to the installed logger, or to the `log` crate if there is none.
+fn log_message(msg: &DebugMessage) {
+    if msg.severity < MIN_SEVERITY.with(|s| s.get()) {
+        return;
+    }
+
+    LOGGER.with(|logger| match *logger.borrow() {
+        Some(ref logger) => logger(msg),
+        None => forward_to_log(msg),
+    });
+}
+
+extern "system" fn debug_callback(
+    source: gl::types::GLenum,
+    kind: gl::types::GLenum,
+    id: gl::types::GLuint,
+    severity: gl::types::GLenum,
+    length: gl::types::GLsizei,
+    message: *const gl::types::GLchar,
+    _user_param: *mut c_void,
+) {
+    if message.is_null() {
+        return;
+    }
+
+    let text = unsafe {
+        if length >= 0 {
+            String::from_utf8_lossy(std::slice::from_raw_parts(
+                message as *const u8,
+                length as usize,
+            ))
+        } else {
+            std::ffi::CStr::from_ptr(message).to_string_lossy()
+        }
+    };
+
+    log_message(&DebugMessage {
+        source: source_name(source),
+        kind: type_name(kind),
+        id,
+        severity: DebugSeverity::from_gl(severity),
+        text: &text,
+    });
+}
+
+/// True if the current context was created with the debug flag.
+pub fn is_debug_context() -> bool {
+    unsafe {
+        let mut flags = 0i32;
+        gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+        flags as u32 & gl::CONTEXT_FLAG_DEBUG_BIT != 0
+    }
+}
+
+/// Routes the debug output of the current context to `logger`, messages less severe
+/// than `min_severity` are filtered out by the driver. Needs a debug context.
+pub fn enable_debug_output(
+    min_severity: DebugSeverity,
+    logger: DebugLogger,
+) -> Result<(), RenderError> {
+    if !is_debug_context() {
+        return Err(RenderError::Unsupported(
+            "debug output needs a debug context",
+        ));
+    }
+
+    LOGGER.with(|l| *l.borrow_mut() = Some(logger));
+    MIN_SEVERITY.with(|s| s.set(min_severity));
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_callback), std::ptr::null());
+
+        DebugSeverity::ALL.iter().for_each(|&severity| {
+            gl::DebugMessageControl(
+                gl::DONT_CARE,
+                gl::DONT_CARE,
+                severity.to_gl(),
+                0,
+                std::ptr::null(),
+                if severity >= min_severity {
+                    gl::TRUE
+                } else {
+                    gl::FALSE
+                },
+            );
+        });
+    }
+
+    Ok(())
+}
+
+pub fn disable_debug_output() {
+    unsafe {
+        gl::DebugMessageCallback(None, std::ptr::null());
+        gl::Disable(gl::DEBUG_OUTPUT);
+    }
+
+    LOGGER.with(|l| *l.borrow_mut() = None);
+}
+
+/// Reports every error in the GL error queue, with `context` (usually the name of the
+/// function that made the calls). Does nothing in release builds.
+#[inline]
+pub fn check_gl_errors(context: &str) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            break;
+        }
+
+        log_message(&DebugMessage {
+            source: "glGetError",
+            kind: "error",
+            id: error,
+            severity: DebugSeverity::High,
+            text: &format!("{} in {}", error_name(error), context),
+        });
+    }
+}
+
+/// Kinds of objects that can be labeled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ObjectKind {
+    Buffer,
+    Program,
+    ProgramPipeline,
+    VertexArray,
+    Query,
+    Sampler,
+    Texture,
+    Framebuffer,
+    Renderbuffer,
+}
+
+impl ObjectKind {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            ObjectKind::Buffer => gl::BUFFER,
+            ObjectKind::Program => gl::PROGRAM,
+            ObjectKind::ProgramPipeline => gl::PROGRAM_PIPELINE,
+            ObjectKind::VertexArray => gl::VERTEX_ARRAY,
+            ObjectKind::Query => gl::QUERY,
+            ObjectKind::Sampler => gl::SAMPLER,
+            ObjectKind::Texture => gl::TEXTURE,
+            ObjectKind::Framebuffer => gl::FRAMEBUFFER,
+            ObjectKind::Renderbuffer => gl::RENDERBUFFER,
+        }
+    }
+}
+
+/// Names an object, the label shows up in debug messages and in graphics debuggers.
+pub fn label_object(kind: ObjectKind, object: gl::types::GLuint, label: &str) {
+    if object == 0 || !gl::ObjectLabel::is_loaded() {
+        return;
+    }
+
+    unsafe {
+        gl::ObjectLabel(
+            kind.to_gl(),
+            object,
+            label.len() as gl::types::GLsizei,
+            label.as_ptr() as *const gl::types::GLchar,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity() {
+        assert_eq!("HIGH".parse::<DebugSeverity>(), Ok(DebugSeverity::High));
+        assert_eq!(
+            " notification ".parse::<DebugSeverity>(),
+            Ok(DebugSeverity::Notification)
+        );
+        assert!("fatal".parse::<DebugSeverity>().is_err());
+
+        assert!(DebugSeverity::Notification < DebugSeverity::Low);
+        assert!(DebugSeverity::Medium < DebugSeverity::High);
+        DebugSeverity::ALL
+            .iter()
+            .for_each(|&s| assert_eq!(DebugSeverity::from_gl(s.to_gl()), s));
+    }
+
+    #[test]
+    fn test_message_format() {
+        let msg = DebugMessage {
+            source: source_name(gl::DEBUG_SOURCE_API),
+            kind: type_name(gl::DEBUG_TYPE_ERROR),
+            id: gl::INVALID_ENUM,
+            severity: DebugSeverity::High,
+            text: "bad enum\n",
+        };
+
+        assert_eq!(msg.to_string(), "GL [high] API error (0x500): bad enum");
+    }
+}
@@ -0,0 +1,130 @@
+use crate::gl_debug::check_gl_errors;
+use crate::renderer_gl::UniqueQuery;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// GPU time spent on a section of a frame.
+#[derive(Copy, Clone, Debug)]
+pub struct GpuTiming {
+    pub name: &'static str,
+    pub frame: u64,
+    /// When the section was submitted, on the CPU timeline.
+    pub submitted: Instant,
+    pub micros: f64,
+}
+
+struct PendingQuery {
+    query: UniqueQuery,
+    name: &'static str,
+    frame: u64,
+    submitted: Instant,
+}
+
+/// Times sections of the GPU work with `TIME_ELAPSED` queries. Results are picked up a
+/// few frames later, once available, so the CPU never waits for the GPU. Sections cannot
+/// overlap, beginning a section ends the one in progress.
+pub struct GpuTimer {
+    free: Vec<UniqueQuery>,
+    in_flight: VecDeque<PendingQuery>,
+    active: Option<PendingQuery>,
+}
+
+impl GpuTimer {
+    /// Queries waiting for their result past this limit are dropped, so a GPU that does
+    /// not report results cannot make the timer allocate queries without bound.
+    const MAX_IN_FLIGHT: usize = 64;
+
+    pub fn new() -> GpuTimer {
+        GpuTimer {
+            free: Vec::new(),
+            in_flight: VecDeque::new(),
+            active: None,
+        }
+    }
+
+    fn acquire_query(&mut self) -> Option<UniqueQuery> {
+        self.free.pop().or_else(|| {
+            UniqueQuery::new(unsafe {
+                let mut query = 0u32;
+                gl::CreateQueries(gl::TIME_ELAPSED, 1, &mut query);
+                query
+            })
+        })
+    }
+
+    pub fn begin(&mut self, name: &'static str, frame: u64) {
+        self.end();
+
+        if self.in_flight.len() >= Self::MAX_IN_FLIGHT {
+            if let Some(oldest) = self.in_flight.pop_front() {
+                self.free.push(oldest.query);
+            }
+        }
+
+        if let Some(query) = self.acquire_query() {
+            unsafe {
+                gl::BeginQuery(gl::TIME_ELAPSED, *query);
+            }
+
+            self.active = Some(PendingQuery {
+                query,
+                name,
+                frame,
+                submitted: Instant::now(),
+            });
+        }
+    }
+
+    pub fn end(&mut self) {
+        if let Some(active) = self.active.take() {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+            self.in_flight.push_back(active);
+        }
+    }
+
+    /// Returns the timings that became available since the last call, oldest first.
+    pub fn collect(&mut self) -> Vec<GpuTiming> {
+        let mut timings = Vec::new();
+
+        while let Some(pending) = self.in_flight.front() {
+            let available = unsafe {
+                let mut available = 0i32;
+                gl::GetQueryObjectiv(*pending.query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+                available != 0
+            };
+
+            if !available {
+                //
+                // results come in submission order
+                break;
+            }
+
+            let nanos = unsafe {
+                let mut nanos = 0u64;
+                gl::GetQueryObjectui64v(*pending.query, gl::QUERY_RESULT, &mut nanos);
+                nanos
+            };
+
+            if let Some(pending) = self.in_flight.pop_front() {
+                timings.push(GpuTiming {
+                    name: pending.name,
+                    frame: pending.frame,
+                    submitted: pending.submitted,
+                    micros: nanos as f64 * 0.001f64,
+                });
+                self.free.push(pending.query);
+            }
+        }
+
+        check_gl_errors("GpuTimer::collect");
+        timings
+    }
+}
+
+impl std::default::Default for GpuTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,59 @@
+pub mod debug_draw;
+mod debug_draw_renderer;
+mod error;
+pub mod font;
+mod font8x8;
+pub mod gl_debug;
+mod gpu_timer;
+mod post_process;
+mod program_cache;
+mod reflection;
+mod render_state;
+mod render_target;
 mod renderer_gl;
+mod sampler;
+mod shader_preprocessor;
+mod typed_buffer;
+pub mod ui;
+mod ui_renderer;
+mod uniform_buffer;
+mod vertex_layout;
 
+pub use self::debug_draw_renderer::DebugDrawRenderer;
+pub use self::error::RenderError;
+pub use self::gpu_timer::{GpuTimer, GpuTiming};
+pub use self::post_process::{Palette, PostProcessSettings, PostProcessor, ToneMapper};
+pub use self::program_cache::{DriverInfo, ProgramBinaryCache};
+pub use self::reflection::{
+    check_interface, BufferBlock, BufferVariable, InterfaceVariable, ProgramReflection,
+    ShaderDataType, UniformVariable,
+};
+pub use self::render_state::{
+    BlendFactor, BlendOp, BlendState, CullMode, DepthStencilState, FrontFace, PolygonMode,
+    RasterizerState, RenderStateCache, StencilOp, StencilState,
+};
+pub use self::render_target::{
+    check_framebuffer, Attachment, ColorFormat, DefaultFramebuffer, DepthFormat, FramebufferTarget,
+    RenderTarget, RenderTargetBuilder, RenderTargetDescription,
+};
 pub use self::renderer_gl::{
     create_shader_program_from_string, BufferAccess, OpenGLStateSnapshot, PipelineBuilder,
-    SamplerBuilder, ShaderType, UniqueBuffer, UniqueBufferMapping, UniquePipeline, UniqueSampler,
-    UniqueShaderProgram, UniqueTexture, UniqueVertexArray,
+    ShaderType, UniqueBuffer, UniqueBufferMapping, UniqueFramebuffer, UniquePipeline, UniqueQuery,
+    UniqueRenderbuffer, UniqueSampler, UniqueShaderProgram, UniqueTexture, UniqueVertexArray,
+};
+pub use self::sampler::{
+    CompareFunc, MagFilter, MinFilter, SamplerBuilder, SamplerCache, SamplerDescription, WrapMode,
+};
+pub use self::shader_preprocessor::{
+    PreprocessedShader, ShaderCache, ShaderPermutation, ShaderPreprocessor,
+};
+pub use self::typed_buffer::{BufferReadGuard, BufferWriteGuard, Pod, TypedBuffer};
+pub use self::ui_renderer::UiRenderer;
+pub use self::uniform_buffer::{
+    FrameConstants, Std140, Std140Writer, UniformBinding, UniformBindings, UniformBlock,
+    UniformBuffer,
+};
+pub use self::vertex_layout::{
+    ActiveAttribute, AttribFormat, Vertex, VertexAttribute, VertexBufferLayout, VertexLayout,
+    VertexLayoutBuilder,
 };
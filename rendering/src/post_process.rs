@@ -0,0 +1,543 @@
+use crate::error::RenderError;
+use crate::gl_debug::{check_gl_errors, label_object, ObjectKind};
+use crate::render_state::{BlendState, DepthStencilState, RasterizerState, RenderStateCache};
+use crate::render_target::{
+    ColorFormat, DepthFormat, FramebufferTarget, RenderTarget, RenderTargetBuilder,
+};
+use crate::renderer_gl::*;
+use crate::sampler::{MagFilter, SamplerBuilder, SamplerCache, WrapMode};
+use crate::shader_preprocessor::{ShaderCache, ShaderPermutation};
+use math::colormap::ColorMap;
+use math::colors::RGBAColor;
+use std::rc::Rc;
+
+/// Curve mapping the HDR scene to the displayable range.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ToneMapper {
+    /// Clamps, values above 1 saturate.
+    Clamp,
+    Reinhard,
+    Aces,
+}
+
+impl ToneMapper {
+    pub fn next(self) -> ToneMapper {
+        match self {
+            ToneMapper::Clamp => ToneMapper::Reinhard,
+            ToneMapper::Reinhard => ToneMapper::Aces,
+            ToneMapper::Aces => ToneMapper::Clamp,
+        }
+    }
+
+    /// Value of the `ToneMapper` uniform of the composite shader.
+    fn shader_index(self) -> i32 {
+        match self {
+            ToneMapper::Clamp => 0,
+            ToneMapper::Reinhard => 1,
+            ToneMapper::Aces => 2,
+        }
+    }
+}
+
+/// The `math::colormap::ColorMap` palettes, used for color grading.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Pf1,
+    Pf2,
+    Pf3,
+    Pf4,
+    Pf5,
+    Pf6,
+    Pf7,
+    Pf8,
+}
+
+impl Palette {
+    pub const SIZE: usize = 256;
+
+    pub fn next(self) -> Palette {
+        match self {
+            Palette::Pf1 => Palette::Pf2,
+            Palette::Pf2 => Palette::Pf3,
+            Palette::Pf3 => Palette::Pf4,
+            Palette::Pf4 => Palette::Pf5,
+            Palette::Pf5 => Palette::Pf6,
+            Palette::Pf6 => Palette::Pf7,
+            Palette::Pf7 => Palette::Pf8,
+            Palette::Pf8 => Palette::Pf1,
+        }
+    }
+
+    /// Exactly `SIZE` colors, short palettes are padded with their last color.
+    pub fn colors(self) -> Vec<RGBAColor> {
+        let mut colors = match self {
+            Palette::Pf1 => ColorMap::pf1(),
+            Palette::Pf2 => ColorMap::pf2(),
+            Palette::Pf3 => ColorMap::pf3(),
+            Palette::Pf4 => ColorMap::pf4(),
+            Palette::Pf5 => ColorMap::pf5(),
+            Palette::Pf6 => ColorMap::pf6(),
+            Palette::Pf7 => ColorMap::pf7(),
+            Palette::Pf8 => ColorMap::pf8(),
+        };
+
+        let last = colors
+            .last()
+            .copied()
+            .unwrap_or_else(|| RGBAColor::new(0, 0, 0));
+        colors.resize(Self::SIZE, last);
+        colors
+    }
+}
+
+/// Which passes run and their parameters, can be changed between frames.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct PostProcessSettings {
+    /// When false the scene is drawn straight to the output and nothing else runs.
+    pub enabled: bool,
+    pub bloom: bool,
+    /// Brightness above which pixels glow.
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    /// Each horizontal and vertical blur pair widens the glow.
+    pub bloom_blur_passes: u32,
+    /// Accumulates the previous frames, fading them by `trail_persistence` each frame.
+    pub trails: bool,
+    pub trail_persistence: f32,
+    pub color_grading: bool,
+    pub palette: Palette,
+    /// 0 keeps the original colors, 1 replaces them with the palette colors.
+    pub grading_strength: f32,
+    pub tone_mapper: ToneMapper,
+    pub exposure: f32,
+}
+
+impl std::default::Default for PostProcessSettings {
+    fn default() -> Self {
+        PostProcessSettings {
+            enabled: true,
+            bloom: true,
+            bloom_threshold: 0.8f32,
+            bloom_intensity: 0.6f32,
+            bloom_blur_passes: 3,
+            trails: false,
+            trail_persistence: 0.85f32,
+            color_grading: false,
+            palette: Palette::Pf1,
+            grading_strength: 0.5f32,
+            tone_mapper: ToneMapper::Aces,
+            exposure: 1f32,
+        }
+    }
+}
+
+/// Normalized weights of a 9 tap gaussian, center tap first. The taps other than the
+/// center one are applied on both sides.
+fn gaussian_weights(sigma: f32) -> [f32; 5] {
+    let mut weights = [0f32; 5];
+    weights.iter_mut().enumerate().for_each(|(i, w)| {
+        let x = i as f32;
+        *w = (-(x * x) / (2f32 * sigma * sigma)).exp();
+    });
+
+    let sum = weights[0] + 2f32 * weights[1..].iter().sum::<f32>();
+    weights.iter_mut().for_each(|w| *w /= sum);
+    weights
+}
+
+/// A fragment shader run over the whole target by the shared full screen triangle.
+struct FullscreenPass {
+    fragshader: Rc<UniqueShaderProgram>,
+    pipeline: UniquePipeline,
+}
+
+impl FullscreenPass {
+    fn new(
+        shaders: &mut ShaderCache,
+        vertshader: &UniqueShaderProgram,
+        source: &str,
+        name: &str,
+    ) -> Result<FullscreenPass, RenderError> {
+        let fragshader = shaders.get_with_source(
+            name,
+            source,
+            ShaderType::Fragment,
+            &ShaderPermutation::new(),
+        )?;
+        let pipeline = PipelineBuilder::new()
+            .add_vertex_shader(vertshader)
+            .add_fragment_shader(&fragshader)
+            .build()?;
+
+        label_object(ObjectKind::ProgramPipeline, *pipeline, name);
+
+        Ok(FullscreenPass {
+            fragshader,
+            pipeline,
+        })
+    }
+
+    fn draw(&self, target: &dyn FramebufferTarget) {
+        target.bind();
+        unsafe {
+            gl::BindProgramPipeline(*self.pipeline);
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+    }
+}
+
+/// Renders the scene to an HDR target, then runs the enabled passes: motion trails,
+/// bloom and a final composite that adds the bloom, tone maps and color grades.
+pub struct PostProcessor {
+    settings: PostProcessSettings,
+    scene: RenderTarget,
+    /// Ping-ponged, one holds the accumulated image of the previous frame.
+    history: [RenderTarget; 2],
+    history_idx: usize,
+    history_valid: bool,
+    /// Half resolution, ping-ponged by the blur.
+    bloom: [RenderTarget; 2],
+    palette: UniqueTexture,
+    palette_kind: Palette,
+    sampler: Rc<UniqueSampler>,
+    /// No attributes, the full screen triangle is generated in the vertex shader.
+    vertexarray: UniqueVertexArray,
+    /// Not referenced after creation, but has to live as long as the pipelines.
+    #[allow(dead_code)]
+    vertshader: Rc<UniqueShaderProgram>,
+    extract: FullscreenPass,
+    blur: FullscreenPass,
+    trails: FullscreenPass,
+    composite: FullscreenPass,
+    blur_weights: [f32; 5],
+}
+
+impl PostProcessor {
+    const SCENE_FORMAT: ColorFormat = ColorFormat::Rgba16F;
+
+    fn create_scene_target(width: i32, height: i32) -> Result<RenderTarget, RenderError> {
+        RenderTargetBuilder::new(width, height)
+            .set_label("post scene")
+            .add_color(Self::SCENE_FORMAT)
+            .set_depth(DepthFormat::Depth24Stencil8)
+            .build()
+    }
+
+    fn create_color_target(
+        width: i32,
+        height: i32,
+        label: &str,
+    ) -> Result<RenderTarget, RenderError> {
+        RenderTargetBuilder::new(width, height)
+            .set_label(label)
+            .add_color(Self::SCENE_FORMAT)
+            .build()
+    }
+
+    fn bloom_size(width: i32, height: i32) -> (i32, i32) {
+        ((width / 2).max(1), (height / 2).max(1))
+    }
+
+    fn create_palette_texture(palette: Palette) -> Result<UniqueTexture, RenderError> {
+        let tex = UniqueTexture::new(unsafe {
+            let mut tex = 0u32;
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut tex);
+            gl::TextureStorage2D(tex, 1, gl::RGBA8, Palette::SIZE as i32, 1);
+            tex
+        })
+        .ok_or(RenderError::ResourceCreation("palette texture"))?;
+
+        Self::upload_palette(*tex, palette);
+        label_object(ObjectKind::Texture, *tex, "post palette");
+        Ok(tex)
+    }
+
+    fn upload_palette(tex: gl::types::GLuint, palette: Palette) {
+        let texels = palette
+            .colors()
+            .iter()
+            .flat_map(|c| [c.r, c.g, c.b, 255u8])
+            .collect::<Vec<u8>>();
+
+        unsafe {
+            gl::TextureSubImage2D(
+                tex,
+                0,
+                0,
+                0,
+                Palette::SIZE as i32,
+                1,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                texels.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+    }
+
+    pub fn new(
+        width: i32,
+        height: i32,
+        samplers: &mut SamplerCache,
+        shaders: &mut ShaderCache,
+    ) -> Result<PostProcessor, RenderError> {
+        let (width, height) = (width.max(1), height.max(1));
+        let (bloom_width, bloom_height) = Self::bloom_size(width, height);
+
+        let vertshader = shaders.get_with_source(
+            "fullscreen.vert",
+            include_str!("../../data/shaders/fullscreen.vert"),
+            ShaderType::Vertex,
+            &ShaderPermutation::new(),
+        )?;
+
+        let vertexarray = UniqueVertexArray::new(unsafe {
+            let mut vao = 0u32;
+            gl::CreateVertexArrays(1, &mut vao);
+            vao
+        })
+        .ok_or(RenderError::ResourceCreation("post process vertex array"))?;
+        label_object(ObjectKind::VertexArray, *vertexarray, "fullscreen triangle");
+
+        let sampler = samplers.get(
+            &SamplerBuilder::new()
+                .set_filter(MagFilter::Linear)
+                .set_wrap(WrapMode::ClampToEdge)
+                .description(),
+        )?;
+
+        let settings = PostProcessSettings::default();
+
+        Ok(PostProcessor {
+            scene: Self::create_scene_target(width, height)?,
+            history: [
+                Self::create_color_target(width, height, "post history 0")?,
+                Self::create_color_target(width, height, "post history 1")?,
+            ],
+            history_idx: 0,
+            history_valid: false,
+            bloom: [
+                Self::create_color_target(bloom_width, bloom_height, "post bloom 0")?,
+                Self::create_color_target(bloom_width, bloom_height, "post bloom 1")?,
+            ],
+            palette: Self::create_palette_texture(settings.palette)?,
+            palette_kind: settings.palette,
+            sampler,
+            extract: FullscreenPass::new(
+                shaders,
+                &vertshader,
+                include_str!("../../data/shaders/bloom_extract.frag"),
+                "bloom_extract.frag",
+            )?,
+            blur: FullscreenPass::new(
+                shaders,
+                &vertshader,
+                include_str!("../../data/shaders/blur.frag"),
+                "blur.frag",
+            )?,
+            trails: FullscreenPass::new(
+                shaders,
+                &vertshader,
+                include_str!("../../data/shaders/trails.frag"),
+                "trails.frag",
+            )?,
+            composite: FullscreenPass::new(
+                shaders,
+                &vertshader,
+                include_str!("../../data/shaders/composite.frag"),
+                "composite.frag",
+            )?,
+            vertexarray,
+            vertshader,
+            settings,
+            blur_weights: gaussian_weights(2f32),
+        })
+    }
+
+    pub fn settings(&self) -> &PostProcessSettings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut PostProcessSettings {
+        &mut self.settings
+    }
+
+    /// Recreates the targets if the size changed, the trails start over.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), RenderError> {
+        let (width, height) = (width.max(1), height.max(1));
+        if (width, height) == self.scene.size() {
+            return Ok(());
+        }
+
+        let (bloom_width, bloom_height) = Self::bloom_size(width, height);
+        self.scene.resize(width, height)?;
+        self.history
+            .iter_mut()
+            .try_for_each(|target| target.resize(width, height))?;
+        self.bloom
+            .iter_mut()
+            .try_for_each(|target| target.resize(bloom_width, bloom_height))?;
+        self.history_valid = false;
+        Ok(())
+    }
+
+    /// Binds and clears the HDR target the scene is drawn into.
+    pub fn begin_scene(&self, clear_color: [f32; 4]) {
+        self.scene.bind();
+        self.scene.clear_color(0, clear_color);
+        self.scene.clear_depth_stencil(1f32, 0);
+    }
+
+    /// Runs the enabled passes on the scene drawn since `begin_scene` and writes the
+    /// result to `output`, which is left bound.
+    pub fn finish(&mut self, states: &mut RenderStateCache, output: &dyn FramebufferTarget) {
+        states.set_blend(&BlendState::disabled());
+        states.set_depth_stencil(&DepthStencilState::disabled());
+        states.set_rasterizer(&RasterizerState::solid());
+
+        if self.palette_kind != self.settings.palette {
+            Self::upload_palette(*self.palette, self.settings.palette);
+            self.palette_kind = self.settings.palette;
+        }
+
+        unsafe {
+            gl::BindVertexArray(*self.vertexarray);
+            (0..3).for_each(|unit| gl::BindSampler(unit, **self.sampler));
+        }
+
+        let source = if self.settings.trails {
+            self.run_trails()
+        } else {
+            self.history_valid = false;
+            self.scene.color_texture(0)
+        };
+
+        if self.settings.bloom {
+            self.run_bloom(source);
+        }
+        self.run_composite(source, output);
+
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::BindProgramPipeline(0);
+        }
+
+        check_gl_errors("PostProcessor::finish");
+    }
+
+    /// Blends the scene with the faded accumulation of the previous frames, returns the
+    /// texture with the result.
+    fn run_trails(&mut self) -> Option<gl::types::GLuint> {
+        let (prev, next) = (self.history_idx, self.history_idx ^ 1);
+        if !self.history_valid {
+            self.history[prev].clear_color(0, [0f32; 4]);
+            self.history_valid = true;
+        }
+
+        unsafe {
+            gl::ProgramUniform1f(
+                **self.trails.fragshader,
+                0,
+                self.settings.trail_persistence.clamp(0f32, 0.999f32),
+            );
+            gl::BindTextureUnit(0, self.scene.color_texture(0).unwrap_or(0));
+            gl::BindTextureUnit(1, self.history[prev].color_texture(0).unwrap_or(0));
+        }
+        self.trails.draw(&self.history[next]);
+
+        self.history_idx = next;
+        self.history[next].color_texture(0)
+    }
+
+    /// Leaves the blurred bright parts of `source` in the first bloom target.
+    fn run_bloom(&self, source: Option<gl::types::GLuint>) {
+        let threshold = self.settings.bloom_threshold.max(0f32);
+        let (width, height) = self.bloom[0].size();
+
+        unsafe {
+            gl::ProgramUniform1f(**self.extract.fragshader, 0, threshold);
+            gl::ProgramUniform1f(**self.extract.fragshader, 1, threshold * 0.5f32);
+            gl::BindTextureUnit(0, source.unwrap_or(0));
+            gl::ProgramUniform1fv(
+                **self.blur.fragshader,
+                1,
+                self.blur_weights.len() as i32,
+                self.blur_weights.as_ptr(),
+            );
+        }
+        self.extract.draw(&self.bloom[0]);
+
+        (0..self.settings.bloom_blur_passes.max(1)).for_each(|_| {
+            unsafe {
+                gl::ProgramUniform2f(**self.blur.fragshader, 0, 1f32 / width as f32, 0f32);
+                gl::BindTextureUnit(0, self.bloom[0].color_texture(0).unwrap_or(0));
+            }
+            self.blur.draw(&self.bloom[1]);
+
+            unsafe {
+                gl::ProgramUniform2f(**self.blur.fragshader, 0, 0f32, 1f32 / height as f32);
+                gl::BindTextureUnit(0, self.bloom[1].color_texture(0).unwrap_or(0));
+            }
+            self.blur.draw(&self.bloom[0]);
+        });
+    }
+
+    fn run_composite(&self, source: Option<gl::types::GLuint>, output: &dyn FramebufferTarget) {
+        let settings = &self.settings;
+        let fs = **self.composite.fragshader;
+
+        unsafe {
+            gl::ProgramUniform1f(
+                fs,
+                0,
+                if settings.bloom {
+                    settings.bloom_intensity.max(0f32)
+                } else {
+                    0f32
+                },
+            );
+            gl::ProgramUniform1f(
+                fs,
+                1,
+                if settings.color_grading {
+                    settings.grading_strength.clamp(0f32, 1f32)
+                } else {
+                    0f32
+                },
+            );
+            gl::ProgramUniform1f(fs, 2, settings.exposure.max(0f32));
+            gl::ProgramUniform1i(fs, 3, settings.tone_mapper.shader_index());
+
+            gl::BindTextureUnit(0, source.unwrap_or(0));
+            gl::BindTextureUnit(1, self.bloom[0].color_texture(0).unwrap_or(0));
+            gl::BindTextureUnit(2, *self.palette);
+        }
+        self.composite.draw(output);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gaussian_weights() {
+        let weights = gaussian_weights(2f32);
+        let sum = weights[0] + 2f32 * weights[1..].iter().sum::<f32>();
+
+        assert!((sum - 1f32).abs() < 1.0e-5f32);
+        assert!(weights.windows(2).all(|w| w[0] > w[1]));
+    }
+
+    #[test]
+    fn test_palettes() {
+        let mut palette = Palette::Pf1;
+        (0..8).for_each(|_| {
+            assert_eq!(palette.colors().len(), Palette::SIZE);
+            palette = palette.next();
+        });
+        assert_eq!(palette, Palette::Pf1);
+
+        assert_eq!(ToneMapper::Aces.next(), ToneMapper::Clamp);
+        assert_eq!(ToneMapper::Reinhard.shader_index(), 1);
+    }
+}
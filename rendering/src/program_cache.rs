@@ -0,0 +1,293 @@
+use crate::error::RenderError;
+use crate::gl_debug::check_gl_errors;
+use crate::renderer_gl::{ShaderType, UniqueShaderProgram};
+use std::convert::TryInto;
+use std::path::PathBuf;
+
+/// Identifies the driver, binaries are only loaded by the driver that produced them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DriverInfo {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+}
+
+impl DriverInfo {
+    pub fn query() -> DriverInfo {
+        let get_string = |name: gl::types::GLenum| unsafe {
+            let s = gl::GetString(name);
+            if s.is_null() {
+                String::new()
+            } else {
+                std::ffi::CStr::from_ptr(s as *const std::os::raw::c_char)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+
+        DriverInfo {
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            version: get_string(gl::VERSION),
+        }
+    }
+
+    fn id(&self) -> String {
+        format!("{}\n{}\n{}", self.vendor, self.renderer, self.version)
+    }
+}
+
+/// 64 bit FNV-1a, stable across builds unlike `DefaultHasher`.
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    parts
+        .iter()
+        .flat_map(|part| part.iter())
+        .fold(0xcbf2_9ce4_8422_2325u64, |hash, &b| {
+            (hash ^ b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+/// Contents of a cache file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CachedBinary {
+    key: u64,
+    driver: String,
+    format: gl::types::GLenum,
+    binary: Vec<u8>,
+}
+
+impl CachedBinary {
+    const MAGIC: &'static [u8; 4] = b"PBC1";
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(20 + self.driver.len() + self.binary.len());
+        bytes.extend_from_slice(Self::MAGIC);
+        bytes.extend_from_slice(&self.key.to_le_bytes());
+        bytes.extend_from_slice(&self.format.to_le_bytes());
+        bytes.extend_from_slice(&(self.driver.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(self.driver.as_bytes());
+        bytes.extend_from_slice(&self.binary);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<CachedBinary> {
+        let mut rest = bytes.strip_prefix(&Self::MAGIC[..])?;
+        let mut take = |len: usize| {
+            if rest.len() < len {
+                return None;
+            }
+            let (head, tail) = rest.split_at(len);
+            rest = tail;
+            Some(head)
+        };
+
+        let key = u64::from_le_bytes(take(8)?.try_into().ok()?);
+        let format = u32::from_le_bytes(take(4)?.try_into().ok()?);
+        let driver_len = u32::from_le_bytes(take(4)?.try_into().ok()?) as usize;
+        let driver = String::from_utf8(take(driver_len)?.to_vec()).ok()?;
+        let binary = rest.to_vec();
+
+        if binary.is_empty() {
+            return None;
+        }
+
+        Some(CachedBinary {
+            key,
+            driver,
+            format,
+            binary,
+        })
+    }
+}
+
+/// Stores linked programs on disk with `glGetProgramBinary`, one file per program, named
+/// after a hash of the source, the stage and the driver. Binaries the driver rejects, after
+/// an update for example, are deleted and the program is compiled again.
+pub struct ProgramBinaryCache {
+    dir: PathBuf,
+    driver: DriverInfo,
+}
+
+impl ProgramBinaryCache {
+    /// Creates `dir` if needed. Fails if the driver offers no binary formats.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<ProgramBinaryCache, RenderError> {
+        let mut formats = 0i32;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        if formats <= 0 {
+            return Err(RenderError::Unsupported("program binary formats"));
+        }
+
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir).map_err(|e| RenderError::io(dir.clone(), e))?;
+
+        Ok(ProgramBinaryCache {
+            dir,
+            driver: DriverInfo::query(),
+        })
+    }
+
+    pub fn driver(&self) -> &DriverInfo {
+        &self.driver
+    }
+
+    fn key(driver: &DriverInfo, source: &str, stage: ShaderType) -> u64 {
+        fnv1a(&[
+            driver.id().as_bytes(),
+            format!("{:?}", stage).as_bytes(),
+            source.as_bytes(),
+        ])
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+
+    fn discard(&self, key: u64, reason: &str) {
+        log::info!(
+            "Discarding cached program {}: {}",
+            self.path(key).display(),
+            reason
+        );
+        let _ = std::fs::remove_file(self.path(key));
+    }
+
+    /// The program for `source`, if a binary for it was stored and the driver accepts it.
+    pub fn load(&self, source: &str, stage: ShaderType) -> Option<UniqueShaderProgram> {
+        let key = Self::key(&self.driver, source, stage);
+        let bytes = std::fs::read(self.path(key)).ok()?;
+
+        let cached = match CachedBinary::from_bytes(&bytes) {
+            Some(cached) if cached.key == key && cached.driver == self.driver.id() => cached,
+            _ => {
+                self.discard(key, "corrupt or from another driver");
+                return None;
+            }
+        };
+
+        let program = UniqueShaderProgram::new(unsafe { gl::CreateProgram() })?;
+        unsafe {
+            gl::ProgramParameteri(*program, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
+        }
+        check_gl_errors("ProgramBinaryCache::load");
+
+        let linked = unsafe {
+            gl::ProgramBinary(
+                *program,
+                cached.format,
+                cached.binary.as_ptr() as *const gl::types::GLvoid,
+                cached.binary.len() as i32,
+            );
+            //
+            // a rejected binary is not an error, clear the INVALID_ENUM some drivers raise
+            gl::GetError();
+
+            let mut link_status = 0i32;
+            gl::GetProgramiv(*program, gl::LINK_STATUS, &mut link_status);
+            link_status == gl::TRUE as i32
+        };
+
+        if linked {
+            Some(program)
+        } else {
+            self.discard(key, "rejected by the driver");
+            None
+        }
+    }
+
+    /// Saves the binary of `program`, which was linked from `source`. The program should be
+    /// linked with `PROGRAM_BINARY_RETRIEVABLE_HINT` set.
+    pub fn store(
+        &self,
+        program: gl::types::GLuint,
+        source: &str,
+        stage: ShaderType,
+    ) -> Result<(), RenderError> {
+        let mut length = 0i32;
+        unsafe {
+            gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+        if length <= 0 {
+            return Err(RenderError::Unsupported("retrieving the program binary"));
+        }
+
+        let mut binary = vec![0u8; length as usize];
+        let mut written = 0i32;
+        let mut format = 0u32;
+        unsafe {
+            gl::GetProgramBinary(
+                program,
+                length,
+                &mut written,
+                &mut format,
+                binary.as_mut_ptr() as *mut gl::types::GLvoid,
+            );
+        }
+        check_gl_errors("ProgramBinaryCache::store");
+        binary.truncate(written.max(0) as usize);
+
+        let key = Self::key(&self.driver, source, stage);
+        let cached = CachedBinary {
+            key,
+            driver: self.driver.id(),
+            format,
+            binary,
+        };
+
+        let path = self.path(key);
+        std::fs::write(&path, cached.to_bytes()).map_err(|e| RenderError::io(path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn driver(version: &str) -> DriverInfo {
+        DriverInfo {
+            vendor: "Vendor".to_string(),
+            renderer: "Renderer".to_string(),
+            version: version.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_keys() {
+        let src = "#version 450 core\nvoid main() {}";
+        let key = ProgramBinaryCache::key(&driver("4.6"), src, ShaderType::Vertex);
+        assert_eq!(
+            key,
+            ProgramBinaryCache::key(&driver("4.6"), src, ShaderType::Vertex)
+        );
+        assert_ne!(
+            key,
+            ProgramBinaryCache::key(&driver("4.6.1"), src, ShaderType::Vertex)
+        );
+        assert_ne!(
+            key,
+            ProgramBinaryCache::key(&driver("4.6"), src, ShaderType::Fragment)
+        );
+        assert_ne!(
+            key,
+            ProgramBinaryCache::key(&driver("4.6"), "void main() {}", ShaderType::Vertex)
+        );
+        assert_eq!(fnv1a(&[b"a"]), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn test_file_format() {
+        let cached = CachedBinary {
+            key: 0x1234_5678_9abc_def0,
+            driver: driver("4.6").id(),
+            format: 0x8741,
+            binary: vec![1, 2, 3, 4, 5],
+        };
+
+        let bytes = cached.to_bytes();
+        assert_eq!(CachedBinary::from_bytes(&bytes), Some(cached));
+        assert_eq!(CachedBinary::from_bytes(&bytes[..bytes.len() - 5]), None);
+        assert_eq!(CachedBinary::from_bytes(&bytes[..10]), None);
+        assert_eq!(CachedBinary::from_bytes(b"PBC0"), None);
+    }
+}
@@ -0,0 +1,498 @@
+use crate::gl_debug::check_gl_errors;
+
+/// Type of a shader variable, as reported by the driver.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShaderDataType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler2D,
+    Sampler2DArray,
+    Sampler2DShadow,
+    /// Any other type, holds the GL type enum.
+    Other(gl::types::GLenum),
+}
+
+impl ShaderDataType {
+    pub fn from_gl(gl_type: gl::types::GLenum) -> ShaderDataType {
+        match gl_type {
+            gl::FLOAT => ShaderDataType::Float,
+            gl::FLOAT_VEC2 => ShaderDataType::Vec2,
+            gl::FLOAT_VEC3 => ShaderDataType::Vec3,
+            gl::FLOAT_VEC4 => ShaderDataType::Vec4,
+            gl::INT => ShaderDataType::Int,
+            gl::INT_VEC2 => ShaderDataType::IVec2,
+            gl::INT_VEC3 => ShaderDataType::IVec3,
+            gl::INT_VEC4 => ShaderDataType::IVec4,
+            gl::UNSIGNED_INT => ShaderDataType::UInt,
+            gl::UNSIGNED_INT_VEC2 => ShaderDataType::UVec2,
+            gl::UNSIGNED_INT_VEC3 => ShaderDataType::UVec3,
+            gl::UNSIGNED_INT_VEC4 => ShaderDataType::UVec4,
+            gl::BOOL => ShaderDataType::Bool,
+            gl::FLOAT_MAT2 => ShaderDataType::Mat2,
+            gl::FLOAT_MAT3 => ShaderDataType::Mat3,
+            gl::FLOAT_MAT4 => ShaderDataType::Mat4,
+            gl::SAMPLER_2D => ShaderDataType::Sampler2D,
+            gl::SAMPLER_2D_ARRAY => ShaderDataType::Sampler2DArray,
+            gl::SAMPLER_2D_SHADOW => ShaderDataType::Sampler2DShadow,
+            other => ShaderDataType::Other(other),
+        }
+    }
+
+    /// Number of scalar components, 0 for opaque and unknown types.
+    pub fn components(self) -> u32 {
+        match self {
+            ShaderDataType::Float
+            | ShaderDataType::Int
+            | ShaderDataType::UInt
+            | ShaderDataType::Bool => 1,
+            ShaderDataType::Vec2 | ShaderDataType::IVec2 | ShaderDataType::UVec2 => 2,
+            ShaderDataType::Vec3 | ShaderDataType::IVec3 | ShaderDataType::UVec3 => 3,
+            ShaderDataType::Vec4
+            | ShaderDataType::IVec4
+            | ShaderDataType::UVec4
+            | ShaderDataType::Mat2 => 4,
+            ShaderDataType::Mat3 => 9,
+            ShaderDataType::Mat4 => 16,
+            ShaderDataType::Sampler2D
+            | ShaderDataType::Sampler2DArray
+            | ShaderDataType::Sampler2DShadow
+            | ShaderDataType::Other(_) => 0,
+        }
+    }
+
+    /// True for signed and unsigned integer scalars and vectors.
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            ShaderDataType::Int
+                | ShaderDataType::IVec2
+                | ShaderDataType::IVec3
+                | ShaderDataType::IVec4
+                | ShaderDataType::UInt
+                | ShaderDataType::UVec2
+                | ShaderDataType::UVec3
+                | ShaderDataType::UVec4
+        )
+    }
+
+    pub fn is_sampler(self) -> bool {
+        matches!(
+            self,
+            ShaderDataType::Sampler2D
+                | ShaderDataType::Sampler2DArray
+                | ShaderDataType::Sampler2DShadow
+        )
+    }
+}
+
+/// An input or output of a shader stage.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InterfaceVariable {
+    /// Members of interface blocks are named `Block.member`.
+    pub name: String,
+    pub data_type: ShaderDataType,
+    pub array_size: u32,
+    /// -1 if the variable has no location, like built-ins and members of blocks.
+    pub location: i32,
+}
+
+impl InterfaceVariable {
+    pub fn is_builtin(&self) -> bool {
+        self.name.starts_with("gl_")
+    }
+}
+
+/// An active uniform, either in the default block or in a uniform block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniformVariable {
+    pub name: String,
+    pub data_type: ShaderDataType,
+    pub array_size: u32,
+    /// -1 for members of uniform blocks.
+    pub location: i32,
+    /// Index in `ProgramReflection::uniform_blocks`, `None` for the default block.
+    pub block: Option<usize>,
+    /// Offset in bytes from the start of the block, `None` for the default block.
+    pub offset: Option<u32>,
+    pub array_stride: u32,
+    pub matrix_stride: u32,
+    /// Texture unit of samplers.
+    pub unit: Option<i32>,
+}
+
+/// A uniform or shader storage block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferBlock {
+    pub name: String,
+    /// Index of the block in the program, for `glUniformBlockBinding` and friends.
+    pub index: u32,
+    pub binding: u32,
+    /// Minimum size of the buffer bound to the block, in bytes.
+    pub data_size: u32,
+}
+
+/// A member of a shader storage block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferVariable {
+    pub name: String,
+    pub data_type: ShaderDataType,
+    /// 0 for runtime sized arrays.
+    pub array_size: u32,
+    /// Index in `ProgramReflection::storage_blocks`.
+    pub block: usize,
+    pub offset: u32,
+    pub array_stride: u32,
+    pub matrix_stride: u32,
+    /// Stride of the outermost array the variable is in, like `particles[]`.
+    pub top_level_array_stride: u32,
+}
+
+/// Everything a linked program exposes to the application, queried with the program
+/// interface API.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramReflection {
+    pub inputs: Vec<InterfaceVariable>,
+    pub outputs: Vec<InterfaceVariable>,
+    pub uniforms: Vec<UniformVariable>,
+    pub uniform_blocks: Vec<BufferBlock>,
+    pub storage_blocks: Vec<BufferBlock>,
+    pub buffer_variables: Vec<BufferVariable>,
+}
+
+fn resource_count(program: gl::types::GLuint, interface: gl::types::GLenum) -> u32 {
+    let mut count = 0i32;
+    unsafe {
+        gl::GetProgramInterfaceiv(program, interface, gl::ACTIVE_RESOURCES, &mut count);
+    }
+    count.max(0) as u32
+}
+
+/// Values of `props` for one resource, in the same order.
+fn resource_props<const N: usize>(
+    program: gl::types::GLuint,
+    interface: gl::types::GLenum,
+    index: u32,
+    props: [gl::types::GLenum; N],
+) -> [i32; N] {
+    let mut values = [0i32; N];
+    unsafe {
+        gl::GetProgramResourceiv(
+            program,
+            interface,
+            index,
+            N as i32,
+            props.as_ptr(),
+            N as i32,
+            std::ptr::null_mut(),
+            values.as_mut_ptr(),
+        );
+    }
+    values
+}
+
+fn resource_name(program: gl::types::GLuint, interface: gl::types::GLenum, index: u32) -> String {
+    let [name_len] = resource_props(program, interface, index, [gl::NAME_LENGTH]);
+    if name_len <= 0 {
+        return String::new();
+    }
+
+    let mut name = vec![0u8; name_len as usize];
+    let mut written = 0i32;
+    unsafe {
+        gl::GetProgramResourceName(
+            program,
+            interface,
+            index,
+            name_len,
+            &mut written,
+            name.as_mut_ptr() as *mut gl::types::GLchar,
+        );
+    }
+    name.truncate(written.max(0) as usize);
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+/// Block indices reported by GL are -1 outside of blocks.
+fn block_index(value: i32) -> Option<usize> {
+    if value < 0 {
+        None
+    } else {
+        Some(value as usize)
+    }
+}
+
+impl ProgramReflection {
+    pub fn query(program: gl::types::GLuint) -> ProgramReflection {
+        let reflection = ProgramReflection {
+            inputs: Self::query_interface(program, gl::PROGRAM_INPUT),
+            outputs: Self::query_interface(program, gl::PROGRAM_OUTPUT),
+            uniforms: Self::query_uniforms(program),
+            uniform_blocks: Self::query_blocks(program, gl::UNIFORM_BLOCK),
+            storage_blocks: Self::query_blocks(program, gl::SHADER_STORAGE_BLOCK),
+            buffer_variables: Self::query_buffer_variables(program),
+        };
+        check_gl_errors("ProgramReflection::query");
+        reflection
+    }
+
+    /// Inputs (`gl::PROGRAM_INPUT`) or outputs (`gl::PROGRAM_OUTPUT`) of `program`.
+    pub(crate) fn query_interface(
+        program: gl::types::GLuint,
+        interface: gl::types::GLenum,
+    ) -> Vec<InterfaceVariable> {
+        (0..resource_count(program, interface))
+            .map(|idx| {
+                let [gl_type, array_size, location] = resource_props(
+                    program,
+                    interface,
+                    idx,
+                    [gl::TYPE, gl::ARRAY_SIZE, gl::LOCATION],
+                );
+                InterfaceVariable {
+                    name: resource_name(program, interface, idx),
+                    data_type: ShaderDataType::from_gl(gl_type as u32),
+                    array_size: array_size.max(0) as u32,
+                    location,
+                }
+            })
+            .collect()
+    }
+
+    fn query_uniforms(program: gl::types::GLuint) -> Vec<UniformVariable> {
+        (0..resource_count(program, gl::UNIFORM))
+            .map(|idx| {
+                let [gl_type, array_size, location, block, offset, array_stride, matrix_stride] =
+                    resource_props(
+                        program,
+                        gl::UNIFORM,
+                        idx,
+                        [
+                            gl::TYPE,
+                            gl::ARRAY_SIZE,
+                            gl::LOCATION,
+                            gl::BLOCK_INDEX,
+                            gl::OFFSET,
+                            gl::ARRAY_STRIDE,
+                            gl::MATRIX_STRIDE,
+                        ],
+                    );
+                let data_type = ShaderDataType::from_gl(gl_type as u32);
+                let block = block_index(block);
+
+                let unit = if data_type.is_sampler() && location >= 0 {
+                    let mut unit = 0i32;
+                    unsafe {
+                        gl::GetUniformiv(program, location, &mut unit);
+                    }
+                    Some(unit)
+                } else {
+                    None
+                };
+
+                UniformVariable {
+                    name: resource_name(program, gl::UNIFORM, idx),
+                    data_type,
+                    array_size: array_size.max(0) as u32,
+                    location,
+                    block,
+                    offset: block.map(|_| offset.max(0) as u32),
+                    array_stride: array_stride.max(0) as u32,
+                    matrix_stride: matrix_stride.max(0) as u32,
+                    unit,
+                }
+            })
+            .collect()
+    }
+
+    fn query_blocks(program: gl::types::GLuint, interface: gl::types::GLenum) -> Vec<BufferBlock> {
+        (0..resource_count(program, interface))
+            .map(|idx| {
+                let [binding, data_size] = resource_props(
+                    program,
+                    interface,
+                    idx,
+                    [gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE],
+                );
+                BufferBlock {
+                    name: resource_name(program, interface, idx),
+                    index: idx,
+                    binding: binding.max(0) as u32,
+                    data_size: data_size.max(0) as u32,
+                }
+            })
+            .collect()
+    }
+
+    fn query_buffer_variables(program: gl::types::GLuint) -> Vec<BufferVariable> {
+        (0..resource_count(program, gl::BUFFER_VARIABLE))
+            .map(|idx| {
+                let [gl_type, array_size, block, offset, array_stride, matrix_stride, top_level_stride] =
+                    resource_props(
+                        program,
+                        gl::BUFFER_VARIABLE,
+                        idx,
+                        [
+                            gl::TYPE,
+                            gl::ARRAY_SIZE,
+                            gl::BLOCK_INDEX,
+                            gl::OFFSET,
+                            gl::ARRAY_STRIDE,
+                            gl::MATRIX_STRIDE,
+                            gl::TOP_LEVEL_ARRAY_STRIDE,
+                        ],
+                    );
+                BufferVariable {
+                    name: resource_name(program, gl::BUFFER_VARIABLE, idx),
+                    data_type: ShaderDataType::from_gl(gl_type as u32),
+                    array_size: array_size.max(0) as u32,
+                    block: block.max(0) as usize,
+                    offset: offset.max(0) as u32,
+                    array_stride: array_stride.max(0) as u32,
+                    matrix_stride: matrix_stride.max(0) as u32,
+                    top_level_array_stride: top_level_stride.max(0) as u32,
+                }
+            })
+            .collect()
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<&UniformVariable> {
+        self.uniforms.iter().find(|u| u.name == name)
+    }
+
+    pub fn uniform_block(&self, name: &str) -> Option<&BufferBlock> {
+        self.uniform_blocks.iter().find(|b| b.name == name)
+    }
+
+    pub fn storage_block(&self, name: &str) -> Option<&BufferBlock> {
+        self.storage_blocks.iter().find(|b| b.name == name)
+    }
+
+    /// Members of the uniform block at `block` in `uniform_blocks`.
+    pub fn block_uniforms(&self, block: usize) -> impl Iterator<Item = &UniformVariable> {
+        self.uniforms.iter().filter(move |u| u.block == Some(block))
+    }
+
+    /// Members of the storage block at `block` in `storage_blocks`.
+    pub fn block_variables(&self, block: usize) -> impl Iterator<Item = &BufferVariable> {
+        self.buffer_variables
+            .iter()
+            .filter(move |v| v.block == block)
+    }
+}
+
+/// Checks that every input of a stage is written by the previous stage with the same
+/// type. Inputs are matched by name, then by location. Unused outputs are fine.
+pub fn check_interface(
+    outputs: &[InterfaceVariable],
+    inputs: &[InterfaceVariable],
+) -> Result<(), String> {
+    let problems = inputs
+        .iter()
+        .filter(|input| !input.is_builtin())
+        .filter_map(|input| {
+            let output = outputs.iter().find(|o| o.name == input.name).or_else(|| {
+                outputs
+                    .iter()
+                    .find(|o| input.location >= 0 && o.location == input.location)
+            });
+
+            match output {
+                None => Some(format!("'{}' is not written", input.name)),
+                Some(output)
+                    if output.data_type != input.data_type
+                        || output.array_size != input.array_size =>
+                {
+                    Some(format!(
+                        "'{}' is a {:?}[{}] but '{}' is written as a {:?}[{}]",
+                        input.name,
+                        input.data_type,
+                        input.array_size,
+                        output.name,
+                        output.data_type,
+                        output.array_size
+                    ))
+                }
+                Some(_) => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str, data_type: ShaderDataType, location: i32) -> InterfaceVariable {
+        InterfaceVariable {
+            name: name.to_string(),
+            data_type,
+            array_size: 1,
+            location,
+        }
+    }
+
+    #[test]
+    fn test_data_types() {
+        assert_eq!(
+            ShaderDataType::from_gl(gl::UNSIGNED_INT_VEC2),
+            ShaderDataType::UVec2
+        );
+        assert!(ShaderDataType::UVec2.is_integer());
+        assert_eq!(ShaderDataType::Mat4.components(), 16);
+        assert!(ShaderDataType::from_gl(gl::SAMPLER_2D_ARRAY).is_sampler());
+        assert_eq!(
+            ShaderDataType::from_gl(gl::DOUBLE),
+            ShaderDataType::Other(gl::DOUBLE)
+        );
+    }
+
+    #[test]
+    fn test_interface_matching() {
+        let outputs = [
+            var("gl_Position", ShaderDataType::Vec4, -1),
+            var("VS_OUT_PS_IN.uv", ShaderDataType::Vec2, -1),
+            var("VS_OUT_PS_IN.color", ShaderDataType::Vec4, -1),
+            var("Normal", ShaderDataType::Vec3, 2),
+        ];
+
+        let inputs = [
+            var("gl_FragCoord", ShaderDataType::Vec4, -1),
+            var("VS_OUT_PS_IN.uv", ShaderDataType::Vec2, -1),
+            var("PsInNormal", ShaderDataType::Vec3, 2),
+        ];
+        assert!(check_interface(&outputs, &inputs).is_ok());
+
+        let inputs = [
+            var("VS_OUT_PS_IN.color", ShaderDataType::Vec3, -1),
+            var("VS_OUT_PS_IN.texid", ShaderDataType::UInt, -1),
+        ];
+        assert_eq!(
+            check_interface(&outputs, &inputs),
+            Err(
+                "'VS_OUT_PS_IN.color' is a Vec3[1] but 'VS_OUT_PS_IN.color' is written as a \
+                 Vec4[1], 'VS_OUT_PS_IN.texid' is not written"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use crate::sampler::CompareFunc;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendFactor {
+    Zero,
+    One,
+    SrcColor,
+    OneMinusSrcColor,
+    SrcAlpha,
+    OneMinusSrcAlpha,
+    DstColor,
+    OneMinusDstColor,
+    DstAlpha,
+    OneMinusDstAlpha,
+}
+
+impl BlendFactor {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            BlendFactor::Zero => gl::ZERO,
+            BlendFactor::One => gl::ONE,
+            BlendFactor::SrcColor => gl::SRC_COLOR,
+            BlendFactor::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
+            BlendFactor::SrcAlpha => gl::SRC_ALPHA,
+            BlendFactor::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
+            BlendFactor::DstColor => gl::DST_COLOR,
+            BlendFactor::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
+            BlendFactor::DstAlpha => gl::DST_ALPHA,
+            BlendFactor::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BlendOp {
+    Add,
+    Subtract,
+    ReverseSubtract,
+    Min,
+    Max,
+}
+
+impl BlendOp {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            BlendOp::Add => gl::FUNC_ADD,
+            BlendOp::Subtract => gl::FUNC_SUBTRACT,
+            BlendOp::ReverseSubtract => gl::FUNC_REVERSE_SUBTRACT,
+            BlendOp::Min => gl::MIN,
+            BlendOp::Max => gl::MAX,
+        }
+    }
+}
+
+/// Blending of the fragment shader output with the framebuffer, the same for all draw
+/// buffers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlendState {
+    enabled: bool,
+    src_color: BlendFactor,
+    dst_color: BlendFactor,
+    color_op: BlendOp,
+    src_alpha: BlendFactor,
+    dst_alpha: BlendFactor,
+    alpha_op: BlendOp,
+}
+
+impl BlendState {
+    /// Same factors and operation for color and alpha.
+    pub fn new(src: BlendFactor, dst: BlendFactor, op: BlendOp) -> BlendState {
+        Self::separate(src, dst, op, src, dst, op)
+    }
+
+    pub fn separate(
+        src_color: BlendFactor,
+        dst_color: BlendFactor,
+        color_op: BlendOp,
+        src_alpha: BlendFactor,
+        dst_alpha: BlendFactor,
+        alpha_op: BlendOp,
+    ) -> BlendState {
+        BlendState {
+            enabled: true,
+            src_color,
+            dst_color,
+            color_op,
+            src_alpha,
+            dst_alpha,
+            alpha_op,
+        }
+    }
+
+    /// The output replaces the framebuffer contents.
+    pub fn disabled() -> BlendState {
+        BlendState {
+            enabled: false,
+            ..Self::new(BlendFactor::One, BlendFactor::Zero, BlendOp::Add)
+        }
+    }
+
+    /// "Over" blending of straight alpha colors.
+    pub fn alpha() -> BlendState {
+        Self::new(
+            BlendFactor::SrcAlpha,
+            BlendFactor::OneMinusSrcAlpha,
+            BlendOp::Add,
+        )
+    }
+
+    /// "Over" blending of premultiplied alpha colors.
+    pub fn premultiplied() -> BlendState {
+        Self::new(
+            BlendFactor::One,
+            BlendFactor::OneMinusSrcAlpha,
+            BlendOp::Add,
+        )
+    }
+
+    pub fn additive() -> BlendState {
+        Self::new(BlendFactor::One, BlendFactor::One, BlendOp::Add)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets the state unconditionally.
+    pub fn apply(&self) {
+        unsafe {
+            if !self.enabled {
+                gl::Disable(gl::BLEND);
+                return;
+            }
+
+            gl::Enable(gl::BLEND);
+            gl::BlendEquationSeparate(self.color_op.to_gl(), self.alpha_op.to_gl());
+            gl::BlendFuncSeparate(
+                self.src_color.to_gl(),
+                self.dst_color.to_gl(),
+                self.src_alpha.to_gl(),
+                self.dst_alpha.to_gl(),
+            );
+        }
+    }
+}
+
+impl std::default::Default for BlendState {
+    fn default() -> Self {
+        BlendState::disabled()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    IncrementClamp,
+    DecrementClamp,
+    Invert,
+    IncrementWrap,
+    DecrementWrap,
+}
+
+impl StencilOp {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Zero => gl::ZERO,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::IncrementClamp => gl::INCR,
+            StencilOp::DecrementClamp => gl::DECR,
+            StencilOp::Invert => gl::INVERT,
+            StencilOp::IncrementWrap => gl::INCR_WRAP,
+            StencilOp::DecrementWrap => gl::DECR_WRAP,
+        }
+    }
+}
+
+/// Stencil test and update, used for both front and back faces.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StencilState {
+    pub func: CompareFunc,
+    pub reference: i32,
+    pub read_mask: u32,
+    pub write_mask: u32,
+    pub stencil_fail: StencilOp,
+    pub depth_fail: StencilOp,
+    pub pass: StencilOp,
+}
+
+impl std::default::Default for StencilState {
+    fn default() -> Self {
+        StencilState {
+            func: CompareFunc::Always,
+            reference: 0,
+            read_mask: !0u32,
+            write_mask: !0u32,
+            stencil_fail: StencilOp::Keep,
+            depth_fail: StencilOp::Keep,
+            pass: StencilOp::Keep,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DepthStencilState {
+    depth_test: bool,
+    depth_write: bool,
+    depth_func: CompareFunc,
+    stencil: Option<StencilState>,
+}
+
+impl DepthStencilState {
+    pub fn new(
+        depth_test: bool,
+        depth_write: bool,
+        depth_func: CompareFunc,
+        stencil: Option<StencilState>,
+    ) -> DepthStencilState {
+        DepthStencilState {
+            depth_test,
+            depth_write,
+            depth_func,
+            stencil,
+        }
+    }
+
+    /// No depth or stencil test, the GL defaults. The depth mask stays on so clearing the
+    /// depth buffer still works.
+    pub fn disabled() -> DepthStencilState {
+        Self::new(false, true, CompareFunc::Less, None)
+    }
+
+    /// Depth test and write with the usual less comparison.
+    pub fn depth_less() -> DepthStencilState {
+        Self::new(true, true, CompareFunc::Less, None)
+    }
+
+    /// Sets the state unconditionally.
+    pub fn apply(&self) {
+        unsafe {
+            if self.depth_test {
+                gl::Enable(gl::DEPTH_TEST);
+                gl::DepthFunc(self.depth_func.to_gl());
+            } else {
+                gl::Disable(gl::DEPTH_TEST);
+            }
+            gl::DepthMask(if self.depth_write {
+                gl::TRUE
+            } else {
+                gl::FALSE
+            });
+
+            match self.stencil {
+                Some(ref s) => {
+                    gl::Enable(gl::STENCIL_TEST);
+                    gl::StencilFunc(s.func.to_gl(), s.reference, s.read_mask);
+                    gl::StencilMask(s.write_mask);
+                    gl::StencilOp(s.stencil_fail.to_gl(), s.depth_fail.to_gl(), s.pass.to_gl());
+                }
+                None => gl::Disable(gl::STENCIL_TEST),
+            }
+        }
+    }
+}
+
+impl std::default::Default for DepthStencilState {
+    fn default() -> Self {
+        DepthStencilState::disabled()
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CullMode {
+    None,
+    Front,
+    Back,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FrontFace {
+    CounterClockwise,
+    Clockwise,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RasterizerState {
+    cull_mode: CullMode,
+    front_face: FrontFace,
+    polygon_mode: PolygonMode,
+    scissor_test: bool,
+}
+
+impl RasterizerState {
+    pub fn new(
+        cull_mode: CullMode,
+        front_face: FrontFace,
+        polygon_mode: PolygonMode,
+        scissor_test: bool,
+    ) -> RasterizerState {
+        RasterizerState {
+            cull_mode,
+            front_face,
+            polygon_mode,
+            scissor_test,
+        }
+    }
+
+    /// Filled polygons, no culling and no scissor test.
+    pub fn solid() -> RasterizerState {
+        Self::new(
+            CullMode::None,
+            FrontFace::CounterClockwise,
+            PolygonMode::Fill,
+            false,
+        )
+    }
+
+    pub fn wireframe() -> RasterizerState {
+        Self::new(
+            CullMode::None,
+            FrontFace::CounterClockwise,
+            PolygonMode::Line,
+            false,
+        )
+    }
+
+    /// Sets the state unconditionally.
+    pub fn apply(&self) {
+        unsafe {
+            match self.cull_mode {
+                CullMode::None => gl::Disable(gl::CULL_FACE),
+                CullMode::Front => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::FRONT);
+                }
+                CullMode::Back => {
+                    gl::Enable(gl::CULL_FACE);
+                    gl::CullFace(gl::BACK);
+                }
+            }
+
+            gl::FrontFace(match self.front_face {
+                FrontFace::CounterClockwise => gl::CCW,
+                FrontFace::Clockwise => gl::CW,
+            });
+
+            gl::PolygonMode(
+                gl::FRONT_AND_BACK,
+                match self.polygon_mode {
+                    PolygonMode::Fill => gl::FILL,
+                    PolygonMode::Line => gl::LINE,
+                    PolygonMode::Point => gl::POINT,
+                },
+            );
+
+            if self.scissor_test {
+                gl::Enable(gl::SCISSOR_TEST);
+            } else {
+                gl::Disable(gl::SCISSOR_TEST);
+            }
+        }
+    }
+}
+
+impl std::default::Default for RasterizerState {
+    fn default() -> Self {
+        RasterizerState::solid()
+    }
+}
+
+/// Remembers the last state objects set through it and skips the GL calls when the same
+/// state is set again. Code that changes the state behind its back has to call
+/// `invalidate`.
+#[derive(Clone, Debug, Default)]
+pub struct RenderStateCache {
+    blend: Option<BlendState>,
+    depth_stencil: Option<DepthStencilState>,
+    rasterizer: Option<RasterizerState>,
+}
+
+impl RenderStateCache {
+    pub fn new() -> RenderStateCache {
+        RenderStateCache::default()
+    }
+
+    /// Stores `state` in `slot`, returns true if it differs from the previous one.
+    fn update<T: PartialEq + Copy>(slot: &mut Option<T>, state: &T) -> bool {
+        if slot.as_ref() == Some(state) {
+            return false;
+        }
+
+        *slot = Some(*state);
+        true
+    }
+
+    pub fn set_blend(&mut self, state: &BlendState) {
+        if Self::update(&mut self.blend, state) {
+            state.apply();
+        }
+    }
+
+    pub fn set_depth_stencil(&mut self, state: &DepthStencilState) {
+        if Self::update(&mut self.depth_stencil, state) {
+            state.apply();
+        }
+    }
+
+    pub fn set_rasterizer(&mut self, state: &RasterizerState) {
+        if Self::update(&mut self.rasterizer, state) {
+            state.apply();
+        }
+    }
+
+    /// Forgets the cached state, the next `set_*` calls always reach GL.
+    pub fn invalidate(&mut self) {
+        self.blend = None;
+        self.depth_stencil = None;
+        self.rasterizer = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_presets() {
+        assert!(!BlendState::default().is_enabled());
+        assert_eq!(BlendState::default(), BlendState::disabled());
+        assert_ne!(BlendState::alpha(), BlendState::premultiplied());
+        assert_eq!(
+            BlendState::additive(),
+            BlendState::separate(
+                BlendFactor::One,
+                BlendFactor::One,
+                BlendOp::Add,
+                BlendFactor::One,
+                BlendFactor::One,
+                BlendOp::Add
+            )
+        );
+        assert_eq!(RasterizerState::default(), RasterizerState::solid());
+        assert_ne!(
+            DepthStencilState::default(),
+            DepthStencilState::depth_less()
+        );
+    }
+
+    #[test]
+    fn test_cache_update() {
+        let mut slot = None;
+        assert!(RenderStateCache::update(&mut slot, &BlendState::alpha()));
+        assert!(!RenderStateCache::update(&mut slot, &BlendState::alpha()));
+        assert!(RenderStateCache::update(
+            &mut slot,
+            &BlendState::premultiplied()
+        ));
+        assert_eq!(slot, Some(BlendState::premultiplied()));
+    }
+}
@@ -0,0 +1,736 @@
+use crate::error::RenderError;
+use crate::gl_debug::{check_gl_errors, label_object, ObjectKind};
+use crate::renderer_gl::{UniqueFramebuffer, UniqueRenderbuffer, UniqueTexture};
+
+/// The minimum number of color attachments every GL 4.5 implementation supports.
+const MAX_COLOR_ATTACHMENTS: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ColorFormat {
+    Rgba8,
+    Srgb8Alpha8,
+    Rgba16F,
+    Rgba32F,
+    R11G11B10F,
+}
+
+impl ColorFormat {
+    pub(crate) fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            ColorFormat::Rgba8 => gl::RGBA8,
+            ColorFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            ColorFormat::Rgba16F => gl::RGBA16F,
+            ColorFormat::Rgba32F => gl::RGBA32F,
+            ColorFormat::R11G11B10F => gl::R11F_G11F_B10F,
+        }
+    }
+
+    /// True for the floating point formats, that can hold values above 1.
+    pub fn is_hdr(self) -> bool {
+        match self {
+            ColorFormat::Rgba8 | ColorFormat::Srgb8Alpha8 => false,
+            ColorFormat::Rgba16F | ColorFormat::Rgba32F | ColorFormat::R11G11B10F => true,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DepthFormat {
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+    Depth32FStencil8,
+}
+
+impl DepthFormat {
+    pub(crate) fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            DepthFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            DepthFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            DepthFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+            DepthFormat::Depth32FStencil8 => gl::DEPTH32F_STENCIL8,
+        }
+    }
+
+    pub fn has_stencil(self) -> bool {
+        match self {
+            DepthFormat::Depth24 | DepthFormat::Depth32F => false,
+            DepthFormat::Depth24Stencil8 | DepthFormat::Depth32FStencil8 => true,
+        }
+    }
+
+    fn attachment_point(self) -> gl::types::GLenum {
+        if self.has_stencil() {
+            gl::DEPTH_STENCIL_ATTACHMENT
+        } else {
+            gl::DEPTH_ATTACHMENT
+        }
+    }
+}
+
+/// Storage of a framebuffer attachment. Multisampled attachments are renderbuffers,
+/// they can only be resolved, not sampled.
+pub enum Attachment {
+    Texture(UniqueTexture),
+    Renderbuffer(UniqueRenderbuffer),
+}
+
+impl Attachment {
+    pub fn handle(&self) -> gl::types::GLuint {
+        match self {
+            Attachment::Texture(t) => **t,
+            Attachment::Renderbuffer(r) => **r,
+        }
+    }
+
+    /// The texture, if the attachment can be sampled.
+    pub fn texture(&self) -> Option<gl::types::GLuint> {
+        match self {
+            Attachment::Texture(t) => Some(**t),
+            Attachment::Renderbuffer(_) => None,
+        }
+    }
+
+    fn texture_2d(
+        format: gl::types::GLenum,
+        width: i32,
+        height: i32,
+    ) -> Result<Attachment, RenderError> {
+        UniqueTexture::new(unsafe {
+            let mut tex = 0u32;
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut tex);
+            gl::TextureStorage2D(tex, 1, format, width, height);
+            //
+            // a single level, so that it is complete even without a sampler object bound
+            gl::TextureParameteri(tex, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(tex, gl::TEXTURE_MAX_LEVEL, 0);
+            tex
+        })
+        .map(Attachment::Texture)
+        .ok_or(RenderError::ResourceCreation("render target texture"))
+    }
+
+    fn renderbuffer(
+        format: gl::types::GLenum,
+        samples: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<Attachment, RenderError> {
+        UniqueRenderbuffer::new(unsafe {
+            let mut rb = 0u32;
+            gl::CreateRenderbuffers(1, &mut rb);
+            gl::NamedRenderbufferStorageMultisample(
+                rb,
+                if samples > 1 { samples } else { 0 },
+                format,
+                width,
+                height,
+            );
+            rb
+        })
+        .map(Attachment::Renderbuffer)
+        .ok_or(RenderError::ResourceCreation("render target renderbuffer"))
+    }
+
+    fn attach(&self, fbo: gl::types::GLuint, point: gl::types::GLenum) {
+        unsafe {
+            match self {
+                Attachment::Texture(t) => gl::NamedFramebufferTexture(fbo, point, **t, 0),
+                Attachment::Renderbuffer(r) => {
+                    gl::NamedFramebufferRenderbuffer(fbo, point, gl::RENDERBUFFER, **r)
+                }
+            }
+        }
+    }
+
+    fn label(&self, label: &str) {
+        match self {
+            Attachment::Texture(t) => label_object(ObjectKind::Texture, **t, label),
+            Attachment::Renderbuffer(r) => label_object(ObjectKind::Renderbuffer, **r, label),
+        }
+    }
+}
+
+fn framebuffer_status_reason(status: gl::types::GLenum) -> &'static str {
+    match status {
+        gl::FRAMEBUFFER_UNDEFINED => "the default framebuffer does not exist",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete or has no storage",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "there are no attachments",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+        gl::FRAMEBUFFER_UNSUPPORTED => "the combination of attachment formats is not supported",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "the attachments have different sample counts",
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "layered and non layered attachments are mixed",
+        _ => "unknown framebuffer status",
+    }
+}
+
+/// Fails with a readable reason if `fbo` cannot be drawn to.
+pub fn check_framebuffer(fbo: gl::types::GLuint, label: &str) -> Result<(), RenderError> {
+    let status = unsafe { gl::CheckNamedFramebufferStatus(fbo, gl::DRAW_FRAMEBUFFER) };
+
+    if status == gl::FRAMEBUFFER_COMPLETE {
+        Ok(())
+    } else {
+        Err(RenderError::IncompleteFramebuffer {
+            label: label.to_string(),
+            reason: format!(
+                "{} (status {:#x})",
+                framebuffer_status_reason(status),
+                status
+            ),
+        })
+    }
+}
+
+/// Something that can be drawn to: the window or an offscreen render target.
+pub trait FramebufferTarget {
+    fn framebuffer(&self) -> gl::types::GLuint;
+    fn size(&self) -> (i32, i32);
+    fn has_stencil(&self) -> bool;
+
+    /// Binds the framebuffer for drawing and sets the viewport to cover all of it.
+    fn bind(&self) {
+        let (width, height) = self.size();
+        unsafe {
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.framebuffer());
+            gl::ViewportIndexedf(0, 0f32, 0f32, width as f32, height as f32);
+        }
+    }
+
+    /// Clears the color attachment `index` (the back buffer for the window).
+    fn clear_color(&self, index: u32, color: [f32; 4]) {
+        unsafe {
+            gl::ClearNamedFramebufferfv(
+                self.framebuffer(),
+                gl::COLOR,
+                index as i32,
+                color.as_ptr(),
+            );
+        }
+    }
+
+    /// Clears the depth attachment, and the stencil one if there is one.
+    fn clear_depth_stencil(&self, depth: f32, stencil: i32) {
+        unsafe {
+            if self.has_stencil() {
+                gl::ClearNamedFramebufferfi(
+                    self.framebuffer(),
+                    gl::DEPTH_STENCIL,
+                    0,
+                    depth,
+                    stencil,
+                );
+            } else {
+                gl::ClearNamedFramebufferfv(self.framebuffer(), gl::DEPTH, 0, &depth);
+            }
+        }
+    }
+}
+
+/// The framebuffer of the window, created with a depth-stencil buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DefaultFramebuffer {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl DefaultFramebuffer {
+    pub fn new(width: i32, height: i32) -> DefaultFramebuffer {
+        DefaultFramebuffer { width, height }
+    }
+}
+
+impl FramebufferTarget for DefaultFramebuffer {
+    fn framebuffer(&self) -> gl::types::GLuint {
+        0
+    }
+
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn has_stencil(&self) -> bool {
+        true
+    }
+}
+
+/// Size, format and sample count of an offscreen render target.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderTargetDescription {
+    pub label: String,
+    pub width: i32,
+    pub height: i32,
+    /// 1 for a single sampled target.
+    pub samples: i32,
+    /// One entry per color attachment, in attachment order.
+    pub color: Vec<ColorFormat>,
+    pub depth: Option<DepthFormat>,
+    /// Store the depth in a texture instead of a renderbuffer, so that it can be sampled.
+    pub depth_texture: bool,
+    /// Give a multisampled target a single sampled copy to resolve into.
+    pub resolve: bool,
+}
+
+impl std::default::Default for RenderTargetDescription {
+    fn default() -> Self {
+        RenderTargetDescription {
+            label: "render target".to_string(),
+            width: 1,
+            height: 1,
+            samples: 1,
+            color: vec![ColorFormat::Rgba8],
+            depth: None,
+            depth_texture: false,
+            resolve: true,
+        }
+    }
+}
+
+impl RenderTargetDescription {
+    /// Catches the mistakes that would otherwise only show up as an incomplete
+    /// framebuffer status.
+    pub fn validate(&self) -> Result<(), RenderError> {
+        let reason = if self.width <= 0 || self.height <= 0 {
+            Some(format!("invalid size {}x{}", self.width, self.height))
+        } else if self.samples < 1 {
+            Some(format!("invalid sample count {}", self.samples))
+        } else if self.color.is_empty() && self.depth.is_none() {
+            Some("there are no attachments".to_string())
+        } else if self.color.len() > MAX_COLOR_ATTACHMENTS {
+            Some(format!(
+                "{} color attachments, at most {} are supported",
+                self.color.len(),
+                MAX_COLOR_ATTACHMENTS
+            ))
+        } else if self.depth_texture && self.depth.is_none() {
+            Some("depth texture requested without a depth format".to_string())
+        } else {
+            None
+        };
+
+        match reason {
+            Some(reason) => Err(RenderError::IncompleteFramebuffer {
+                label: self.label.clone(),
+                reason,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub fn is_multisampled(&self) -> bool {
+        self.samples > 1
+    }
+}
+
+/// An offscreen framebuffer with its attachments. A multisampled target that resolves
+/// owns a single sampled target with the same formats, its textures are the ones
+/// handed out by `color_texture` and `depth_texture`.
+pub struct RenderTarget {
+    desc: RenderTargetDescription,
+    framebuffer: UniqueFramebuffer,
+    color: Vec<Attachment>,
+    depth: Option<Attachment>,
+    resolve_target: Option<Box<RenderTarget>>,
+}
+
+impl RenderTarget {
+    pub fn new(desc: &RenderTargetDescription) -> Result<RenderTarget, RenderError> {
+        desc.validate()?;
+
+        if desc.is_multisampled() {
+            let max_samples = unsafe {
+                let mut max_samples = 0i32;
+                gl::GetIntegerv(gl::MAX_SAMPLES, &mut max_samples);
+                max_samples
+            };
+
+            if desc.samples > max_samples {
+                return Err(RenderError::Unsupported(
+                    "sample count above GL_MAX_SAMPLES",
+                ));
+            }
+        }
+
+        let framebuffer = UniqueFramebuffer::new(unsafe {
+            let mut fbo = 0u32;
+            gl::CreateFramebuffers(1, &mut fbo);
+            fbo
+        })
+        .ok_or(RenderError::ResourceCreation("framebuffer"))?;
+
+        let color = desc
+            .color
+            .iter()
+            .map(|format| {
+                if desc.is_multisampled() {
+                    Attachment::renderbuffer(format.to_gl(), desc.samples, desc.width, desc.height)
+                } else {
+                    Attachment::texture_2d(format.to_gl(), desc.width, desc.height)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let depth = desc
+            .depth
+            .map(|format| {
+                if desc.depth_texture && !desc.is_multisampled() {
+                    Attachment::texture_2d(format.to_gl(), desc.width, desc.height)
+                } else {
+                    Attachment::renderbuffer(format.to_gl(), desc.samples, desc.width, desc.height)
+                }
+            })
+            .transpose()?;
+
+        color.iter().enumerate().for_each(|(idx, attachment)| {
+            attachment.attach(*framebuffer, gl::COLOR_ATTACHMENT0 + idx as u32);
+        });
+        if let (Some(attachment), Some(format)) = (depth.as_ref(), desc.depth) {
+            attachment.attach(*framebuffer, format.attachment_point());
+        }
+
+        let draw_buffers = (0..color.len() as u32)
+            .map(|idx| gl::COLOR_ATTACHMENT0 + idx)
+            .collect::<Vec<_>>();
+        unsafe {
+            if draw_buffers.is_empty() {
+                gl::NamedFramebufferDrawBuffer(*framebuffer, gl::NONE);
+                gl::NamedFramebufferReadBuffer(*framebuffer, gl::NONE);
+            } else {
+                gl::NamedFramebufferDrawBuffers(
+                    *framebuffer,
+                    draw_buffers.len() as i32,
+                    draw_buffers.as_ptr(),
+                );
+            }
+        }
+
+        label_object(ObjectKind::Framebuffer, *framebuffer, &desc.label);
+        color.iter().enumerate().for_each(|(idx, attachment)| {
+            attachment.label(&format!("{} color{}", desc.label, idx));
+        });
+        if let Some(ref attachment) = depth {
+            attachment.label(&format!("{} depth", desc.label));
+        }
+
+        check_gl_errors("RenderTarget::new");
+        check_framebuffer(*framebuffer, &desc.label)?;
+
+        let resolve_target = if desc.is_multisampled() && desc.resolve {
+            Some(Box::new(RenderTarget::new(&RenderTargetDescription {
+                label: format!("{} resolve", desc.label),
+                samples: 1,
+                resolve: false,
+                depth: desc.depth.filter(|_| desc.depth_texture),
+                ..desc.clone()
+            })?))
+        } else {
+            None
+        };
+
+        Ok(RenderTarget {
+            desc: desc.clone(),
+            framebuffer,
+            color,
+            depth,
+            resolve_target,
+        })
+    }
+
+    pub fn description(&self) -> &RenderTargetDescription {
+        &self.desc
+    }
+
+    pub fn width(&self) -> i32 {
+        self.desc.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.desc.height
+    }
+
+    pub fn samples(&self) -> i32 {
+        self.desc.samples
+    }
+
+    pub fn color_attachment(&self, index: usize) -> Option<&Attachment> {
+        self.color.get(index)
+    }
+
+    pub fn depth_attachment(&self) -> Option<&Attachment> {
+        self.depth.as_ref()
+    }
+
+    /// The texture to sample color attachment `index` from, the resolved one for a
+    /// multisampled target. `None` for a multisampled target without resolve.
+    pub fn color_texture(&self, index: usize) -> Option<gl::types::GLuint> {
+        match self.resolve_target {
+            Some(ref resolved) => resolved.color_texture(index),
+            None => self.color.get(index).and_then(|a| a.texture()),
+        }
+    }
+
+    pub fn depth_texture(&self) -> Option<gl::types::GLuint> {
+        match self.resolve_target {
+            Some(ref resolved) => resolved.depth_texture(),
+            None => self.depth.as_ref().and_then(|a| a.texture()),
+        }
+    }
+
+    /// Copies the samples of a multisampled target into its textures, does nothing for
+    /// a single sampled target. Call before sampling `color_texture`/`depth_texture`.
+    pub fn resolve(&self) {
+        let resolved = match self.resolve_target {
+            Some(ref resolved) => resolved,
+            None => return,
+        };
+
+        let (src, dst) = (*self.framebuffer, *resolved.framebuffer);
+        let (w, h) = (self.desc.width, self.desc.height);
+
+        unsafe {
+            (0..self.color.len() as u32).for_each(|idx| {
+                gl::NamedFramebufferReadBuffer(src, gl::COLOR_ATTACHMENT0 + idx);
+                gl::NamedFramebufferDrawBuffer(dst, gl::COLOR_ATTACHMENT0 + idx);
+                gl::BlitNamedFramebuffer(
+                    src,
+                    dst,
+                    0,
+                    0,
+                    w,
+                    h,
+                    0,
+                    0,
+                    w,
+                    h,
+                    gl::COLOR_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            });
+
+            if resolved.depth.is_some() {
+                gl::BlitNamedFramebuffer(
+                    src,
+                    dst,
+                    0,
+                    0,
+                    w,
+                    h,
+                    0,
+                    0,
+                    w,
+                    h,
+                    gl::DEPTH_BUFFER_BIT,
+                    gl::NEAREST,
+                );
+            }
+
+            if !self.color.is_empty() {
+                let draw_buffers = (0..self.color.len() as u32)
+                    .map(|idx| gl::COLOR_ATTACHMENT0 + idx)
+                    .collect::<Vec<_>>();
+                gl::NamedFramebufferReadBuffer(src, gl::COLOR_ATTACHMENT0);
+                gl::NamedFramebufferDrawBuffers(
+                    dst,
+                    draw_buffers.len() as i32,
+                    draw_buffers.as_ptr(),
+                );
+            }
+        }
+
+        check_gl_errors("RenderTarget::resolve");
+    }
+
+    /// Copies color attachment 0 to `dst`, scaling it to fill the destination. Resolves
+    /// a multisampled target on the way, one without resolve textures must have the
+    /// size of `dst` and `filter` must be `Nearest`.
+    pub fn blit_to(&self, dst: &dyn FramebufferTarget, filter: crate::MagFilter) {
+        let (dst_width, dst_height) = dst.size();
+        let filter = match filter {
+            crate::MagFilter::Nearest => gl::NEAREST,
+            crate::MagFilter::Linear => gl::LINEAR,
+        };
+
+        let src = match self.resolve_target {
+            Some(ref resolved) => {
+                self.resolve();
+                resolved.as_ref()
+            }
+            None => self,
+        };
+
+        unsafe {
+            gl::NamedFramebufferReadBuffer(*src.framebuffer, gl::COLOR_ATTACHMENT0);
+            gl::BlitNamedFramebuffer(
+                *src.framebuffer,
+                dst.framebuffer(),
+                0,
+                0,
+                src.desc.width,
+                src.desc.height,
+                0,
+                0,
+                dst_width,
+                dst_height,
+                gl::COLOR_BUFFER_BIT,
+                filter,
+            );
+        }
+
+        check_gl_errors("RenderTarget::blit_to");
+    }
+
+    /// Recreates the attachments if the size changed, their contents are lost.
+    pub fn resize(&mut self, width: i32, height: i32) -> Result<(), RenderError> {
+        if (width, height) == self.size() {
+            return Ok(());
+        }
+
+        *self = RenderTarget::new(&RenderTargetDescription {
+            width,
+            height,
+            ..self.desc.clone()
+        })?;
+        Ok(())
+    }
+}
+
+impl FramebufferTarget for RenderTarget {
+    fn framebuffer(&self) -> gl::types::GLuint {
+        *self.framebuffer
+    }
+
+    fn size(&self) -> (i32, i32) {
+        (self.desc.width, self.desc.height)
+    }
+
+    fn has_stencil(&self) -> bool {
+        self.desc.depth.is_some_and(|d| d.has_stencil())
+    }
+}
+
+pub struct RenderTargetBuilder {
+    desc: RenderTargetDescription,
+}
+
+impl RenderTargetBuilder {
+    /// A single sampled target without attachments.
+    pub fn new(width: i32, height: i32) -> Self {
+        RenderTargetBuilder {
+            desc: RenderTargetDescription {
+                width,
+                height,
+                color: Vec::new(),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn set_label(&mut self, label: &str) -> &mut Self {
+        self.desc.label = label.to_string();
+        self
+    }
+
+    pub fn add_color(&mut self, format: ColorFormat) -> &mut Self {
+        self.desc.color.push(format);
+        self
+    }
+
+    pub fn set_depth(&mut self, format: DepthFormat) -> &mut Self {
+        self.desc.depth = Some(format);
+        self
+    }
+
+    /// The depth attachment is a texture that can be sampled.
+    pub fn set_depth_texture(&mut self, format: DepthFormat) -> &mut Self {
+        self.desc.depth = Some(format);
+        self.desc.depth_texture = true;
+        self
+    }
+
+    /// Multisampled when `samples` > 1, `resolve` adds the single sampled textures the
+    /// samples are resolved into.
+    pub fn set_samples(&mut self, samples: i32, resolve: bool) -> &mut Self {
+        self.desc.samples = samples;
+        self.desc.resolve = resolve;
+        self
+    }
+
+    pub fn description(&self) -> &RenderTargetDescription {
+        &self.desc
+    }
+
+    pub fn build(&self) -> Result<RenderTarget, RenderError> {
+        RenderTarget::new(&self.desc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        let mut builder = RenderTargetBuilder::new(640, 480);
+        builder.set_label("scene");
+        assert!(builder.description().validate().is_err());
+
+        builder
+            .add_color(ColorFormat::Rgba16F)
+            .set_depth(DepthFormat::Depth24Stencil8)
+            .set_samples(4, true);
+        assert!(builder.description().validate().is_ok());
+        assert!(builder.description().is_multisampled());
+
+        let invalid = |desc: RenderTargetDescription| match desc.validate() {
+            Err(RenderError::IncompleteFramebuffer { label, reason }) => {
+                assert_eq!(label, "scene");
+                reason
+            }
+            _ => panic!("{:?} should not validate", desc),
+        };
+
+        let desc = builder.description().clone();
+        assert_eq!(
+            invalid(RenderTargetDescription {
+                width: 0,
+                ..desc.clone()
+            }),
+            "invalid size 0x480"
+        );
+        assert_eq!(
+            invalid(RenderTargetDescription {
+                samples: 0,
+                ..desc.clone()
+            }),
+            "invalid sample count 0"
+        );
+        invalid(RenderTargetDescription {
+            color: vec![ColorFormat::Rgba8; 9],
+            ..desc.clone()
+        });
+        invalid(RenderTargetDescription {
+            depth: None,
+            depth_texture: true,
+            ..desc
+        });
+    }
+
+    #[test]
+    fn test_formats() {
+        assert!(ColorFormat::R11G11B10F.is_hdr());
+        assert!(!ColorFormat::Srgb8Alpha8.is_hdr());
+        assert_eq!(
+            DepthFormat::Depth32FStencil8.attachment_point(),
+            gl::DEPTH_STENCIL_ATTACHMENT
+        );
+        assert_eq!(
+            DepthFormat::Depth24.attachment_point(),
+            gl::DEPTH_ATTACHMENT
+        );
+        assert_eq!(
+            framebuffer_status_reason(gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE),
+            "the attachments have different sample counts"
+        );
+    }
+}
@@ -1,3 +1,6 @@
+use crate::error::RenderError;
+use crate::gl_debug::check_gl_errors;
+use crate::reflection::{check_interface, ProgramReflection};
 use std::result::Result;
 use sys::gen_unique_resource_type;
 
@@ -21,6 +24,16 @@ gen_unique_resource_type!(
     }
 );
 
+gen_unique_resource_type!(
+    UniqueShader,
+    GLShaderDeleter,
+    gl::types::GLuint,
+    0u32,
+    |shader: gl::types::GLuint| unsafe {
+        gl::DeleteShader(shader);
+    }
+);
+
 gen_unique_resource_type!(
     UniqueShaderProgram,
     GLProgramDeleter,
@@ -61,6 +74,36 @@ gen_unique_resource_type!(
     }
 );
 
+gen_unique_resource_type!(
+    UniqueQuery,
+    GLQueryDeleter,
+    gl::types::GLuint,
+    0u32,
+    |q: gl::types::GLuint| unsafe {
+        gl::DeleteQueries(1, &q);
+    }
+);
+
+gen_unique_resource_type!(
+    UniqueFramebuffer,
+    GLFramebufferDeleter,
+    gl::types::GLuint,
+    0u32,
+    |f: gl::types::GLuint| unsafe {
+        gl::DeleteFramebuffers(1, &f);
+    }
+);
+
+gen_unique_resource_type!(
+    UniqueRenderbuffer,
+    GLRenderbufferDeleter,
+    gl::types::GLuint,
+    0u32,
+    |r: gl::types::GLuint| unsafe {
+        gl::DeleteRenderbuffers(1, &r);
+    }
+);
+
 #[derive(Copy, Clone, Debug)]
 pub enum BufferAccess {
     Read,
@@ -91,6 +134,7 @@ impl UniqueBufferMapping {
 
         let mapped_memory =
             unsafe { gl::MapNamedBufferRange(buffer, 0, buffer_size as isize, access) };
+        check_gl_errors("UniqueBufferMapping::new");
         if mapped_memory.is_null() {
             return None;
         }
@@ -131,10 +175,11 @@ impl std::ops::Drop for UniqueBufferMapping {
         unsafe {
             gl::UnmapNamedBuffer(self.buffer);
         }
+        check_gl_errors("UniqueBufferMapping::drop");
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ShaderType {
     Vertex,
     Fragment,
@@ -143,117 +188,299 @@ pub enum ShaderType {
 pub fn create_shader_program_from_string(
     s: &str,
     prog_type: ShaderType,
-) -> Result<UniqueShaderProgram, String> {
-    let src_code = std::ffi::CString::new(s)
-        .map_err(|_| String::from("failed to convert source code to C-string"))?;
+) -> Result<UniqueShaderProgram, RenderError> {
+    create_shader_program(s, prog_type, false)
+}
 
-    let x = [src_code.as_ptr()];
+/// Like `create_shader_program_from_string`, `binary_retrievable` asks the driver to keep
+/// the program binary around for `glGetProgramBinary`.
+pub(crate) fn create_shader_program(
+    s: &str,
+    prog_type: ShaderType,
+    binary_retrievable: bool,
+) -> Result<UniqueShaderProgram, RenderError> {
+    let src_code = std::ffi::CString::new(s).map_err(|_| RenderError::ShaderCompile {
+        stage: prog_type,
+        log: "source code contains a NUL byte".to_string(),
+    })?;
+
+    let stage = prog_type;
     let prog_type = match prog_type {
         ShaderType::Vertex => gl::VERTEX_SHADER,
         ShaderType::Fragment => gl::FRAGMENT_SHADER,
     };
 
-    let prg =
-        UniqueShaderProgram::new(unsafe { gl::CreateShaderProgramv(prog_type, 1, x.as_ptr()) })
-            .ok_or_else(|| "glCreateShaderProgramv() failed".to_string())?;
+    //
+    // same steps as glCreateShaderProgramv(), done separately to tell compile errors
+    // from link errors
+    let shader = UniqueShader::new(unsafe { gl::CreateShader(prog_type) })
+        .ok_or(RenderError::ResourceCreation("shader"))?;
 
-    let linked_successfully = (|| {
-        let mut link_status = 0i32;
-        unsafe {
-            gl::GetProgramiv(*prg, gl::LINK_STATUS, &mut link_status);
+    let compiled_successfully = unsafe {
+        let x = [src_code.as_ptr()];
+        gl::ShaderSource(*shader, 1, x.as_ptr(), std::ptr::null());
+        gl::CompileShader(*shader);
+
+        let mut compile_status = 0i32;
+        gl::GetShaderiv(*shader, gl::COMPILE_STATUS, &mut compile_status);
+        compile_status == gl::TRUE as i32
+    };
+
+    if !compiled_successfully {
+        return Err(RenderError::ShaderCompile {
+            stage,
+            log: info_log(*shader, gl::GetShaderiv, gl::GetShaderInfoLog),
+        });
+    }
+
+    let prg = UniqueShaderProgram::new(unsafe { gl::CreateProgram() })
+        .ok_or(RenderError::ResourceCreation("shader program"))?;
+
+    let linked_successfully = unsafe {
+        gl::ProgramParameteri(*prg, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
+        if binary_retrievable {
+            gl::ProgramParameteri(*prg, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as i32);
         }
+        gl::AttachShader(*prg, *shader);
+        gl::LinkProgram(*prg);
+        gl::DetachShader(*prg, *shader);
+
+        let mut link_status = 0i32;
+        gl::GetProgramiv(*prg, gl::LINK_STATUS, &mut link_status);
         link_status == gl::TRUE as i32
-    })();
+    };
+    check_gl_errors("create_shader_program");
 
     if linked_successfully {
         return Ok(prg);
     }
 
-    let mut info_log_buff: Vec<u8> = vec![0; 1024];
+    Err(RenderError::ShaderLink {
+        stage,
+        log: info_log(*prg, gl::GetProgramiv, gl::GetProgramInfoLog),
+    })
+}
+
+/// The whole info log of a shader or program object.
+fn info_log(
+    object: gl::types::GLuint,
+    get_param: unsafe fn(gl::types::GLuint, gl::types::GLenum, *mut gl::types::GLint),
+    get_log: unsafe fn(
+        gl::types::GLuint,
+        gl::types::GLsizei,
+        *mut gl::types::GLsizei,
+        *mut gl::types::GLchar,
+    ),
+) -> String {
+    let mut log_length = 0i32;
+    unsafe {
+        get_param(object, gl::INFO_LOG_LENGTH, &mut log_length);
+    }
+
+    if log_length <= 0 {
+        return "no log available".to_string();
+    }
+
+    let mut info_log_buff: Vec<u8> = vec![0; log_length as usize];
     let mut info_log_size = 0i32;
     unsafe {
-        gl::GetProgramInfoLog(
-            *prg,
+        get_log(
+            object,
             info_log_buff.len() as gl::types::GLsizei,
             &mut info_log_size,
-            info_log_buff.as_mut_ptr() as *mut i8,
+            info_log_buff.as_mut_ptr() as *mut gl::types::GLchar,
         );
     }
 
-    if info_log_size > 0 {
-        info_log_buff[info_log_size as usize] = 0;
-        return Err(String::from_utf8(info_log_buff)
-            .unwrap_or("Failed to display compiler error".to_string()));
-    }
-
-    Err("Error but no compile log is available".to_string())
+    info_log_buff.truncate(info_log_size.max(0) as usize);
+    String::from_utf8_lossy(&info_log_buff).into_owned()
 }
 
-/// Stores a snapshot of the OpenGL state machine at some point in time.
+/// Stores a snapshot of the OpenGL state machine at some point in time and restores it
+/// when dropped: blend, depth-stencil and rasterizer state, viewport, scissor box, the
+/// bound program pipeline, vertex array and the textures and samplers of the first
+/// `SAVED_TEXTURE_UNITS` units.
 pub struct OpenGLStateSnapshot {
-    last_blend_src: gl::types::GLint,
-    last_blend_dst: gl::types::GLint,
-    last_blend_eq_rgb: gl::types::GLint,
-    last_blend_eq_alpha: gl::types::GLint,
+    blend_src_rgb: gl::types::GLint,
+    blend_dst_rgb: gl::types::GLint,
+    blend_src_alpha: gl::types::GLint,
+    blend_dst_alpha: gl::types::GLint,
+    blend_eq_rgb: gl::types::GLint,
+    blend_eq_alpha: gl::types::GLint,
     blend_enabled: bool,
     cullface_enabled: bool,
+    cull_face_mode: gl::types::GLint,
+    front_face: gl::types::GLint,
+    polygon_mode: [gl::types::GLint; 2],
     depth_enabled: bool,
+    depth_mask: bool,
+    depth_func: gl::types::GLint,
+    stencil_enabled: bool,
+    stencil_func: gl::types::GLint,
+    stencil_ref: gl::types::GLint,
+    stencil_value_mask: gl::types::GLint,
+    stencil_writemask: gl::types::GLint,
+    stencil_ops: [gl::types::GLint; 3],
     scissors_enabled: bool,
+    scissor_box: [gl::types::GLint; 4],
+    viewport: [gl::types::GLint; 4],
+    program: gl::types::GLint,
+    program_pipeline: gl::types::GLint,
+    vertex_array: gl::types::GLint,
+    active_texture: gl::types::GLint,
+    /// 2D and 2D array texture bound to each unit.
+    textures: [(gl::types::GLint, gl::types::GLint); Self::SAVED_TEXTURE_UNITS],
+    samplers: [gl::types::GLint; Self::SAVED_TEXTURE_UNITS],
 }
 
 impl OpenGLStateSnapshot {
+    pub const SAVED_TEXTURE_UNITS: usize = 4;
+
     pub fn new() -> Self {
+        let get_int = |pname: gl::types::GLenum| unsafe {
+            let mut value = 0i32;
+            gl::GetIntegerv(pname, &mut value);
+            value
+        };
+        let get_ints = |pname: gl::types::GLenum, values: &mut [gl::types::GLint]| unsafe {
+            gl::GetIntegerv(pname, values.as_mut_ptr());
+        };
+        let is_enabled = |cap: gl::types::GLenum| unsafe { gl::IsEnabled(cap) != gl::FALSE };
+
+        let mut polygon_mode = [gl::FILL as i32; 2];
+        get_ints(gl::POLYGON_MODE, &mut polygon_mode);
+        let mut scissor_box = [0i32; 4];
+        get_ints(gl::SCISSOR_BOX, &mut scissor_box);
+        let mut viewport = [0i32; 4];
+        get_ints(gl::VIEWPORT, &mut viewport);
+
+        let active_texture = get_int(gl::ACTIVE_TEXTURE);
+        let mut textures = [(0i32, 0i32); Self::SAVED_TEXTURE_UNITS];
+        let mut samplers = [0i32; Self::SAVED_TEXTURE_UNITS];
+        (0..Self::SAVED_TEXTURE_UNITS).for_each(|unit| {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+            }
+            textures[unit] = (
+                get_int(gl::TEXTURE_BINDING_2D),
+                get_int(gl::TEXTURE_BINDING_2D_ARRAY),
+            );
+            samplers[unit] = get_int(gl::SAMPLER_BINDING);
+        });
         unsafe {
-            let mut glstate = std::mem::MaybeUninit::<OpenGLStateSnapshot>::zeroed().assume_init();
-
-            gl::GetIntegerv(gl::BLEND_SRC, &mut glstate.last_blend_src);
-            gl::GetIntegerv(gl::BLEND_DST, &mut glstate.last_blend_dst);
-            gl::GetIntegerv(gl::BLEND_EQUATION_RGB, &mut glstate.last_blend_eq_rgb);
-            gl::GetIntegerv(gl::BLEND_EQUATION_ALPHA, &mut glstate.last_blend_eq_alpha);
+            gl::ActiveTexture(active_texture as u32);
+        }
 
-            glstate.blend_enabled = gl::IsEnabled(gl::BLEND) != gl::FALSE;
-            glstate.cullface_enabled = gl::IsEnabled(gl::CULL_FACE) != gl::FALSE;
-            glstate.depth_enabled = gl::IsEnabled(gl::DEPTH_TEST) != gl::FALSE;
-            glstate.scissors_enabled = gl::IsEnabled(gl::SCISSOR_TEST) != gl::FALSE;
+        let depth_mask = unsafe {
+            let mut mask = gl::TRUE;
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut mask);
+            mask != gl::FALSE
+        };
 
-            glstate
+        OpenGLStateSnapshot {
+            blend_src_rgb: get_int(gl::BLEND_SRC_RGB),
+            blend_dst_rgb: get_int(gl::BLEND_DST_RGB),
+            blend_src_alpha: get_int(gl::BLEND_SRC_ALPHA),
+            blend_dst_alpha: get_int(gl::BLEND_DST_ALPHA),
+            blend_eq_rgb: get_int(gl::BLEND_EQUATION_RGB),
+            blend_eq_alpha: get_int(gl::BLEND_EQUATION_ALPHA),
+            blend_enabled: is_enabled(gl::BLEND),
+            cullface_enabled: is_enabled(gl::CULL_FACE),
+            cull_face_mode: get_int(gl::CULL_FACE_MODE),
+            front_face: get_int(gl::FRONT_FACE),
+            polygon_mode,
+            depth_enabled: is_enabled(gl::DEPTH_TEST),
+            depth_mask,
+            depth_func: get_int(gl::DEPTH_FUNC),
+            stencil_enabled: is_enabled(gl::STENCIL_TEST),
+            stencil_func: get_int(gl::STENCIL_FUNC),
+            stencil_ref: get_int(gl::STENCIL_REF),
+            stencil_value_mask: get_int(gl::STENCIL_VALUE_MASK),
+            stencil_writemask: get_int(gl::STENCIL_WRITEMASK),
+            stencil_ops: [
+                get_int(gl::STENCIL_FAIL),
+                get_int(gl::STENCIL_PASS_DEPTH_FAIL),
+                get_int(gl::STENCIL_PASS_DEPTH_PASS),
+            ],
+            scissors_enabled: is_enabled(gl::SCISSOR_TEST),
+            scissor_box,
+            viewport,
+            program: get_int(gl::CURRENT_PROGRAM),
+            program_pipeline: get_int(gl::PROGRAM_PIPELINE_BINDING),
+            vertex_array: get_int(gl::VERTEX_ARRAY_BINDING),
+            active_texture,
+            textures,
+            samplers,
         }
     }
 }
 
 impl std::ops::Drop for OpenGLStateSnapshot {
     fn drop(&mut self) {
-        unsafe {
-            gl::BlendEquationSeparate(
-                self.last_blend_eq_rgb as u32,
-                self.last_blend_eq_alpha as u32,
-            );
-            gl::BlendFunc(self.last_blend_src as u32, self.last_blend_dst as u32);
-
-            if self.blend_enabled {
-                gl::Enable(gl::BLEND);
+        let set_enabled = |cap: gl::types::GLenum, enabled: bool| unsafe {
+            if enabled {
+                gl::Enable(cap);
             } else {
-                gl::Disable(gl::BLEND);
+                gl::Disable(cap);
             }
+        };
 
-            if self.cullface_enabled {
-                gl::Enable(gl::CULL_FACE);
-            } else {
-                gl::Disable(gl::CULL_FACE);
-            }
+        set_enabled(gl::BLEND, self.blend_enabled);
+        set_enabled(gl::CULL_FACE, self.cullface_enabled);
+        set_enabled(gl::DEPTH_TEST, self.depth_enabled);
+        set_enabled(gl::STENCIL_TEST, self.stencil_enabled);
+        set_enabled(gl::SCISSOR_TEST, self.scissors_enabled);
 
-            if self.depth_enabled {
-                gl::Enable(gl::DEPTH_TEST);
-            } else {
-                gl::Disable(gl::DEPTH_TEST);
-            }
+        unsafe {
+            gl::BlendEquationSeparate(self.blend_eq_rgb as u32, self.blend_eq_alpha as u32);
+            gl::BlendFuncSeparate(
+                self.blend_src_rgb as u32,
+                self.blend_dst_rgb as u32,
+                self.blend_src_alpha as u32,
+                self.blend_dst_alpha as u32,
+            );
 
-            if self.scissors_enabled {
-                gl::Enable(gl::SCISSOR_TEST);
-            } else {
-                gl::Disable(gl::SCISSOR_TEST);
-            }
+            gl::CullFace(self.cull_face_mode as u32);
+            gl::FrontFace(self.front_face as u32);
+            gl::PolygonMode(gl::FRONT_AND_BACK, self.polygon_mode[0] as u32);
+
+            gl::DepthMask(if self.depth_mask { gl::TRUE } else { gl::FALSE });
+            gl::DepthFunc(self.depth_func as u32);
+            gl::StencilFunc(
+                self.stencil_func as u32,
+                self.stencil_ref,
+                self.stencil_value_mask as u32,
+            );
+            gl::StencilMask(self.stencil_writemask as u32);
+            gl::StencilOp(
+                self.stencil_ops[0] as u32,
+                self.stencil_ops[1] as u32,
+                self.stencil_ops[2] as u32,
+            );
+
+            let [x, y, w, h] = self.scissor_box;
+            gl::Scissor(x, y, w, h);
+            let [x, y, w, h] = self.viewport;
+            gl::Viewport(x, y, w, h);
+
+            gl::UseProgram(self.program as u32);
+            gl::BindProgramPipeline(self.program_pipeline as u32);
+            gl::BindVertexArray(self.vertex_array as u32);
+
+            self.textures
+                .iter()
+                .zip(self.samplers.iter())
+                .enumerate()
+                .for_each(|(unit, (&(tex2d, tex2darray), &sampler))| {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit as u32);
+                    gl::BindTexture(gl::TEXTURE_2D, tex2d as u32);
+                    gl::BindTexture(gl::TEXTURE_2D_ARRAY, tex2darray as u32);
+                    gl::BindSampler(unit as u32, sampler as u32);
+                });
+            gl::ActiveTexture(self.active_texture as u32);
         }
+
+        check_gl_errors("OpenGLStateSnapshot::drop");
     }
 }
 
@@ -280,13 +507,21 @@ impl<'a> PipelineBuilder<'a> {
         self
     }
 
-    pub fn build(&self) -> Result<UniquePipeline, String> {
+    pub fn build(&self) -> Result<UniquePipeline, RenderError> {
         let pp = UniquePipeline::new(unsafe {
             let mut pp = 0u32;
             gl::CreateProgramPipelines(1, &mut pp);
             pp
         })
-        .ok_or_else(|| "Failed to create program pipeline object!".to_string())?;
+        .ok_or(RenderError::ResourceCreation("program pipeline"))?;
+
+        if let (Some(vs), Some(fs)) = (self.vertexshader, self.fragmentshader) {
+            check_interface(
+                &ProgramReflection::query_interface(**vs, gl::PROGRAM_OUTPUT),
+                &ProgramReflection::query_interface(**fs, gl::PROGRAM_INPUT),
+            )
+            .map_err(RenderError::InterfaceMismatch)?;
+        }
 
         if let Some(vs) = self.vertexshader {
             unsafe {
@@ -300,83 +535,7 @@ impl<'a> PipelineBuilder<'a> {
             }
         }
 
+        check_gl_errors("PipelineBuilder::build");
         Ok(pp)
     }
 }
-
-pub struct SamplerBuilder {
-    border_color: Option<(f32, f32, f32, f32)>,
-    mag_filter: Option<i32>,
-    min_filter: Option<i32>,
-    wrap_s: Option<i32>,
-    wrap_t: Option<i32>,
-}
-
-impl SamplerBuilder {
-    pub fn new() -> SamplerBuilder {
-        SamplerBuilder {
-            border_color: None,
-            mag_filter: None,
-            min_filter: None,
-            wrap_s: None,
-            wrap_t: None,
-        }
-    }
-
-    pub fn set_border_color(&mut self, r: f32, g: f32, b: f32) -> &mut Self {
-        self.border_color = Some((r, g, b, 1f32));
-        self
-    }
-
-    pub fn set_min_filter(&mut self, minfilter: i32) -> &mut Self {
-        self.min_filter = Some(minfilter);
-        self
-    }
-
-    pub fn set_mag_filter(&mut self, magfilter: i32) -> &mut Self {
-        self.mag_filter = Some(magfilter);
-        self
-    }
-
-    pub fn build(self) -> Result<UniqueSampler, String> {
-        let s = UniqueSampler::new(unsafe {
-            let mut s = 0u32;
-            gl::CreateSamplers(1, &mut s);
-            s
-        })
-        .ok_or_else(|| "Failed to create sampler!".to_string())?;
-
-        if let Some(c) = self.border_color {
-            unsafe {
-                let border_color = [c.0, c.1, c.2, c.3];
-                gl::SamplerParameterfv(*s, gl::TEXTURE_BORDER_COLOR, border_color.as_ptr());
-            }
-        }
-
-        if let Some(minflt) = self.min_filter {
-            unsafe {
-                gl::SamplerParameteri(*s, gl::TEXTURE_MIN_FILTER, minflt);
-            }
-        }
-
-        if let Some(magflt) = self.mag_filter {
-            unsafe {
-                gl::SamplerParameteri(*s, gl::TEXTURE_MAG_FILTER, magflt);
-            }
-        }
-
-        if let Some(wraps) = self.wrap_s {
-            unsafe {
-                gl::SamplerParameteri(*s, gl::TEXTURE_WRAP_S, wraps);
-            }
-        }
-
-        if let Some(wrapt) = self.wrap_t {
-            unsafe {
-                gl::SamplerParameteri(*s, gl::TEXTURE_WRAP_T, wrapt);
-            }
-        }
-
-        Ok(s)
-    }
-}
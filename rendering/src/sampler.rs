@@ -0,0 +1,381 @@
+use crate::error::RenderError;
+use crate::gl_debug::{check_gl_errors, label_object, ObjectKind};
+use crate::renderer_gl::UniqueSampler;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Same values for GL 4.6 and EXT_texture_filter_anisotropic, the bindings are 4.5 only.
+const TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FF;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MagFilter {
+    Nearest,
+    Linear,
+}
+
+impl MagFilter {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            MagFilter::Nearest => gl::NEAREST,
+            MagFilter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+/// Filter for minified textures, the mipmap variants name the filter within a level
+/// first and the filter between levels second.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MinFilter {
+    Nearest,
+    Linear,
+    NearestMipmapNearest,
+    LinearMipmapNearest,
+    NearestMipmapLinear,
+    LinearMipmapLinear,
+}
+
+impl MinFilter {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            MinFilter::Nearest => gl::NEAREST,
+            MinFilter::Linear => gl::LINEAR,
+            MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
+            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
+            MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
+            MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum WrapMode {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+    MirrorClampToEdge,
+}
+
+impl WrapMode {
+    fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            WrapMode::Repeat => gl::REPEAT,
+            WrapMode::MirroredRepeat => gl::MIRRORED_REPEAT,
+            WrapMode::ClampToEdge => gl::CLAMP_TO_EDGE,
+            WrapMode::ClampToBorder => gl::CLAMP_TO_BORDER,
+            WrapMode::MirrorClampToEdge => gl::MIRROR_CLAMP_TO_EDGE,
+        }
+    }
+}
+
+/// Comparison used by depth textures sampled with shadow samplers.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    pub(crate) fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+/// Complete state of a sampler object. The defaults are the GL defaults.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplerDescription {
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+    pub wrap_s: WrapMode,
+    pub wrap_t: WrapMode,
+    pub wrap_r: WrapMode,
+    pub border_color: [f32; 4],
+    pub lod_bias: f32,
+    pub min_lod: f32,
+    pub max_lod: f32,
+    /// 1 disables anisotropic filtering, clamped to what the driver supports.
+    pub max_anisotropy: f32,
+    /// Depth comparison, `None` returns the depth value itself.
+    pub compare: Option<CompareFunc>,
+}
+
+impl std::default::Default for SamplerDescription {
+    fn default() -> Self {
+        SamplerDescription {
+            min_filter: MinFilter::NearestMipmapLinear,
+            mag_filter: MagFilter::Linear,
+            wrap_s: WrapMode::Repeat,
+            wrap_t: WrapMode::Repeat,
+            wrap_r: WrapMode::Repeat,
+            border_color: [0f32; 4],
+            lod_bias: 0f32,
+            min_lod: -1000f32,
+            max_lod: 1000f32,
+            max_anisotropy: 1f32,
+            compare: None,
+        }
+    }
+}
+
+/// Hashable form of a description, floats are compared bitwise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct SamplerKey {
+    min_filter: MinFilter,
+    mag_filter: MagFilter,
+    wrap: [WrapMode; 3],
+    floats: [u32; 8],
+    compare: Option<CompareFunc>,
+}
+
+impl SamplerDescription {
+    fn key(&self) -> SamplerKey {
+        let c = self.border_color;
+        SamplerKey {
+            min_filter: self.min_filter,
+            mag_filter: self.mag_filter,
+            wrap: [self.wrap_s, self.wrap_t, self.wrap_r],
+            floats: [
+                c[0].to_bits(),
+                c[1].to_bits(),
+                c[2].to_bits(),
+                c[3].to_bits(),
+                self.lod_bias.to_bits(),
+                self.min_lod.to_bits(),
+                self.max_lod.to_bits(),
+                self.max_anisotropy.to_bits(),
+            ],
+            compare: self.compare,
+        }
+    }
+
+    /// Creates a sampler object with this state.
+    pub fn create(&self) -> Result<UniqueSampler, RenderError> {
+        let s = UniqueSampler::new(unsafe {
+            let mut s = 0u32;
+            gl::CreateSamplers(1, &mut s);
+            s
+        })
+        .ok_or(RenderError::ResourceCreation("sampler"))?;
+
+        unsafe {
+            gl::SamplerParameteri(*s, gl::TEXTURE_MIN_FILTER, self.min_filter.to_gl() as i32);
+            gl::SamplerParameteri(*s, gl::TEXTURE_MAG_FILTER, self.mag_filter.to_gl() as i32);
+            gl::SamplerParameteri(*s, gl::TEXTURE_WRAP_S, self.wrap_s.to_gl() as i32);
+            gl::SamplerParameteri(*s, gl::TEXTURE_WRAP_T, self.wrap_t.to_gl() as i32);
+            gl::SamplerParameteri(*s, gl::TEXTURE_WRAP_R, self.wrap_r.to_gl() as i32);
+            gl::SamplerParameterfv(*s, gl::TEXTURE_BORDER_COLOR, self.border_color.as_ptr());
+            gl::SamplerParameterf(*s, gl::TEXTURE_LOD_BIAS, self.lod_bias);
+            gl::SamplerParameterf(*s, gl::TEXTURE_MIN_LOD, self.min_lod);
+            gl::SamplerParameterf(*s, gl::TEXTURE_MAX_LOD, self.max_lod);
+
+            match self.compare {
+                Some(func) => {
+                    gl::SamplerParameteri(
+                        *s,
+                        gl::TEXTURE_COMPARE_MODE,
+                        gl::COMPARE_REF_TO_TEXTURE as i32,
+                    );
+                    gl::SamplerParameteri(*s, gl::TEXTURE_COMPARE_FUNC, func.to_gl() as i32);
+                }
+                None => gl::SamplerParameteri(*s, gl::TEXTURE_COMPARE_MODE, gl::NONE as i32),
+            }
+
+            if self.max_anisotropy > 1f32 {
+                let mut max_supported = 1f32;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max_supported);
+                gl::SamplerParameterf(
+                    *s,
+                    TEXTURE_MAX_ANISOTROPY,
+                    self.max_anisotropy.min(max_supported.max(1f32)),
+                );
+            }
+        }
+
+        check_gl_errors("SamplerDescription::create");
+        Ok(s)
+    }
+}
+
+/// Builds a `SamplerDescription`, starting from the GL defaults.
+pub struct SamplerBuilder {
+    desc: SamplerDescription,
+}
+
+impl SamplerBuilder {
+    pub fn new() -> SamplerBuilder {
+        SamplerBuilder {
+            desc: SamplerDescription::default(),
+        }
+    }
+
+    pub fn set_border_color(&mut self, r: f32, g: f32, b: f32) -> &mut Self {
+        self.set_border_color_rgba(r, g, b, 1f32)
+    }
+
+    pub fn set_border_color_rgba(&mut self, r: f32, g: f32, b: f32, a: f32) -> &mut Self {
+        self.desc.border_color = [r, g, b, a];
+        self
+    }
+
+    pub fn set_min_filter(&mut self, minfilter: MinFilter) -> &mut Self {
+        self.desc.min_filter = minfilter;
+        self
+    }
+
+    pub fn set_mag_filter(&mut self, magfilter: MagFilter) -> &mut Self {
+        self.desc.mag_filter = magfilter;
+        self
+    }
+
+    /// Sets both the min and mag filter, without mipmapping.
+    pub fn set_filter(&mut self, filter: MagFilter) -> &mut Self {
+        self.desc.mag_filter = filter;
+        self.desc.min_filter = match filter {
+            MagFilter::Nearest => MinFilter::Nearest,
+            MagFilter::Linear => MinFilter::Linear,
+        };
+        self
+    }
+
+    pub fn set_wrap_s(&mut self, wrap: WrapMode) -> &mut Self {
+        self.desc.wrap_s = wrap;
+        self
+    }
+
+    pub fn set_wrap_t(&mut self, wrap: WrapMode) -> &mut Self {
+        self.desc.wrap_t = wrap;
+        self
+    }
+
+    pub fn set_wrap_r(&mut self, wrap: WrapMode) -> &mut Self {
+        self.desc.wrap_r = wrap;
+        self
+    }
+
+    /// Same wrap mode on all axes.
+    pub fn set_wrap(&mut self, wrap: WrapMode) -> &mut Self {
+        self.desc.wrap_s = wrap;
+        self.desc.wrap_t = wrap;
+        self.desc.wrap_r = wrap;
+        self
+    }
+
+    pub fn set_lod_bias(&mut self, bias: f32) -> &mut Self {
+        self.desc.lod_bias = bias;
+        self
+    }
+
+    pub fn set_lod_range(&mut self, min_lod: f32, max_lod: f32) -> &mut Self {
+        self.desc.min_lod = min_lod;
+        self.desc.max_lod = max_lod;
+        self
+    }
+
+    pub fn set_max_anisotropy(&mut self, max_anisotropy: f32) -> &mut Self {
+        self.desc.max_anisotropy = max_anisotropy.max(1f32);
+        self
+    }
+
+    pub fn set_compare(&mut self, func: Option<CompareFunc>) -> &mut Self {
+        self.desc.compare = func;
+        self
+    }
+
+    pub fn description(&self) -> SamplerDescription {
+        self.desc
+    }
+
+    pub fn build(&self) -> Result<UniqueSampler, RenderError> {
+        self.desc.create()
+    }
+}
+
+/// Hands out one sampler object per distinct description.
+#[derive(Default)]
+pub struct SamplerCache {
+    samplers: HashMap<SamplerKey, Rc<UniqueSampler>>,
+}
+
+impl SamplerCache {
+    pub fn new() -> SamplerCache {
+        SamplerCache::default()
+    }
+
+    /// The sampler with the given state, created on first use.
+    pub fn get(&mut self, desc: &SamplerDescription) -> Result<Rc<UniqueSampler>, RenderError> {
+        let key = desc.key();
+        if let Some(sampler) = self.samplers.get(&key) {
+            return Ok(Rc::clone(sampler));
+        }
+
+        let sampler = Rc::new(desc.create()?);
+        label_object(
+            ObjectKind::Sampler,
+            **sampler,
+            &format!(
+                "sampler {:?}/{:?} {:?}",
+                desc.min_filter, desc.mag_filter, desc.wrap_s
+            ),
+        );
+        self.samplers.insert(key, Rc::clone(&sampler));
+        Ok(sampler)
+    }
+
+    /// Number of distinct samplers created.
+    pub fn len(&self) -> usize {
+        self.samplers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samplers.is_empty()
+    }
+
+    /// Drops the samplers no one else holds.
+    pub fn purge(&mut self) {
+        self.samplers.retain(|_, s| Rc::strong_count(s) > 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_description_key() {
+        let mut sb = SamplerBuilder::new();
+        assert_eq!(sb.description(), SamplerDescription::default());
+
+        sb.set_filter(MagFilter::Nearest)
+            .set_wrap(WrapMode::ClampToEdge)
+            .set_max_anisotropy(0.5f32);
+        let a = sb.description();
+        assert_eq!(a.min_filter, MinFilter::Nearest);
+        assert_eq!(a.wrap_r, WrapMode::ClampToEdge);
+        assert_eq!(a.max_anisotropy, 1f32);
+
+        let mut b = a;
+        assert_eq!(a.key(), b.key());
+        b.lod_bias = 0.5f32;
+        assert_ne!(a.key(), b.key());
+        b.lod_bias = 0f32;
+        assert_eq!(a.key(), b.key());
+        b.compare = Some(CompareFunc::LessEqual);
+        assert_ne!(a.key(), b.key());
+    }
+}
@@ -0,0 +1,525 @@
+use crate::error::RenderError;
+use crate::gl_debug::{label_object, ObjectKind};
+use crate::program_cache::ProgramBinaryCache;
+use crate::renderer_gl::{create_shader_program, ShaderType, UniqueShaderProgram};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// The defines that select one variant of a shader. Two permutations with the same
+/// defines are equal, whatever the order they were added in.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderPermutation {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderPermutation {
+    pub fn new() -> ShaderPermutation {
+        ShaderPermutation::default()
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn defines(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.defines.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+impl std::fmt::Display for ShaderPermutation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let defines = self
+            .defines
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>();
+        write!(f, "[{}]", defines.join(","))
+    }
+}
+
+/// Output of `ShaderPreprocessor::process`, a single source string with `#line`
+/// directives pointing back at the original files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PreprocessedShader {
+    pub source: String,
+    /// File names, indexed by the source string number of the `#line` directives.
+    pub files: Vec<String>,
+}
+
+impl PreprocessedShader {
+    /// Replaces the `source:line` locations of a compiler log with `file:line`. Handles
+    /// the `0:12(5)` (Mesa, AMD) and `0(12)` (NVIDIA) formats.
+    pub fn remap_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.remap_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn remap_line(&self, line: &str) -> String {
+        let bytes = line.as_bytes();
+        let digits_end = |start: usize| {
+            (start..bytes.len())
+                .find(|&i| !bytes[i].is_ascii_digit())
+                .unwrap_or(bytes.len())
+        };
+
+        (0..bytes.len())
+            .filter(|&start| {
+                bytes[start].is_ascii_digit() && (start == 0 || bytes[start - 1] == b' ')
+            })
+            .find_map(|start| {
+                let file_end = digits_end(start);
+                let separator = *bytes.get(file_end)?;
+                if separator != b':' && separator != b'(' {
+                    return None;
+                }
+
+                let line_end = digits_end(file_end + 1);
+                if line_end == file_end + 1
+                    || (separator == b'(' && bytes.get(line_end) != Some(&b')'))
+                {
+                    return None;
+                }
+
+                let file_index: usize = line[start..file_end].parse().ok()?;
+                let file = self.files.get(file_index)?;
+                let location_end = if separator == b'(' {
+                    line_end + 1
+                } else {
+                    line_end
+                };
+                Some(format!(
+                    "{}{}:{}{}",
+                    &line[..start],
+                    file,
+                    &line[file_end + 1..line_end],
+                    &line[location_end..]
+                ))
+            })
+            .unwrap_or_else(|| line.to_string())
+    }
+}
+
+/// Expands `#include "file"` directives and injects `#define`s after the `#version` line.
+/// Includes are looked up in the files added with `add_source`, then in each directory
+/// of the search path. A file is pasted at most once per shader, later includes of it are
+/// skipped, so headers need no include guards.
+#[derive(Clone, Debug, Default)]
+pub struct ShaderPreprocessor {
+    search_path: Vec<PathBuf>,
+    sources: HashMap<String, String>,
+    defines: BTreeMap<String, String>,
+}
+
+/// State of one `process` call.
+struct Expansion {
+    source: String,
+    files: Vec<String>,
+    stack: Vec<String>,
+}
+
+impl ShaderPreprocessor {
+    pub fn new() -> ShaderPreprocessor {
+        ShaderPreprocessor::default()
+    }
+
+    pub fn add_search_path<P: Into<PathBuf>>(&mut self, dir: P) -> &mut Self {
+        self.search_path.push(dir.into());
+        self
+    }
+
+    /// Registers a file held in memory, found before the ones on disk.
+    pub fn add_source(&mut self, name: &str, source: &str) -> &mut Self {
+        self.sources.insert(name.to_string(), source.to_string());
+        self
+    }
+
+    /// A define added to every shader, permutations can override it.
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Contents of `name`, from memory or the first directory of the search path that has it.
+    pub fn load(&self, name: &str) -> Result<String, RenderError> {
+        if let Some(source) = self.sources.get(name) {
+            return Ok(source.clone());
+        }
+
+        for dir in self.search_path.iter() {
+            let path = dir.join(name);
+            match std::fs::read_to_string(&path) {
+                Ok(source) => return Ok(source),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(RenderError::io(path, e)),
+            }
+        }
+
+        Err(RenderError::ShaderPreprocess(format!(
+            "cannot find '{}' in {:?}",
+            name, self.search_path
+        )))
+    }
+
+    /// Expands the file `name`, whose contents are `source`.
+    pub fn process(
+        &self,
+        name: &str,
+        source: &str,
+        permutation: &ShaderPermutation,
+    ) -> Result<PreprocessedShader, RenderError> {
+        let mut defines = self.defines.clone();
+        permutation.defines().for_each(|(k, v)| {
+            defines.insert(k.to_string(), v.to_string());
+        });
+
+        if let Some(bad) = defines.keys().find(|k| !is_identifier(k)) {
+            return Err(RenderError::ShaderPreprocess(format!(
+                "'{}' is not a valid define name",
+                bad
+            )));
+        }
+
+        let define_block = defines
+            .iter()
+            .map(|(name, value)| format!("#define {} {}\n", name, value))
+            .collect::<String>();
+
+        let mut expansion = Expansion {
+            source: String::new(),
+            files: vec![name.to_string()],
+            stack: Vec::new(),
+        };
+
+        let has_version = source
+            .lines()
+            .next()
+            .is_some_and(|l| l.trim_start().starts_with("#version"));
+        if !has_version {
+            expansion.source.push_str(&define_block);
+            expansion.source.push_str("#line 1 0\n");
+        }
+
+        self.expand(name, source, 0, &define_block, &mut expansion)
+            .map_err(RenderError::ShaderPreprocess)?;
+
+        Ok(PreprocessedShader {
+            source: expansion.source,
+            files: expansion.files,
+        })
+    }
+
+    /// Loads and expands the file `name`.
+    pub fn process_file(
+        &self,
+        name: &str,
+        permutation: &ShaderPermutation,
+    ) -> Result<PreprocessedShader, RenderError> {
+        let source = self.load(name)?;
+        self.process(name, &source, permutation)
+    }
+
+    fn expand(
+        &self,
+        name: &str,
+        source: &str,
+        file_index: usize,
+        define_block: &str,
+        expansion: &mut Expansion,
+    ) -> Result<(), String> {
+        expansion.stack.push(name.to_string());
+
+        for (line_idx, line) in source.lines().enumerate() {
+            let line_no = line_idx + 1;
+            let directive = line.trim_start();
+
+            if directive.starts_with("#version") {
+                if file_index != 0 || line_no != 1 {
+                    return Err(format!(
+                        "{}:{}: #version must be the first line of the main file",
+                        name, line_no
+                    ));
+                }
+
+                expansion.source.push_str(line);
+                expansion.source.push('\n');
+                expansion.source.push_str(define_block);
+                expansion
+                    .source
+                    .push_str(&format!("#line {} {}\n", line_no + 1, file_index));
+                continue;
+            }
+
+            if !directive.starts_with("#include") {
+                expansion.source.push_str(line);
+                expansion.source.push('\n');
+                continue;
+            }
+
+            let include = parse_include(&directive["#include".len()..])
+                .ok_or_else(|| format!("{}:{}: malformed #include", name, line_no))?;
+
+            if expansion.stack.iter().any(|f| f == include) {
+                return Err(format!(
+                    "{}:{}: include cycle {} -> {}",
+                    name,
+                    line_no,
+                    expansion.stack.join(" -> "),
+                    include
+                ));
+            }
+
+            if expansion.files.iter().any(|f| f == include) {
+                expansion
+                    .source
+                    .push_str(&format!("#line {} {}\n", line_no + 1, file_index));
+                continue;
+            }
+
+            let included = self
+                .load(include)
+                .map_err(|e| format!("{}:{}: {}", name, line_no, e))?;
+            let included_index = expansion.files.len();
+            expansion.files.push(include.to_string());
+            expansion
+                .source
+                .push_str(&format!("#line 1 {}\n", included_index));
+            self.expand(include, &included, included_index, define_block, expansion)?;
+            expansion
+                .source
+                .push_str(&format!("#line {} {}\n", line_no + 1, file_index));
+        }
+
+        expansion.stack.pop();
+        Ok(())
+    }
+}
+
+/// The file name of `"file"` or `<file>`.
+fn parse_include(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    if rest.len() < 3 {
+        return None;
+    }
+
+    let quoted = rest.starts_with('"') && rest.ends_with('"');
+    let bracketed = rest.starts_with('<') && rest.ends_with('>');
+    if quoted || bracketed {
+        Some(&rest[1..rest.len() - 1])
+    } else {
+        None
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Compiles each (file, stage, permutation) once, the programs are shared. With a binary
+/// cache set, programs are loaded from disk when the driver accepts the stored binary.
+pub struct ShaderCache {
+    preprocessor: ShaderPreprocessor,
+    programs: HashMap<(String, ShaderType, ShaderPermutation), Rc<UniqueShaderProgram>>,
+    binaries: Option<ProgramBinaryCache>,
+}
+
+impl ShaderCache {
+    pub fn new(preprocessor: ShaderPreprocessor) -> ShaderCache {
+        ShaderCache {
+            preprocessor,
+            programs: HashMap::new(),
+            binaries: None,
+        }
+    }
+
+    pub fn preprocessor(&self) -> &ShaderPreprocessor {
+        &self.preprocessor
+    }
+
+    pub fn set_binary_cache(&mut self, binaries: Option<ProgramBinaryCache>) -> &mut Self {
+        self.binaries = binaries;
+        self
+    }
+
+    /// The program for the file `name` with `permutation`, compiled on first use. Compiler
+    /// messages refer to the original files.
+    pub fn get(
+        &mut self,
+        name: &str,
+        stage: ShaderType,
+        permutation: &ShaderPermutation,
+    ) -> Result<Rc<UniqueShaderProgram>, RenderError> {
+        let key = (name.to_string(), stage, permutation.clone());
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(Rc::clone(program));
+        }
+
+        let source = self.preprocessor.load(name)?;
+        self.get_with_source(name, &source, stage, permutation)
+    }
+
+    /// Like `get`, for a main file that is not loaded by the preprocessor, like sources
+    /// embedded in the executable. `name` identifies the source in the cache and the logs.
+    pub fn get_with_source(
+        &mut self,
+        name: &str,
+        source: &str,
+        stage: ShaderType,
+        permutation: &ShaderPermutation,
+    ) -> Result<Rc<UniqueShaderProgram>, RenderError> {
+        let key = (name.to_string(), stage, permutation.clone());
+        if let Some(program) = self.programs.get(&key) {
+            return Ok(Rc::clone(program));
+        }
+
+        let shader = self.preprocessor.process(name, source, permutation)?;
+        let program = Rc::new(self.compile(&shader, stage)?);
+
+        label_object(
+            ObjectKind::Program,
+            **program,
+            &format!("{} {}", name, permutation),
+        );
+        self.programs.insert(key, Rc::clone(&program));
+        Ok(program)
+    }
+
+    fn compile(
+        &self,
+        shader: &PreprocessedShader,
+        stage: ShaderType,
+    ) -> Result<UniqueShaderProgram, RenderError> {
+        if let Some(program) = self
+            .binaries
+            .as_ref()
+            .and_then(|binaries| binaries.load(&shader.source, stage))
+        {
+            return Ok(program);
+        }
+
+        let program = create_shader_program(&shader.source, stage, self.binaries.is_some())
+            .map_err(|e| match e {
+                RenderError::ShaderCompile { stage, log } => RenderError::ShaderCompile {
+                    stage,
+                    log: shader.remap_log(&log),
+                },
+                RenderError::ShaderLink { stage, log } => RenderError::ShaderLink {
+                    stage,
+                    log: shader.remap_log(&log),
+                },
+                e => e,
+            })?;
+
+        if let Some(binaries) = self.binaries.as_ref() {
+            if let Err(e) = binaries.store(*program, &shader.source, stage) {
+                log::warn!(
+                    "Failed to cache the program binary of {}: {}",
+                    shader.files[0],
+                    e
+                );
+            }
+        }
+
+        Ok(program)
+    }
+
+    /// Number of distinct programs compiled.
+    pub fn len(&self) -> usize {
+        self.programs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.programs.is_empty()
+    }
+
+    /// Drops the programs no one else holds.
+    pub fn purge(&mut self) {
+        self.programs.retain(|_, p| Rc::strong_count(p) > 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocessor() -> ShaderPreprocessor {
+        let mut pp = ShaderPreprocessor::new();
+        pp.add_source("common.glsl", "struct S {\n  float x;\n};")
+            .add_source("a.glsl", "#include \"common.glsl\"\nfloat a;")
+            .add_source("cycle.glsl", "#include \"cycle2.glsl\"")
+            .add_source("cycle2.glsl", "#include <cycle.glsl>")
+            .define("MAX_LIGHTS", "4");
+        pp
+    }
+
+    #[test]
+    fn test_includes_and_defines() {
+        let mut permutation = ShaderPermutation::new();
+        permutation.define("BLOOM", "1").define("MAX_LIGHTS", "8");
+
+        let shader = preprocessor()
+            .process(
+                "main.vert",
+                "#version 450 core\n#include \"a.glsl\"\n#include \"common.glsl\"\nvoid main() {}",
+                &permutation,
+            )
+            .unwrap();
+
+        assert_eq!(shader.files, vec!["main.vert", "a.glsl", "common.glsl"]);
+        assert_eq!(
+            shader.source,
+            "#version 450 core\n#define BLOOM 1\n#define MAX_LIGHTS 8\n#line 2 0\n\
+             #line 1 1\n#line 1 2\nstruct S {\n  float x;\n};\n#line 2 1\nfloat a;\n#line 3 0\n\
+             #line 4 0\nvoid main() {}\n"
+        );
+        assert_eq!(permutation.to_string(), "[BLOOM=1,MAX_LIGHTS=8]");
+    }
+
+    #[test]
+    fn test_errors() {
+        let pp = preprocessor();
+        let none = ShaderPermutation::new();
+
+        let err = pp
+            .process("main.frag", "#include \"cycle.glsl\"", &none)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("include cycle main.frag -> cycle.glsl -> cycle2.glsl -> cycle.glsl"));
+
+        assert!(pp
+            .process("main.frag", "#include missing.glsl", &none)
+            .is_err());
+        assert!(pp
+            .process("main.frag", "#include \"missing.glsl\"", &none)
+            .is_err());
+
+        let mut bad = ShaderPermutation::new();
+        bad.define("NOT VALID", "1");
+        assert!(pp.process("main.frag", "void main() {}", &bad).is_err());
+    }
+
+    #[test]
+    fn test_remap_log() {
+        let shader = PreprocessedShader {
+            source: String::new(),
+            files: vec!["particles.vert".to_string(), "frame.glsl".to_string()],
+        };
+
+        assert_eq!(
+            shader.remap_log("1:3(12): error: `Foo' undeclared\n0(40) : error C1008: undefined"),
+            "frame.glsl:3(12): error: `Foo' undeclared\nparticles.vert:40 : error C1008: undefined"
+        );
+        assert_eq!(
+            shader.remap_log("ERROR: 0:7: 'x' : undeclared identifier"),
+            "ERROR: particles.vert:7: 'x' : undeclared identifier"
+        );
+        assert_eq!(shader.remap_log("5:1: unknown file"), "5:1: unknown file");
+    }
+}
@@ -0,0 +1,333 @@
+use crate::error::RenderError;
+use crate::gl_debug::check_gl_errors;
+use crate::renderer_gl::{BufferAccess, UniqueBuffer};
+use math::vertex_types::{VertexPC, VertexPTC};
+use std::marker::PhantomData;
+use std::ops::Range;
+
+/// Types that can be copied to and from GPU memory as raw bytes.
+///
+/// # Safety
+///
+/// The type must be `#[repr(C)]` (or a primitive), contain no pointers or references and
+/// be valid for any bit pattern, the contents of a buffer mapped for reading are
+/// whatever the GPU wrote.
+pub unsafe trait Pod: Copy + 'static {}
+
+unsafe impl Pod for u8 {}
+unsafe impl Pod for u16 {}
+unsafe impl Pod for u32 {}
+unsafe impl Pod for i32 {}
+unsafe impl Pod for f32 {}
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+unsafe impl Pod for VertexPC {}
+unsafe impl Pod for VertexPTC {}
+
+impl BufferAccess {
+    fn storage_flags(self) -> gl::types::GLbitfield {
+        match self {
+            BufferAccess::Read => gl::MAP_READ_BIT,
+            BufferAccess::Write => gl::MAP_WRITE_BIT,
+            BufferAccess::ReadWrite => gl::MAP_READ_BIT | gl::MAP_WRITE_BIT,
+        }
+    }
+
+    pub fn can_read(self) -> bool {
+        match self {
+            BufferAccess::Read | BufferAccess::ReadWrite => true,
+            BufferAccess::Write => false,
+        }
+    }
+
+    pub fn can_write(self) -> bool {
+        match self {
+            BufferAccess::Write | BufferAccess::ReadWrite => true,
+            BufferAccess::Read => false,
+        }
+    }
+}
+
+/// Fails if `range` does not fit in a buffer of `capacity` elements.
+fn check_range(range: &Range<usize>, capacity: usize) -> Result<(), String> {
+    if range.start > range.end || range.end > capacity {
+        Err(format!(
+            "range {}..{} is out of the bounds of a buffer of {} elements",
+            range.start, range.end, capacity
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// A buffer object holding `capacity` elements of type `T`. The access given at creation
+/// decides which of `map_read`/`map_write` can be used, buffers created without one
+/// can only be read by the GPU. GL refuses to map a buffer that is already mapped, so
+/// there is at most one guard per buffer at any time.
+pub struct TypedBuffer<T: Pod> {
+    buffer: UniqueBuffer,
+    capacity: usize,
+    access: Option<BufferAccess>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> TypedBuffer<T> {
+    fn create(
+        capacity: usize,
+        data: *const T,
+        access: Option<BufferAccess>,
+    ) -> Result<TypedBuffer<T>, RenderError> {
+        let flags = access.map_or(0, |a| a.storage_flags());
+        let size = (capacity.max(1) * std::mem::size_of::<T>()) as isize;
+
+        let buffer = UniqueBuffer::new(unsafe {
+            let mut buff = 0u32;
+            gl::CreateBuffers(1, &mut buff);
+            gl::NamedBufferStorage(buff, size, data as *const gl::types::GLvoid, flags);
+            buff
+        })
+        .ok_or(RenderError::ResourceCreation("buffer"))?;
+        check_gl_errors("TypedBuffer::create");
+
+        Ok(TypedBuffer {
+            buffer,
+            capacity,
+            access,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Room for `capacity` elements, with undefined contents.
+    pub fn with_capacity(
+        capacity: usize,
+        access: Option<BufferAccess>,
+    ) -> Result<TypedBuffer<T>, RenderError> {
+        Self::create(capacity, std::ptr::null(), access)
+    }
+
+    /// A buffer holding a copy of `data`.
+    pub fn from_slice(
+        data: &[T],
+        access: Option<BufferAccess>,
+    ) -> Result<TypedBuffer<T>, RenderError> {
+        if data.is_empty() {
+            return Self::with_capacity(0, access);
+        }
+        Self::create(data.len(), data.as_ptr(), access)
+    }
+
+    pub fn handle(&self) -> gl::types::GLuint {
+        *self.buffer
+    }
+
+    /// Number of elements.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.capacity * std::mem::size_of::<T>()
+    }
+
+    pub fn access(&self) -> Option<BufferAccess> {
+        self.access
+    }
+
+    fn map_range(
+        &self,
+        range: &Range<usize>,
+        needs: fn(BufferAccess) -> bool,
+        flags: gl::types::GLbitfield,
+    ) -> Result<*mut T, RenderError> {
+        if !self.access.is_some_and(needs) {
+            return Err(RenderError::BufferMapping(format!(
+                "buffer {} was created with {:?} access",
+                *self.buffer, self.access
+            )));
+        }
+        check_range(range, self.capacity).map_err(RenderError::BufferMapping)?;
+
+        if range.is_empty() {
+            //
+            // GL refuses empty mappings, the guard skips the unmap
+            return Ok(std::ptr::NonNull::dangling().as_ptr());
+        }
+
+        let elem_size = std::mem::size_of::<T>();
+        let memory = unsafe {
+            gl::MapNamedBufferRange(
+                *self.buffer,
+                (range.start * elem_size) as isize,
+                (range.len() * elem_size) as isize,
+                flags,
+            )
+        };
+        check_gl_errors("TypedBuffer::map_range");
+
+        if memory.is_null() {
+            Err(RenderError::BufferMapping(format!(
+                "failed to map buffer {}, it may already be mapped",
+                *self.buffer
+            )))
+        } else {
+            Ok(memory as *mut T)
+        }
+    }
+
+    /// The buffer to unmap when a guard for `range` is dropped, 0 if nothing was mapped.
+    fn mapped_handle(&self, range: &Range<usize>) -> gl::types::GLuint {
+        if range.is_empty() {
+            0
+        } else {
+            *self.buffer
+        }
+    }
+
+    /// Maps `range` for reading. The GPU writes to the buffer must have completed.
+    pub fn map_read_range(
+        &self,
+        range: Range<usize>,
+    ) -> Result<BufferReadGuard<'_, T>, RenderError> {
+        let memory = self.map_range(&range, BufferAccess::can_read, gl::MAP_READ_BIT)?;
+        Ok(BufferReadGuard {
+            buffer: self.mapped_handle(&range),
+            data: unsafe { std::slice::from_raw_parts(memory, range.len()) },
+        })
+    }
+
+    pub fn map_read(&self) -> Result<BufferReadGuard<'_, T>, RenderError> {
+        self.map_read_range(0..self.capacity)
+    }
+
+    /// Maps `range` for writing, its previous contents are discarded.
+    pub fn map_write_range(
+        &self,
+        range: Range<usize>,
+    ) -> Result<BufferWriteGuard<'_, T>, RenderError> {
+        let memory = self.map_range(
+            &range,
+            BufferAccess::can_write,
+            gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_RANGE_BIT,
+        )?;
+        Ok(BufferWriteGuard {
+            buffer: self.mapped_handle(&range),
+            data: unsafe { std::slice::from_raw_parts_mut(memory, range.len()) },
+        })
+    }
+
+    /// Maps the whole buffer for writing, its previous contents are discarded.
+    pub fn map_write(&self) -> Result<BufferWriteGuard<'_, T>, RenderError> {
+        let memory = self.map_range(
+            &(0..self.capacity),
+            BufferAccess::can_write,
+            gl::MAP_WRITE_BIT | gl::MAP_INVALIDATE_BUFFER_BIT,
+        )?;
+        Ok(BufferWriteGuard {
+            buffer: self.mapped_handle(&(0..self.capacity)),
+            data: unsafe { std::slice::from_raw_parts_mut(memory, self.capacity) },
+        })
+    }
+
+    /// Copies `data` to the start of the buffer, fails if it does not fit.
+    pub fn write(&self, data: &[T]) -> Result<(), RenderError> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        self.map_write_range(0..data.len())?.copy_from_slice(data);
+        Ok(())
+    }
+}
+
+/// A buffer range mapped for reading, unmapped when dropped.
+pub struct BufferReadGuard<'a, T: Pod> {
+    buffer: gl::types::GLuint,
+    data: &'a [T],
+}
+
+impl<'a, T: Pod> std::ops::Deref for BufferReadGuard<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<'a, T: Pod> std::ops::Drop for BufferReadGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.buffer == 0 {
+            return;
+        }
+
+        unsafe {
+            gl::UnmapNamedBuffer(self.buffer);
+        }
+        check_gl_errors("BufferReadGuard::drop");
+    }
+}
+
+/// A buffer range mapped for writing, unmapped when dropped.
+pub struct BufferWriteGuard<'a, T: Pod> {
+    buffer: gl::types::GLuint,
+    data: &'a mut [T],
+}
+
+impl<'a, T: Pod> std::ops::Deref for BufferWriteGuard<'a, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.data
+    }
+}
+
+impl<'a, T: Pod> std::ops::DerefMut for BufferWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.data
+    }
+}
+
+impl<'a, T: Pod> std::ops::Drop for BufferWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.buffer == 0 {
+            return;
+        }
+
+        let intact = unsafe { gl::UnmapNamedBuffer(self.buffer) };
+        if intact == gl::FALSE {
+            log::warn!(
+                "Contents of buffer {} were lost while it was mapped",
+                self.buffer
+            );
+        }
+        check_gl_errors("BufferWriteGuard::drop");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_access() {
+        assert!(BufferAccess::ReadWrite.can_read() && BufferAccess::ReadWrite.can_write());
+        assert!(!BufferAccess::Write.can_read());
+        assert!(!BufferAccess::Read.can_write());
+        assert_eq!(
+            BufferAccess::ReadWrite.storage_flags(),
+            gl::MAP_READ_BIT | gl::MAP_WRITE_BIT
+        );
+    }
+
+    #[test]
+    fn test_range_check() {
+        assert!(check_range(&(0..16), 16).is_ok());
+        assert!(check_range(&(4..4), 16).is_ok());
+        assert!(check_range(&(16..16), 16).is_ok());
+        assert_eq!(
+            check_range(&(8..17), 16),
+            Err("range 8..17 is out of the bounds of a buffer of 16 elements".to_string())
+        );
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = 8..4;
+        assert!(check_range(&reversed, 16).is_err());
+    }
+}
@@ -0,0 +1,794 @@
+use crate::font::FontAtlas;
+use math::colors::RGBAColorF32;
+use math::rectangle::RectangleF32;
+use math::vec2::Vec2F32;
+use math::vertex_types::VertexPTC;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use sys::input::*;
+
+/// Number of `MouseButtonId` values, indexes `Ui::captured`.
+const MOUSE_BUTTONS: usize = MouseButtonId::Button5 as usize + 1;
+
+/// Text shown for a widget label: everything after "##" is only used to tell apart
+/// widgets with the same caption.
+fn display_text(label: &str) -> &str {
+    label.split("##").next().unwrap_or("")
+}
+
+/// Triangles produced by a frame of UI. Positions are in screen pixels, with the origin
+/// in the top left corner and Y pointing down.
+#[derive(Clone, Debug)]
+pub struct UiDrawList {
+    pub vertices: Vec<VertexPTC>,
+    pub indices: Vec<u16>,
+    white_uv: Vec2F32,
+}
+
+impl UiDrawList {
+    /// Shapes and text are textured with the atlas of `font`.
+    pub fn new(font: &FontAtlas) -> UiDrawList {
+        UiDrawList {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            white_uv: font.white_uv(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.vertices.clear();
+        self.indices.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Adds a quad, corners given in clockwise order starting with the top left one.
+    /// Quads that would overflow the 16 bit indices are dropped.
+    fn quad(&mut self, pos: [Vec2F32; 4], uv: [Vec2F32; 4], color: RGBAColorF32) {
+        if self.vertices.len() + 4 > u16::MAX as usize + 1 {
+            return;
+        }
+
+        let base = self.vertices.len() as u16;
+        self.vertices
+            .extend(pos.iter().zip(uv.iter()).map(|(pos, uv)| VertexPTC {
+                pos: *pos,
+                texcoords: *uv,
+                color,
+            }));
+        self.indices
+            .extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    pub fn rect_filled(&mut self, r: RectangleF32, color: RGBAColorF32) {
+        let uv = self.white_uv;
+        self.quad(
+            [
+                Vec2F32::new(r.x, r.y),
+                Vec2F32::new(r.x + r.w, r.y),
+                Vec2F32::new(r.x + r.w, r.y + r.h),
+                Vec2F32::new(r.x, r.y + r.h),
+            ],
+            [uv; 4],
+            color,
+        );
+    }
+
+    pub fn rect_outline(&mut self, r: RectangleF32, thickness: f32, color: RGBAColorF32) {
+        let t = thickness;
+        self.rect_filled(RectangleF32::new(r.x, r.y, r.w, t), color);
+        self.rect_filled(RectangleF32::new(r.x, r.y + r.h - t, r.w, t), color);
+        self.rect_filled(RectangleF32::new(r.x, r.y + t, t, r.h - 2f32 * t), color);
+        self.rect_filled(
+            RectangleF32::new(r.x + r.w - t, r.y + t, t, r.h - 2f32 * t),
+            color,
+        );
+    }
+
+    pub fn line(&mut self, a: Vec2F32, b: Vec2F32, thickness: f32, color: RGBAColorF32) {
+        let d = b - a;
+        let len = d.len();
+        if len <= f32::EPSILON {
+            return;
+        }
+
+        let n = Vec2F32::new(-d.y, d.x) * (0.5f32 * thickness / len);
+        let uv = self.white_uv;
+        self.quad([a + n, b + n, b - n, a - n], [uv; 4], color);
+    }
+
+    /// Draws text with its top left corner at `pos`, returns the width. `font` must be
+    /// the font the draw list was created with.
+    pub fn text(
+        &mut self,
+        font: &FontAtlas,
+        pos: Vec2F32,
+        text: &str,
+        scale: f32,
+        color: RGBAColorF32,
+    ) -> f32 {
+        font.layout(text, pos, scale, |pos, uv| self.quad(pos, uv, color));
+        font.measure(text, scale).x
+    }
+}
+
+/// Sizes and colors of the widgets.
+#[derive(Copy, Clone, Debug)]
+pub struct UiStyle {
+    pub text_scale: f32,
+    pub padding: f32,
+    pub spacing: f32,
+    pub text: RGBAColorF32,
+    pub text_dim: RGBAColorF32,
+    pub panel_bg: RGBAColorF32,
+    pub title_bg: RGBAColorF32,
+    pub widget_bg: RGBAColorF32,
+    pub widget_hot: RGBAColorF32,
+    pub widget_active: RGBAColorF32,
+    pub accent: RGBAColorF32,
+}
+
+impl std::default::Default for UiStyle {
+    fn default() -> Self {
+        UiStyle {
+            text_scale: 2f32,
+            padding: 6f32,
+            spacing: 4f32,
+            text: RGBAColorF32::new(0.92f32, 0.92f32, 0.92f32),
+            text_dim: RGBAColorF32::new(0.6f32, 0.6f32, 0.6f32),
+            panel_bg: RGBAColorF32::new_with_alpha(0.08f32, 0.08f32, 0.1f32, 0.85f32),
+            title_bg: RGBAColorF32::new_with_alpha(0.2f32, 0.25f32, 0.4f32, 0.95f32),
+            widget_bg: RGBAColorF32::new_with_alpha(0.2f32, 0.2f32, 0.24f32, 1f32),
+            widget_hot: RGBAColorF32::new_with_alpha(0.28f32, 0.28f32, 0.34f32, 1f32),
+            widget_active: RGBAColorF32::new_with_alpha(0.34f32, 0.34f32, 0.42f32, 1f32),
+            accent: RGBAColorF32::new(0.35f32, 0.55f32, 0.95f32),
+        }
+    }
+}
+
+type WidgetId = u64;
+
+struct PanelState {
+    pos: Vec2F32,
+    collapsed: bool,
+    /// Area covered during the last frame the panel was drawn.
+    rect: RectangleF32,
+    visible: bool,
+}
+
+struct PanelLayout {
+    title: String,
+    /// Top left corner of the panel.
+    origin: Vec2F32,
+    x: f32,
+    cursor_y: f32,
+    width: f32,
+    /// Index of the first vertex of the background quad, resized by `end_panel`.
+    background: usize,
+}
+
+/// Immediate mode UI: widgets are declared every frame, between `begin_frame` and
+/// `end_frame`, and report what the user did to them right away. Widgets live in panels
+/// that can be dragged by their title bar and collapsed.
+///
+/// Input comes from `handle_event`, the output is a draw list in screen pixels.
+pub struct Ui {
+    style: UiStyle,
+    font: FontAtlas,
+    draw_list: UiDrawList,
+    mouse: Vec2F32,
+    mouse_down: bool,
+    mouse_pressed: bool,
+    mouse_released: bool,
+    /// Buttons pressed over the UI, their releases belong to the UI wherever they happen.
+    captured: [bool; MOUSE_BUTTONS],
+    hot: Option<WidgetId>,
+    active: Option<WidgetId>,
+    drag_offset: Vec2F32,
+    panels: HashMap<String, PanelState>,
+    current: Option<PanelLayout>,
+}
+
+impl Ui {
+    /// Uses the builtin 8x8 pixel font.
+    pub fn new(style: UiStyle) -> Ui {
+        Self::with_font(style, FontAtlas::builtin())
+    }
+
+    /// The text scale of the style is relative to the size the font was loaded with.
+    pub fn with_font(style: UiStyle, font: FontAtlas) -> Ui {
+        Ui {
+            style,
+            draw_list: UiDrawList::new(&font),
+            font,
+            mouse: Vec2F32::default(),
+            mouse_down: false,
+            mouse_pressed: false,
+            mouse_released: false,
+            captured: [false; MOUSE_BUTTONS],
+            hot: None,
+            active: None,
+            drag_offset: Vec2F32::default(),
+            panels: HashMap::new(),
+            current: None,
+        }
+    }
+
+    pub fn style(&self) -> &UiStyle {
+        &self.style
+    }
+
+    pub fn font(&self) -> &FontAtlas {
+        &self.font
+    }
+
+    pub fn draw_list(&self) -> &UiDrawList {
+        &self.draw_list
+    }
+
+    /// True while the pointer is over a panel or a widget is being dragged.
+    pub fn wants_mouse(&self) -> bool {
+        self.active.is_some()
+            || self
+                .panels
+                .values()
+                .any(|p| p.visible && p.rect.contains_point(self.mouse.x, self.mouse.y))
+    }
+
+    /// Feeds an input event to the UI. Returns true if the event was used by the UI and
+    /// should not be passed to anything else.
+    pub fn handle_event(&mut self, evt: &Event) -> bool {
+        let ie = match evt {
+            Event::Input(ie) => ie,
+            _ => return false,
+        };
+
+        match ie {
+            InputEventData::MouseMotion(mme) => {
+                self.mouse = Vec2F32::new(mme.pointer_x as f32, mme.pointer_y as f32);
+                self.active.is_some()
+            }
+
+            InputEventData::MouseButton(mbe) => {
+                self.mouse = Vec2F32::new(mbe.pointer_x as f32, mbe.pointer_y as f32);
+                let over_ui = self.wants_mouse();
+                let captured = &mut self.captured[mbe.button as usize];
+                let consumed = match mbe.type_ {
+                    ActionType::Press => {
+                        *captured = over_ui;
+                        over_ui
+                    }
+                    //
+                    // releases of presses that started outside of the UI always go through
+                    ActionType::Release => std::mem::replace(captured, false),
+                };
+
+                if consumed && mbe.button == MouseButtonId::Button1 {
+                    match mbe.type_ {
+                        ActionType::Press => {
+                            self.mouse_down = true;
+                            self.mouse_pressed = true;
+                        }
+                        ActionType::Release => {
+                            self.mouse_down = false;
+                            self.mouse_released = true;
+                        }
+                    }
+                }
+
+                consumed
+            }
+
+            InputEventData::MouseWheel(mwe) => {
+                self.mouse = Vec2F32::new(mwe.pointer_x as f32, mwe.pointer_y as f32);
+                self.wants_mouse()
+            }
+
+            InputEventData::Key(_) => false,
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.draw_list.clear();
+        self.hot = None;
+        self.panels.values_mut().for_each(|p| p.visible = false);
+    }
+
+    pub fn end_frame(&mut self) {
+        debug_assert!(self.current.is_none(), "end_panel() not called");
+
+        if self.mouse_released && !self.mouse_down {
+            self.active = None;
+        }
+
+        self.mouse_pressed = false;
+        self.mouse_released = false;
+    }
+
+    fn line_height(&self) -> f32 {
+        self.font.line_height() * self.style.text_scale
+    }
+
+    fn widget_id(&self, label: &str) -> WidgetId {
+        let mut hasher = DefaultHasher::new();
+        self.current
+            .as_ref()
+            .map(|p| p.title.as_str())
+            .hash(&mut hasher);
+        label.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns (hovered, held, clicked) for a widget covering `rect`.
+    fn interact(&mut self, id: WidgetId, rect: RectangleF32) -> (bool, bool, bool) {
+        let hovered = rect.contains_point(self.mouse.x, self.mouse.y);
+        if hovered {
+            self.hot = Some(id);
+        }
+
+        if hovered && self.mouse_pressed && self.active.is_none() {
+            self.active = Some(id);
+        }
+
+        let held = self.active == Some(id);
+        (hovered, held, held && hovered && self.mouse_released)
+    }
+
+    fn widget_color(&self, hovered: bool, held: bool) -> RGBAColorF32 {
+        if held {
+            self.style.widget_active
+        } else if hovered {
+            self.style.widget_hot
+        } else {
+            self.style.widget_bg
+        }
+    }
+
+    /// Reserves a row of the current panel, returns its rectangle.
+    fn next_row(&mut self, height: f32) -> RectangleF32 {
+        let spacing = self.style.spacing;
+        let layout = self
+            .current
+            .as_mut()
+            .expect("Widgets must be placed inside a panel");
+
+        let r = RectangleF32::new(layout.x, layout.cursor_y, layout.width, height);
+        layout.cursor_y += height + spacing;
+        r
+    }
+
+    /// Starts a panel, the position is only used the first time the panel is shown.
+    /// Returns false if the panel is collapsed, in which case no widgets should be added;
+    /// `end_panel` must be called either way.
+    pub fn begin_panel(&mut self, title: &str, pos: Vec2F32, width: f32) -> bool {
+        debug_assert!(self.current.is_none(), "Panels cannot be nested");
+
+        let line_height = self.line_height();
+        let padding = self.style.padding;
+        let title_height = line_height + padding * 2f32;
+
+        let panel_pos = {
+            let state = self
+                .panels
+                .entry(title.to_string())
+                .or_insert_with(|| PanelState {
+                    pos,
+                    collapsed: false,
+                    rect: RectangleF32::new(pos.x, pos.y, width, title_height),
+                    visible: true,
+                });
+            state.visible = true;
+            state.pos
+        };
+
+        //
+        // background first, its height is known once all widgets are placed
+        let background = self.draw_list.vertices.len();
+        self.draw_list.rect_filled(
+            RectangleF32::new(panel_pos.x, panel_pos.y, width, title_height),
+            self.style.panel_bg,
+        );
+
+        self.current = Some(PanelLayout {
+            title: title.to_string(),
+            origin: panel_pos,
+            x: panel_pos.x + padding,
+            cursor_y: panel_pos.y + title_height + padding,
+            width: width - padding * 2f32,
+            background,
+        });
+
+        //
+        // title bar, dragging it moves the panel and the box on the right collapses it
+        let title_rect = RectangleF32::new(panel_pos.x, panel_pos.y, width, title_height);
+        let toggle_rect = RectangleF32::new(
+            panel_pos.x + width - title_height,
+            panel_pos.y,
+            title_height,
+            title_height,
+        );
+
+        let toggle_id = self.widget_id("##collapse");
+        let (_, _, toggled) = self.interact(toggle_id, toggle_rect);
+        let title_id = self.widget_id("##title");
+        let (_, dragging, _) = self.interact(title_id, title_rect);
+
+        let state = self.panels.get_mut(title).unwrap();
+        if toggled {
+            state.collapsed = !state.collapsed;
+        }
+
+        if dragging {
+            if self.mouse_pressed {
+                self.drag_offset = self.mouse - state.pos;
+            }
+            state.pos = self.mouse - self.drag_offset;
+        }
+
+        let collapsed = state.collapsed;
+        self.draw_list.rect_filled(title_rect, self.style.title_bg);
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(panel_pos.x + padding, panel_pos.y + padding),
+            title,
+            self.style.text_scale,
+            self.style.text,
+        );
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(
+                toggle_rect.x + (toggle_rect.w - line_height) * 0.5f32,
+                panel_pos.y + padding,
+            ),
+            if collapsed { "+" } else { "-" },
+            self.style.text_scale,
+            self.style.text,
+        );
+
+        if collapsed {
+            self.current.as_mut().unwrap().cursor_y = panel_pos.y + title_height;
+        }
+
+        !collapsed
+    }
+
+    pub fn end_panel(&mut self) {
+        let layout = self
+            .current
+            .take()
+            .expect("end_panel() without begin_panel()");
+        let state = self.panels.get_mut(&layout.title).unwrap();
+
+        let bottom = if state.collapsed {
+            layout.cursor_y
+        } else {
+            layout.cursor_y - self.style.spacing + self.style.padding
+        };
+
+        self.draw_list
+            .vertices
+            .iter_mut()
+            .skip(layout.background + 2)
+            .take(2)
+            .for_each(|v| v.pos.y = bottom);
+
+        state.rect = RectangleF32::new(
+            layout.origin.x,
+            layout.origin.y,
+            layout.width + self.style.padding * 2f32,
+            bottom - layout.origin.y,
+        );
+    }
+
+    pub fn label(&mut self, text: &str) {
+        self.label_colored(text, self.style.text);
+    }
+
+    pub fn label_colored(&mut self, text: &str, color: RGBAColorF32) {
+        let r = self.next_row(self.line_height());
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(r.x, r.y),
+            text,
+            self.style.text_scale,
+            color,
+        );
+    }
+
+    pub fn separator(&mut self) {
+        let r = self.next_row(1f32);
+        self.draw_list.rect_filled(r, self.style.text_dim);
+    }
+
+    /// Returns true when the button is clicked. Labels of buttons, checkboxes and sliders
+    /// can have a "##suffix" that is not displayed, to make them unique inside a panel.
+    pub fn button(&mut self, text: &str) -> bool {
+        let height = self.line_height() + self.style.padding;
+        let r = self.next_row(height);
+        let id = self.widget_id(text);
+        let (hovered, held, clicked) = self.interact(id, r);
+
+        let text = display_text(text);
+        self.draw_list
+            .rect_filled(r, self.widget_color(hovered, held));
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(
+                r.x + (r.w - self.font.measure(text, self.style.text_scale).x) * 0.5f32,
+                r.y + self.style.padding * 0.5f32,
+            ),
+            text,
+            self.style.text_scale,
+            self.style.text,
+        );
+
+        clicked
+    }
+
+    /// Returns true when the value changes.
+    pub fn checkbox(&mut self, label: &str, value: &mut bool) -> bool {
+        let size = self.line_height();
+        let r = self.next_row(size);
+        let id = self.widget_id(label);
+        let (hovered, held, clicked) = self.interact(id, r);
+
+        if clicked {
+            *value = !*value;
+        }
+
+        let check_box = RectangleF32::new(r.x, r.y, size, size);
+        self.draw_list
+            .rect_filled(check_box, self.widget_color(hovered, held));
+        if *value {
+            self.draw_list.rect_filled(
+                RectangleF32::shrink(&check_box, size * 0.25f32),
+                self.style.accent,
+            );
+        }
+
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(r.x + size + self.style.spacing * 2f32, r.y),
+            display_text(label),
+            self.style.text_scale,
+            self.style.text,
+        );
+
+        clicked
+    }
+
+    /// Horizontal slider, the value is shown next to the label. Returns true when the value
+    /// changes.
+    pub fn slider_f32(&mut self, label: &str, value: &mut f32, min: f32, max: f32) -> bool {
+        let text = format!("{}: {:.2}", display_text(label), value);
+        let t = self.slider(label, &text, (*value - min) / (max - min));
+
+        match t {
+            Some(t) => {
+                let new_value = min + (max - min) * t;
+                let changed = new_value != *value;
+                *value = new_value;
+                changed
+            }
+            None => false,
+        }
+    }
+
+    /// Same as `slider_f32`, for integer values.
+    pub fn slider_u32(&mut self, label: &str, value: &mut u32, min: u32, max: u32) -> bool {
+        let text = format!("{}: {}", display_text(label), value);
+        let range = max.saturating_sub(min).max(1) as f32;
+        let t = self.slider(label, &text, (value.saturating_sub(min)) as f32 / range);
+
+        match t {
+            Some(t) => {
+                let new_value = min + (t * range).round() as u32;
+                let changed = new_value != *value;
+                *value = new_value;
+                changed
+            }
+            None => false,
+        }
+    }
+
+    /// Draws a slider filled up to `t`, returns the new position while it is dragged.
+    fn slider(&mut self, label: &str, text: &str, t: f32) -> Option<f32> {
+        let height = self.line_height() + self.style.padding;
+        let r = self.next_row(height);
+        let id = self.widget_id(label);
+        let (hovered, held, _) = self.interact(id, r);
+
+        let t = if t.is_finite() {
+            math::utility::saturate(t)
+        } else {
+            0f32
+        };
+        let new_t = if held && r.w > 0f32 {
+            Some(math::utility::saturate((self.mouse.x - r.x) / r.w))
+        } else {
+            None
+        };
+
+        self.draw_list
+            .rect_filled(r, self.widget_color(hovered, held));
+        self.draw_list.rect_filled(
+            RectangleF32::new(r.x, r.y, r.w * new_t.unwrap_or(t), r.h),
+            self.style.accent * 0.6f32,
+        );
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(r.x + self.style.padding, r.y + self.style.padding * 0.5f32),
+            text,
+            self.style.text_scale,
+            self.style.text,
+        );
+
+        new_t
+    }
+
+    /// Line graph of `values` (oldest first), with the vertical axis going from `min` to
+    /// `max`. The label is drawn over the graph.
+    pub fn plot_lines(&mut self, label: &str, values: &[f32], min: f32, max: f32, height: f32) {
+        let r = self.next_row(height);
+        self.draw_list.rect_filled(r, self.style.widget_bg);
+
+        let range = if max > min { max - min } else { 1f32 };
+        let step = r.w / (values.len().max(2) - 1) as f32;
+        let point = |idx: usize, v: f32| {
+            Vec2F32::new(
+                r.x + idx as f32 * step,
+                r.y + r.h - math::utility::saturate((v - min) / range) * r.h,
+            )
+        };
+
+        values.windows(2).enumerate().for_each(|(idx, w)| {
+            self.draw_list.line(
+                point(idx, w[0]),
+                point(idx + 1, w[1]),
+                1.5f32,
+                self.style.accent,
+            );
+        });
+
+        self.draw_list.text(
+            &self.font,
+            Vec2F32::new(r.x + self.style.padding, r.y + self.style.padding * 0.5f32),
+            label,
+            self.style.text_scale,
+            self.style.text,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn motion(x: i32, y: i32) -> Event {
+        Event::Input(InputEventData::MouseMotion(MouseMotionEventData {
+            pointer_x: x,
+            pointer_y: y,
+            ..Default::default()
+        }))
+    }
+
+    fn button(x: i32, y: i32, type_: ActionType) -> Event {
+        Event::Input(InputEventData::MouseButton(MouseButtonEventData {
+            pointer_x: x,
+            pointer_y: y,
+            type_,
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_text_geometry() {
+        let font = FontAtlas::builtin();
+        let mut dl = UiDrawList::new(&font);
+        let width = dl.text(
+            &font,
+            Vec2F32::new(0f32, 0f32),
+            "ab c",
+            2f32,
+            RGBAColorF32::default(),
+        );
+
+        assert_eq!(width, 64f32);
+        //
+        // spaces produce no geometry
+        assert_eq!(dl.vertices.len(), 12);
+        assert_eq!(dl.indices.len(), 18);
+    }
+
+    #[test]
+    fn test_button_click_and_capture() {
+        let mut ui = Ui::new(UiStyle::default());
+        let frame = |ui: &mut Ui| {
+            ui.begin_frame();
+            ui.begin_panel("panel", Vec2F32::new(10f32, 10f32), 200f32);
+            let clicked = ui.button("press me");
+            ui.end_panel();
+            ui.end_frame();
+            clicked
+        };
+
+        assert!(!frame(&mut ui));
+
+        //
+        // title bar is 16 + 2 * 6 pixels high, the button starts below it
+        assert!(!ui.handle_event(&motion(50, 50)));
+        assert!(ui.handle_event(&button(50, 50, ActionType::Press)));
+        assert!(ui.handle_event(&button(50, 50, ActionType::Release)));
+        assert!(frame(&mut ui));
+        assert!(!frame(&mut ui));
+
+        //
+        // outside of the panel events go through
+        assert!(!ui.handle_event(&button(500, 500, ActionType::Press)));
+        assert!(!ui.handle_event(&button(500, 500, ActionType::Release)));
+        assert!(!frame(&mut ui));
+    }
+
+    #[test]
+    fn test_world_drag_released_over_panel() {
+        let mut ui = Ui::new(UiStyle::default());
+        let frame = |ui: &mut Ui| {
+            ui.begin_frame();
+            ui.begin_panel("panel", Vec2F32::new(10f32, 10f32), 200f32);
+            let clicked = ui.button("press me");
+            ui.end_panel();
+            ui.end_frame();
+            clicked
+        };
+        let pan = |x: i32, y: i32, type_: ActionType| {
+            Event::Input(InputEventData::MouseButton(MouseButtonEventData {
+                pointer_x: x,
+                pointer_y: y,
+                type_,
+                button: MouseButtonId::Button3,
+                ..Default::default()
+            }))
+        };
+
+        frame(&mut ui);
+        assert!(!ui.handle_event(&button(500, 500, ActionType::Press)));
+        assert!(!ui.handle_event(&pan(500, 500, ActionType::Press)));
+        assert!(!ui.handle_event(&motion(50, 50)));
+        assert!(!ui.handle_event(&button(50, 50, ActionType::Release)));
+        assert!(!ui.handle_event(&pan(50, 50, ActionType::Release)));
+        assert!(!frame(&mut ui));
+
+        //
+        // presses over the panel are still captured, with the other buttons too
+        assert!(ui.handle_event(&pan(50, 50, ActionType::Press)));
+        assert!(ui.handle_event(&pan(500, 500, ActionType::Release)));
+    }
+
+    #[test]
+    fn test_slider_drag() {
+        let mut ui = Ui::new(UiStyle::default());
+        let mut value = 0.5f32;
+        let mut frame = |ui: &mut Ui| {
+            ui.begin_frame();
+            ui.begin_panel("panel", Vec2F32::new(0f32, 0f32), 212f32);
+            ui.slider_f32("value", &mut value, 0f32, 2f32);
+            ui.end_panel();
+            ui.end_frame();
+            value
+        };
+
+        frame(&mut ui);
+        //
+        // slider spans x = [6, 206], press at the start and drag past the end
+        ui.handle_event(&button(6, 40, ActionType::Press));
+        assert!(frame(&mut ui).abs() <= 0.001f32);
+        assert!(ui.handle_event(&motion(400, 300)));
+        assert!((frame(&mut ui) - 2f32).abs() <= 0.001f32);
+        ui.handle_event(&button(400, 300, ActionType::Release));
+        frame(&mut ui);
+        assert!(!ui.wants_mouse());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::RenderError;
+use crate::font::FontAtlas;
+use crate::gl_debug::{check_gl_errors, label_object, ObjectKind};
+use crate::render_state::{BlendState, DepthStencilState, RasterizerState};
+use crate::renderer_gl::*;
+use crate::sampler::{MagFilter, SamplerBuilder, SamplerCache, WrapMode};
+use crate::shader_preprocessor::{ShaderCache, ShaderPermutation};
+use crate::typed_buffer::TypedBuffer;
+use crate::ui::UiDrawList;
+use crate::vertex_layout::Vertex;
+use math::mat4::Mat4F32;
+use math::projection;
+use math::vertex_types::VertexPTC;
+use std::rc::Rc;
+
+/// Draws the geometry produced by `ui::Ui` (or any other `UiDrawList`), in a single draw
+/// call. The draw lists must be built with the font the renderer was created with.
+pub struct UiRenderer {
+    vertexbuffer: TypedBuffer<VertexPTC>,
+    indexbuffer: TypedBuffer<u16>,
+    vertexarray: UniqueVertexArray,
+    vertshader: Rc<UniqueShaderProgram>,
+    /// Not referenced after creation, but has to live as long as the pipeline.
+    #[allow(dead_code)]
+    fragshader: Rc<UniqueShaderProgram>,
+    pipeline: UniquePipeline,
+    font_atlas: UniqueTexture,
+    sampler: Rc<UniqueSampler>,
+}
+
+impl UiRenderer {
+    /// Every vertex can be addressed with a 16 bit index.
+    pub const MAX_VERTICES: usize = 65536;
+    pub const MAX_INDICES: usize = Self::MAX_VERTICES / 4 * 6;
+
+    fn create_font_atlas(font: &FontAtlas) -> Result<UniqueTexture, RenderError> {
+        UniqueTexture::new(unsafe {
+            let mut tex = 0u32;
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut tex);
+            gl::TextureStorage2D(tex, 1, gl::R8, font.width(), font.height());
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TextureSubImage2D(
+                tex,
+                0,
+                0,
+                0,
+                font.width(),
+                font.height(),
+                gl::RED,
+                gl::UNSIGNED_BYTE,
+                font.pixels().as_ptr() as *const gl::types::GLvoid,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+            tex
+        })
+        .ok_or(RenderError::ResourceCreation("font texture"))
+    }
+
+    pub fn new(
+        font: &FontAtlas,
+        samplers: &mut SamplerCache,
+        shaders: &mut ShaderCache,
+    ) -> Result<UiRenderer, RenderError> {
+        let vertexbuffer =
+            TypedBuffer::with_capacity(Self::MAX_VERTICES, Some(BufferAccess::Write))?;
+        let indexbuffer = TypedBuffer::with_capacity(Self::MAX_INDICES, Some(BufferAccess::Write))?;
+
+        let vertshader = shaders.get_with_source(
+            "ui.vert",
+            include_str!("../../data/shaders/ui.vert"),
+            ShaderType::Vertex,
+            &ShaderPermutation::new(),
+        )?;
+
+        let layout = VertexPTC::layout();
+        layout.validate(**vertshader)?;
+        let vertexarray =
+            layout.create_vertex_array(&[vertexbuffer.handle()], Some(indexbuffer.handle()))?;
+
+        let fragshader = shaders.get_with_source(
+            "ui.frag",
+            include_str!("../../data/shaders/ui.frag"),
+            ShaderType::Fragment,
+            &ShaderPermutation::new(),
+        )?;
+
+        let pipeline = PipelineBuilder::new()
+            .add_vertex_shader(&vertshader)
+            .add_fragment_shader(&fragshader)
+            .build()?;
+
+        let font_atlas = Self::create_font_atlas(font)?;
+        let sampler = samplers.get(
+            &SamplerBuilder::new()
+                .set_filter(MagFilter::Nearest)
+                .set_wrap(WrapMode::ClampToEdge)
+                .description(),
+        )?;
+
+        label_object(ObjectKind::Buffer, vertexbuffer.handle(), "ui vertices");
+        label_object(ObjectKind::Buffer, indexbuffer.handle(), "ui indices");
+        label_object(ObjectKind::VertexArray, *vertexarray, "ui");
+        label_object(ObjectKind::ProgramPipeline, *pipeline, "ui");
+        label_object(ObjectKind::Texture, *font_atlas, "ui font atlas");
+
+        Ok(UiRenderer {
+            vertexbuffer,
+            indexbuffer,
+            vertexarray,
+            vertshader,
+            fragshader,
+            pipeline,
+            font_atlas,
+            sampler,
+        })
+    }
+
+    /// Draws on top of whatever is in the bound framebuffer, the blend, depth, culling
+    /// and scissor state is restored afterwards.
+    pub fn render(&self, draw_list: &UiDrawList, screen_width: i32, screen_height: i32) {
+        if draw_list.is_empty() {
+            return;
+        }
+
+        let vertices = &draw_list.vertices[..draw_list.vertices.len().min(Self::MAX_VERTICES)];
+        let indices = &draw_list.indices[..draw_list.indices.len().min(Self::MAX_INDICES)];
+
+        if let Err(e) = self
+            .vertexbuffer
+            .write(vertices)
+            .and_then(|_| self.indexbuffer.write(indices))
+        {
+            log::error!("Failed to upload the UI geometry: {}", e);
+            return;
+        }
+
+        let projection: Mat4F32 = projection::orthographic(
+            0f32,
+            0f32,
+            screen_width as f32,
+            screen_height as f32,
+            -1f32,
+            1f32,
+        );
+
+        let _saved_state = OpenGLStateSnapshot::new();
+
+        BlendState::alpha().apply();
+        DepthStencilState::disabled().apply();
+        RasterizerState::solid().apply();
+
+        unsafe {
+            gl::ProgramUniformMatrix4fv(**self.vertshader, 0, 1, gl::TRUE, projection.as_ptr());
+
+            gl::BindTextureUnit(0, *self.font_atlas);
+            gl::BindSampler(0, **self.sampler);
+            gl::BindVertexArray(*self.vertexarray);
+            gl::BindProgramPipeline(*self.pipeline);
+
+            gl::DrawElements(
+                gl::TRIANGLES,
+                indices.len() as i32,
+                gl::UNSIGNED_SHORT,
+                std::ptr::null(),
+            );
+
+            gl::BindVertexArray(0);
+            gl::BindProgramPipeline(0);
+        }
+        check_gl_errors("UiRenderer::render");
+    }
+}
@@ -0,0 +1,398 @@
+use crate::error::RenderError;
+use crate::gl_debug::check_gl_errors;
+use crate::reflection::ProgramReflection;
+use crate::renderer_gl::UniqueBuffer;
+use math::colors::RGBAColorF32;
+use math::mat4::Mat4F32;
+use math::vec2::Vec2F32;
+use math::vec4::Vec4F32;
+use std::marker::PhantomData;
+
+/// Values that can be stored in a std140 uniform block.
+pub trait Std140 {
+    /// Base alignment in bytes.
+    const ALIGN: usize;
+
+    /// Appends the std140 representation, the writer has already aligned `out`.
+    fn write_std140(&self, out: &mut Vec<u8>);
+}
+
+fn write_floats(values: &[f32], out: &mut Vec<u8>) {
+    values
+        .iter()
+        .for_each(|v| out.extend_from_slice(&v.to_ne_bytes()));
+}
+
+impl Std140 for f32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+impl Std140 for u32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+impl Std140 for i32 {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_ne_bytes());
+    }
+}
+
+/// GLSL `bool`, stored as a 32 bit integer.
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        (*self as u32).write_std140(out);
+    }
+}
+
+impl Std140 for Vec2F32 {
+    const ALIGN: usize = 8;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(self.as_slice(), out);
+    }
+}
+
+impl Std140 for Vec4F32 {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(self.as_slice(), out);
+    }
+}
+
+/// GLSL `vec4`.
+impl Std140 for RGBAColorF32 {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(self.as_slice(), out);
+    }
+}
+
+/// GLSL `mat4`, the matrix is stored row major and GLSL expects columns.
+impl Std140 for Mat4F32 {
+    const ALIGN: usize = 16;
+
+    fn write_std140(&self, out: &mut Vec<u8>) {
+        write_floats(self.transpose().as_slice(), out);
+    }
+}
+
+/// Builds the contents of a std140 block, members are written in declaration order.
+#[derive(Clone, Debug, Default)]
+pub struct Std140Writer {
+    bytes: Vec<u8>,
+}
+
+impl Std140Writer {
+    /// Alignment of the block itself, array elements and structs.
+    const BLOCK_ALIGN: usize = 16;
+
+    pub fn new() -> Std140Writer {
+        Std140Writer { bytes: Vec::new() }
+    }
+
+    fn align(&mut self, alignment: usize) {
+        let padded = self.bytes.len().div_ceil(alignment) * alignment;
+        self.bytes.resize(padded, 0);
+    }
+
+    pub fn write<T: Std140>(&mut self, value: &T) -> &mut Self {
+        self.align(T::ALIGN);
+        value.write_std140(&mut self.bytes);
+        self
+    }
+
+    /// Writes an array, the elements are aligned and padded to 16 bytes each.
+    pub fn write_array<T: Std140>(&mut self, values: &[T]) -> &mut Self {
+        values.iter().for_each(|v| {
+            self.align(Self::BLOCK_ALIGN);
+            v.write_std140(&mut self.bytes);
+        });
+        self.align(Self::BLOCK_ALIGN);
+        self
+    }
+
+    /// Offset of the next byte written, before alignment.
+    pub fn offset(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// The block contents, padded to the block alignment.
+    pub fn finish(&mut self) -> &[u8] {
+        self.align(Self::BLOCK_ALIGN);
+        &self.bytes
+    }
+}
+
+/// A uniform block, `NAME` is the block name in the shaders.
+pub trait UniformBlock {
+    const NAME: &'static str;
+
+    fn write_std140(&self, out: &mut Std140Writer);
+
+    fn to_std140(&self) -> Vec<u8> {
+        let mut writer = Std140Writer::new();
+        self.write_std140(&mut writer);
+        writer.finish().to_vec()
+    }
+}
+
+/// Index of a uniform buffer binding point.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UniformBinding(u32);
+
+impl UniformBinding {
+    /// Where the `FrameConstants` block is bound, shaders declare it with `binding = 0`.
+    pub const FRAME: UniformBinding = UniformBinding(0);
+    /// Binding points below this one are reserved for the blocks above.
+    const FIRST_FREE: u32 = 1;
+
+    pub fn index(self) -> u32 {
+        self.0
+    }
+}
+
+/// Hands out the binding points that are not reserved, up to the limit of the context.
+#[derive(Clone, Debug)]
+pub struct UniformBindings {
+    next: u32,
+    limit: u32,
+}
+
+impl UniformBindings {
+    pub fn from_context() -> UniformBindings {
+        let mut limit = 0i32;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_UNIFORM_BUFFER_BINDINGS, &mut limit);
+        }
+        Self::with_limit(limit.max(0) as u32)
+    }
+
+    fn with_limit(limit: u32) -> UniformBindings {
+        UniformBindings {
+            next: UniformBinding::FIRST_FREE,
+            limit,
+        }
+    }
+
+    pub fn allocate(&mut self) -> Result<UniformBinding, RenderError> {
+        if self.next >= self.limit {
+            return Err(RenderError::Unsupported(
+                "more uniform buffer binding points than the context provides",
+            ));
+        }
+
+        let binding = UniformBinding(self.next);
+        self.next += 1;
+        Ok(binding)
+    }
+}
+
+/// Checks a block found in a program against the size of the block written on the CPU.
+fn check_block_size(name: &str, expected: usize, found: usize) -> Result<(), String> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(format!(
+            "uniform block {} is {} bytes in the shader and {} bytes on the CPU",
+            name, found, expected
+        ))
+    }
+}
+
+/// A buffer holding one uniform block, updated with `update` and bound to `binding`.
+pub struct UniformBuffer<B: UniformBlock> {
+    buffer: UniqueBuffer,
+    binding: UniformBinding,
+    size: usize,
+    _marker: PhantomData<B>,
+}
+
+impl<B: UniformBlock> UniformBuffer<B> {
+    pub fn new(binding: UniformBinding, initial: &B) -> Result<UniformBuffer<B>, RenderError> {
+        let contents = initial.to_std140();
+        let buffer = UniqueBuffer::new(unsafe {
+            let mut buff = 0u32;
+            gl::CreateBuffers(1, &mut buff);
+            gl::NamedBufferStorage(
+                buff,
+                contents.len() as isize,
+                contents.as_ptr() as *const gl::types::GLvoid,
+                gl::DYNAMIC_STORAGE_BIT,
+            );
+            buff
+        })
+        .ok_or(RenderError::ResourceCreation("uniform buffer"))?;
+        check_gl_errors("UniformBuffer::new");
+
+        Ok(UniformBuffer {
+            buffer,
+            binding,
+            size: contents.len(),
+            _marker: PhantomData,
+        })
+    }
+
+    pub fn handle(&self) -> gl::types::GLuint {
+        *self.buffer
+    }
+
+    pub fn binding(&self) -> UniformBinding {
+        self.binding
+    }
+
+    pub fn size_bytes(&self) -> usize {
+        self.size
+    }
+
+    pub fn update(&self, block: &B) -> Result<(), RenderError> {
+        let contents = block.to_std140();
+        if contents.len() > self.size {
+            return Err(RenderError::UniformBlockMismatch(format!(
+                "{} grew from {} to {} bytes",
+                B::NAME,
+                self.size,
+                contents.len()
+            )));
+        }
+
+        unsafe {
+            gl::NamedBufferSubData(
+                *self.buffer,
+                0,
+                contents.len() as isize,
+                contents.as_ptr() as *const gl::types::GLvoid,
+            );
+        }
+        check_gl_errors("UniformBuffer::update");
+        Ok(())
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindBufferRange(
+                gl::UNIFORM_BUFFER,
+                self.binding.index(),
+                *self.buffer,
+                0,
+                self.size as isize,
+            );
+        }
+    }
+
+    /// Checks that `program` declares the block with the size written on the CPU and points
+    /// it at the binding of this buffer. Programs that do not use the block are accepted.
+    pub fn attach(&self, program: gl::types::GLuint) -> Result<(), RenderError> {
+        let reflection = ProgramReflection::query(program);
+        let block = match reflection.uniform_block(B::NAME) {
+            Some(block) => block,
+            None => return Ok(()),
+        };
+        check_block_size(B::NAME, self.size, block.data_size as usize)
+            .map_err(RenderError::UniformBlockMismatch)?;
+
+        unsafe {
+            gl::UniformBlockBinding(program, block.index, self.binding.index());
+        }
+        check_gl_errors("UniformBuffer::attach");
+        Ok(())
+    }
+}
+
+/// Constants shared by all the draws of a frame.
+///
+/// ```glsl
+/// layout (binding = 0, std140) uniform FrameConstants {
+///   mat4 Projection;
+///   vec2 WorldSize;
+///   float Time;
+/// } Frame;
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct FrameConstants {
+    /// World to clip space.
+    pub projection: Mat4F32,
+    pub world_size: Vec2F32,
+    /// Seconds of simulated time.
+    pub time: f32,
+}
+
+impl UniformBlock for FrameConstants {
+    const NAME: &'static str = "FrameConstants";
+
+    fn write_std140(&self, out: &mut Std140Writer) {
+        out.write(&self.projection)
+            .write(&self.world_size)
+            .write(&self.time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::mat4::consts;
+
+    #[test]
+    fn test_std140_alignment() {
+        let mut writer = Std140Writer::new();
+        writer.write(&1f32);
+        assert_eq!(writer.offset(), 4);
+        writer.write(&Vec2F32::new(2f32, 3f32));
+        assert_eq!(writer.offset(), 16);
+        writer.write(&4u32);
+        writer.write(&RGBAColorF32::new(1f32, 0f32, 0f32));
+        assert_eq!(writer.offset(), 48);
+        writer.write_array(&[1f32, 2f32]);
+        assert_eq!(writer.offset(), 80);
+        writer.write(&true);
+        assert_eq!(writer.finish().len(), 96);
+    }
+
+    #[test]
+    fn test_frame_constants_layout() {
+        let mut projection = consts::identity::<f32>();
+        projection.as_mut_slice()[3] = 5f32;
+
+        let bytes = FrameConstants {
+            projection,
+            world_size: Vec2F32::new(640f32, 480f32),
+            time: 2f32,
+        }
+        .to_std140();
+        assert_eq!(bytes.len(), 80);
+
+        let float_at = |offset: usize| {
+            let mut b = [0u8; 4];
+            b.copy_from_slice(&bytes[offset..offset + 4]);
+            f32::from_ne_bytes(b)
+        };
+        // row 0, column 3 of the matrix ends up in column 3
+        assert_eq!(float_at(12 * 4), 5f32);
+        assert_eq!(float_at(64), 640f32);
+        assert_eq!(float_at(68), 480f32);
+        assert_eq!(float_at(72), 2f32);
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut bindings = UniformBindings::with_limit(3);
+        assert_eq!(bindings.allocate().unwrap(), UniformBinding(1));
+        assert_eq!(bindings.allocate().unwrap(), UniformBinding(2));
+        assert!(bindings.allocate().is_err());
+        assert!(check_block_size("FrameConstants", 80, 80).is_ok());
+        assert!(check_block_size("FrameConstants", 80, 76).is_err());
+    }
+}
@@ -0,0 +1,497 @@
+use crate::error::RenderError;
+use crate::gl_debug::check_gl_errors;
+use crate::reflection::{InterfaceVariable, ProgramReflection};
+use crate::renderer_gl::UniqueVertexArray;
+use math::vertex_types::{VertexPC, VertexPTC};
+
+/// Data type and component count of a vertex attribute, as stored in the buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttribFormat {
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    /// 4 bytes read as floats in [0, 1].
+    UNorm8x4,
+    UInt,
+    UInt2,
+    Int,
+    Int2,
+}
+
+impl AttribFormat {
+    pub fn components(self) -> u32 {
+        match self {
+            AttribFormat::Float | AttribFormat::UInt | AttribFormat::Int => 1,
+            AttribFormat::Float2 | AttribFormat::UInt2 | AttribFormat::Int2 => 2,
+            AttribFormat::Float3 => 3,
+            AttribFormat::Float4 | AttribFormat::UNorm8x4 => 4,
+        }
+    }
+
+    pub fn size_bytes(self) -> u32 {
+        match self {
+            AttribFormat::UNorm8x4 => 4,
+            _ => self.components() * 4,
+        }
+    }
+
+    /// True if the shader sees integers rather than floats.
+    pub fn is_integer(self) -> bool {
+        matches!(
+            self,
+            AttribFormat::UInt | AttribFormat::UInt2 | AttribFormat::Int | AttribFormat::Int2
+        )
+    }
+
+    fn gl_type(self) -> gl::types::GLenum {
+        match self {
+            AttribFormat::Float
+            | AttribFormat::Float2
+            | AttribFormat::Float3
+            | AttribFormat::Float4 => gl::FLOAT,
+            AttribFormat::UNorm8x4 => gl::UNSIGNED_BYTE,
+            AttribFormat::UInt | AttribFormat::UInt2 => gl::UNSIGNED_INT,
+            AttribFormat::Int | AttribFormat::Int2 => gl::INT,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VertexAttribute {
+    /// Matches `layout (location = N)` in the vertex shader.
+    pub location: u32,
+    pub format: AttribFormat,
+    /// Offset from the start of the vertex, in bytes.
+    pub offset: u32,
+    /// Vertex buffer binding the attribute is read from.
+    pub binding: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VertexBufferLayout {
+    pub stride: u32,
+    /// 0 advances per vertex, N per N instances.
+    pub divisor: u32,
+}
+
+/// An input of a vertex shader, as reported by the driver.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActiveAttribute {
+    pub name: String,
+    pub location: i32,
+    /// Number of components of the shader type, 0 for types that are not supported.
+    pub components: u32,
+    pub is_integer: bool,
+}
+
+impl ActiveAttribute {
+    /// The inputs of `program`, built-ins like `gl_VertexID` are left out.
+    pub fn query(program: gl::types::GLuint) -> Vec<ActiveAttribute> {
+        ProgramReflection::query_interface(program, gl::PROGRAM_INPUT)
+            .iter()
+            .filter(|input| input.location >= 0)
+            .map(ActiveAttribute::from)
+            .collect()
+    }
+}
+
+impl From<&InterfaceVariable> for ActiveAttribute {
+    fn from(input: &InterfaceVariable) -> ActiveAttribute {
+        ActiveAttribute {
+            name: input.name.clone(),
+            location: input.location,
+            components: input.data_type.components(),
+            is_integer: input.data_type.is_integer(),
+        }
+    }
+}
+
+/// Where each vertex attribute is read from, built with `VertexLayoutBuilder`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+    buffers: Vec<VertexBufferLayout>,
+}
+
+impl VertexLayout {
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub fn buffers(&self) -> &[VertexBufferLayout] {
+        &self.buffers
+    }
+
+    pub fn stride(&self, binding: u32) -> u32 {
+        self.buffers
+            .get(binding as usize)
+            .map_or(0, |buffer| buffer.stride)
+    }
+
+    /// Checks that every input of the shader is fed by an attribute with the same base
+    /// type and component count. Attributes the shader does not use are fine.
+    pub fn check_attributes(&self, active: &[ActiveAttribute]) -> Result<(), String> {
+        let problems = active
+            .iter()
+            .filter_map(|input| {
+                let attribute = match self
+                    .attributes
+                    .iter()
+                    .find(|a| a.location as i32 == input.location)
+                {
+                    Some(attribute) => attribute,
+                    None => {
+                        return Some(format!(
+                            "'{}' (location {}) has no attribute",
+                            input.name, input.location
+                        ))
+                    }
+                };
+
+                if input.components == 0 {
+                    Some(format!("'{}' has an unsupported type", input.name))
+                } else if attribute.format.is_integer() != input.is_integer
+                    || attribute.format.components() != input.components
+                {
+                    Some(format!(
+                        "'{}' (location {}) is a {}{} but the layout has {:?}",
+                        input.name,
+                        input.location,
+                        if input.is_integer { "ivec" } else { "vec" },
+                        input.components,
+                        attribute.format
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join(", "))
+        }
+    }
+
+    /// Checks the layout against the inputs of the vertex shader `program`.
+    pub fn validate(&self, program: gl::types::GLuint) -> Result<(), RenderError> {
+        self.check_attributes(&ActiveAttribute::query(program))
+            .map_err(RenderError::VertexLayoutMismatch)
+    }
+
+    /// Sets up the attributes of `vao`. `vertex_buffers` has one buffer per binding.
+    pub fn apply(
+        &self,
+        vao: gl::types::GLuint,
+        vertex_buffers: &[gl::types::GLuint],
+        index_buffer: Option<gl::types::GLuint>,
+    ) {
+        unsafe {
+            self.buffers
+                .iter()
+                .zip(vertex_buffers.iter())
+                .enumerate()
+                .for_each(|(binding, (layout, &buffer))| {
+                    gl::VertexArrayVertexBuffer(
+                        vao,
+                        binding as u32,
+                        buffer,
+                        0,
+                        layout.stride as i32,
+                    );
+                    gl::VertexArrayBindingDivisor(vao, binding as u32, layout.divisor);
+                });
+
+            if let Some(index_buffer) = index_buffer {
+                gl::VertexArrayElementBuffer(vao, index_buffer);
+            }
+
+            self.attributes.iter().for_each(|attr| {
+                let components = attr.format.components() as i32;
+                if attr.format.is_integer() {
+                    gl::VertexArrayAttribIFormat(
+                        vao,
+                        attr.location,
+                        components,
+                        attr.format.gl_type(),
+                        attr.offset,
+                    );
+                } else {
+                    gl::VertexArrayAttribFormat(
+                        vao,
+                        attr.location,
+                        components,
+                        attr.format.gl_type(),
+                        if attr.format == AttribFormat::UNorm8x4 {
+                            gl::TRUE
+                        } else {
+                            gl::FALSE
+                        },
+                        attr.offset,
+                    );
+                }
+                gl::VertexArrayAttribBinding(vao, attr.location, attr.binding);
+                gl::EnableVertexArrayAttrib(vao, attr.location);
+            });
+        }
+
+        check_gl_errors("VertexLayout::apply");
+    }
+
+    /// A new vertex array reading the attributes from `vertex_buffers`.
+    pub fn create_vertex_array(
+        &self,
+        vertex_buffers: &[gl::types::GLuint],
+        index_buffer: Option<gl::types::GLuint>,
+    ) -> Result<UniqueVertexArray, RenderError> {
+        let vao = UniqueVertexArray::new(unsafe {
+            let mut vao = 0u32;
+            gl::CreateVertexArrays(1, &mut vao);
+            vao
+        })
+        .ok_or(RenderError::ResourceCreation("vertex array"))?;
+
+        self.apply(*vao, vertex_buffers, index_buffer);
+        Ok(vao)
+    }
+}
+
+/// Lays out the attributes of each buffer binding one after the other, in the order
+/// they are added.
+pub struct VertexLayoutBuilder {
+    attributes: Vec<VertexAttribute>,
+    buffers: Vec<VertexBufferLayout>,
+    /// Stride given with `set_stride`, per binding.
+    explicit_strides: Vec<Option<u32>>,
+}
+
+impl VertexLayoutBuilder {
+    /// Starts with binding 0, advancing per vertex.
+    pub fn new() -> Self {
+        VertexLayoutBuilder {
+            attributes: Vec::new(),
+            buffers: vec![VertexBufferLayout {
+                stride: 0,
+                divisor: 0,
+            }],
+            explicit_strides: vec![None],
+        }
+    }
+
+    fn current_binding(&self) -> u32 {
+        self.buffers.len() as u32 - 1
+    }
+
+    pub fn add_attribute(&mut self, location: u32, format: AttribFormat) -> &mut Self {
+        let binding = self.current_binding();
+        let buffer = self.buffers.last_mut().unwrap();
+
+        self.attributes.push(VertexAttribute {
+            location,
+            format,
+            offset: buffer.stride,
+            binding,
+        });
+        buffer.stride += format.size_bytes();
+        self
+    }
+
+    /// Leaves `bytes` of padding before the next attribute.
+    pub fn skip(&mut self, bytes: u32) -> &mut Self {
+        self.buffers.last_mut().unwrap().stride += bytes;
+        self
+    }
+
+    /// Overrides the stride of the current binding, for vertices with trailing data.
+    pub fn set_stride(&mut self, stride: u32) -> &mut Self {
+        *self.explicit_strides.last_mut().unwrap() = Some(stride);
+        self
+    }
+
+    /// The next attributes are read from a new buffer binding.
+    pub fn next_binding(&mut self, divisor: u32) -> &mut Self {
+        self.buffers.push(VertexBufferLayout { stride: 0, divisor });
+        self.explicit_strides.push(None);
+        self
+    }
+
+    pub fn build(&self) -> Result<VertexLayout, RenderError> {
+        let duplicate = self.attributes.iter().enumerate().find_map(|(idx, a)| {
+            self.attributes[..idx]
+                .iter()
+                .find(|b| b.location == a.location)
+                .map(|_| a.location)
+        });
+        if let Some(location) = duplicate {
+            return Err(RenderError::VertexLayoutMismatch(format!(
+                "location {} is used by more than one attribute",
+                location
+            )));
+        }
+
+        let buffers = self
+            .buffers
+            .iter()
+            .zip(self.explicit_strides.iter())
+            .enumerate()
+            .map(|(binding, (buffer, explicit))| match *explicit {
+                Some(stride) if stride < buffer.stride => {
+                    Err(RenderError::VertexLayoutMismatch(format!(
+                        "stride {} of binding {} is less than the {} bytes of its attributes",
+                        stride, binding, buffer.stride
+                    )))
+                }
+                Some(stride) => Ok(VertexBufferLayout { stride, ..*buffer }),
+                None => Ok(*buffer),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(VertexLayout {
+            attributes: self.attributes.clone(),
+            buffers,
+        })
+    }
+}
+
+impl std::default::Default for VertexLayoutBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Vertex types with a known layout.
+pub trait Vertex {
+    fn layout() -> VertexLayout;
+}
+
+impl Vertex for VertexPC {
+    /// Position at location 0, color at location 1.
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new()
+            .add_attribute(0, AttribFormat::Float2)
+            .add_attribute(1, AttribFormat::Float4)
+            .build()
+            .expect("VertexPC layout")
+    }
+}
+
+impl Vertex for VertexPTC {
+    /// Position at location 0, texture coordinates at location 1, color at location 2.
+    fn layout() -> VertexLayout {
+        VertexLayoutBuilder::new()
+            .add_attribute(0, AttribFormat::Float2)
+            .add_attribute(1, AttribFormat::Float2)
+            .add_attribute(2, AttribFormat::Float4)
+            .build()
+            .expect("VertexPTC layout")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::colors::RGBAColorF32;
+
+    #[test]
+    fn test_vertex_types() {
+        let pc = VertexPC::layout();
+        assert_eq!(pc.stride(0) as usize, std::mem::size_of::<VertexPC>());
+        assert_eq!(
+            pc.attributes()[1].offset as usize,
+            std::mem::offset_of!(VertexPC, color)
+        );
+
+        let ptc = VertexPTC::layout();
+        assert_eq!(ptc.stride(0) as usize, std::mem::size_of::<VertexPTC>());
+        assert_eq!(
+            ptc.attributes()[1].offset as usize,
+            std::mem::offset_of!(VertexPTC, texcoords)
+        );
+        assert_eq!(
+            ptc.attributes()[2].offset as usize,
+            std::mem::offset_of!(VertexPTC, color)
+        );
+
+        //
+        // the shaders read the color components in r, g, b, a order
+        let component = std::mem::size_of::<f32>();
+        assert_eq!(std::mem::offset_of!(RGBAColorF32, r), 0);
+        assert_eq!(std::mem::offset_of!(RGBAColorF32, g), component);
+        assert_eq!(std::mem::offset_of!(RGBAColorF32, b), 2 * component);
+        assert_eq!(std::mem::offset_of!(RGBAColorF32, a), 3 * component);
+        assert_eq!(std::mem::size_of::<RGBAColorF32>(), 4 * component);
+    }
+
+    #[test]
+    fn test_builder() {
+        let layout = VertexLayoutBuilder::new()
+            .add_attribute(0, AttribFormat::Float3)
+            .skip(4)
+            .add_attribute(1, AttribFormat::UNorm8x4)
+            .next_binding(1)
+            .add_attribute(2, AttribFormat::UInt)
+            .set_stride(16)
+            .build()
+            .unwrap();
+
+        assert_eq!(layout.stride(0), 20);
+        assert_eq!(layout.attributes()[1].offset, 16);
+        assert_eq!(
+            layout.buffers()[1],
+            VertexBufferLayout {
+                stride: 16,
+                divisor: 1
+            }
+        );
+        assert_eq!(layout.attributes()[2].binding, 1);
+
+        assert!(VertexLayoutBuilder::new()
+            .add_attribute(0, AttribFormat::Float)
+            .add_attribute(0, AttribFormat::Float2)
+            .build()
+            .is_err());
+        assert!(VertexLayoutBuilder::new()
+            .add_attribute(0, AttribFormat::Float4)
+            .set_stride(8)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn test_check_attributes() {
+        let input =
+            |name: &str, location: i32, components: u32, is_integer: bool| ActiveAttribute {
+                name: name.to_string(),
+                location,
+                components,
+                is_integer,
+            };
+
+        let layout = VertexPC::layout();
+        assert!(layout
+            .check_attributes(&[
+                input("VsInPos", 0, 2, false),
+                input("VsInColor", 1, 4, false)
+            ])
+            .is_ok());
+        //
+        // unused attributes are fine
+        assert!(layout
+            .check_attributes(&[input("VsInPos", 0, 2, false)])
+            .is_ok());
+
+        let err = layout
+            .check_attributes(&[input("VsInPos", 0, 3, false), input("VsInUV", 2, 2, false)])
+            .unwrap_err();
+        assert_eq!(
+            err,
+            "'VsInPos' (location 0) is a vec3 but the layout has Float2, \
+             'VsInUV' (location 2) has no attribute"
+        );
+        assert!(layout
+            .check_attributes(&[input("VsInColor", 1, 4, true)])
+            .is_err());
+    }
+}
@@ -0,0 +1,25 @@
+/// Errors from creating a window and its OpenGL context.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WindowError {
+    /// No connection to the display server or no usable screen/monitor.
+    Display(String),
+    /// No pixel format (framebuffer config, visual) with the required attributes.
+    PixelFormat(String),
+    /// The window or one of its properties could not be created.
+    Window(String),
+    /// The OpenGL 4.5 context could not be created or made current.
+    Context(String),
+}
+
+impl std::fmt::Display for WindowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WindowError::Display(msg) => write!(f, "Display error: {}", msg),
+            WindowError::PixelFormat(msg) => write!(f, "Pixel format error: {}", msg),
+            WindowError::Window(msg) => write!(f, "Window error: {}", msg),
+            WindowError::Context(msg) => write!(f, "OpenGL context error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WindowError {}
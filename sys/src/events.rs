@@ -105,7 +105,7 @@ pub struct MouseWheelEventData {
     pub pointer_x: i32,
     /// < Y pointer position (client coords)
     pub pointer_y: i32,
-    /// < Amount of movement.
+    /// < Amount of movement, in wheel steps. Positive when the wheel is rotated away from the user.
     pub delta: i32,
     /// < Active modifiers
     pub button1: bool,
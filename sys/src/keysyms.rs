@@ -1,4 +1,6 @@
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+use num_derive::FromPrimitive;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, FromPrimitive)]
 #[repr(u8)]
 pub enum KeySymbol {
     Unknown = 0x0,
@@ -112,3 +114,38 @@ impl std::default::Default for KeySymbol {
         KeySymbol::Unknown
     }
 }
+
+impl std::str::FromStr for KeySymbol {
+    type Err = String;
+
+    /// Parses the name of a key, as printed by `Debug` ("Escape", "KeyA", "F1", ..).
+    /// Case is ignored and single letters or digits can be used instead of "KeyA", "Key1", ..
+    fn from_str(s: &str) -> Result<KeySymbol, String> {
+        let name = s.trim();
+        let name = match name.as_bytes() {
+            [c] if c.is_ascii_alphanumeric() => format!("Key{}", name),
+            _ => name.to_string(),
+        };
+
+        (KeySymbol::Unknown as u8 + 1..=KeySymbol::F15 as u8)
+            .filter_map(num::FromPrimitive::from_u8)
+            .find(|k: &KeySymbol| format!("{:?}", k).eq_ignore_ascii_case(&name))
+            .ok_or_else(|| format!("Unknown key name '{}'", s.trim()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_names() {
+        assert_eq!("Escape".parse::<KeySymbol>(), Ok(KeySymbol::Escape));
+        assert_eq!("kpadd".parse::<KeySymbol>(), Ok(KeySymbol::KpAdd));
+        assert_eq!(" F12 ".parse::<KeySymbol>(), Ok(KeySymbol::F12));
+        assert_eq!("s".parse::<KeySymbol>(), Ok(KeySymbol::KeyS));
+        assert_eq!("7".parse::<KeySymbol>(), Ok(KeySymbol::Key7));
+        assert!("Unknown".parse::<KeySymbol>().is_err());
+        assert!("NotAKey".parse::<KeySymbol>().is_err());
+    }
+}
@@ -3,19 +3,25 @@ mod unique_resource;
 
 pub use unique_resource::{ResourceDeleter, UniqueResource};
 
+mod error;
+pub use self::error::WindowError;
+
 mod scope_guard;
 pub use scope_guard::ScopeGuard;
 
+mod window_builder;
+pub use self::window_builder::{FullscreenMode, Monitor, MonitorRect, WindowBuilder};
+
 #[cfg(windows)]
 mod window_win32;
 
 #[cfg(windows)]
-pub use self::window_win32::{FrameContext, SimpleWindow};
+pub use self::window_win32::{request_fullscreen_toggle, request_quit, FrameContext, SimpleWindow};
 
 #[cfg(unix)]
 mod window_x11;
 #[cfg(unix)]
-pub use self::window_x11::SimpleWindow;
+pub use self::window_x11::{request_fullscreen_toggle, request_quit, SimpleWindow};
 
 mod events;
 mod keysyms;
@@ -25,5 +31,8 @@ pub mod input {
     pub use super::keysyms::*;
 }
 
+mod logger;
+pub use self::logger::{json_escape, LogFilter, LogFormat, Logger};
+
 mod memory_mapped_file;
 pub use self::memory_mapped_file::MemoryMappedFile;
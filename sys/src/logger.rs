@@ -0,0 +1,232 @@
+use log::{LevelFilter, Log, Metadata, Record};
+use std::io::Write;
+use std::time::Instant;
+
+/// How records are written to stderr.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// `[   1.234s INFO  sys::window_x11] message`
+    Text,
+    /// One JSON object per line, with the `ts`, `level`, `target` and `msg` keys.
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFormat, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("Unknown log format '{}' (text or json)", s.trim())),
+        }
+    }
+}
+
+/// Maximum level per module target. Parsed from a comma separated list of directives,
+/// a bare level sets the default and `target=level` overrides it for a module and its
+/// children. `warn,rendering=debug,sys::window_x11=trace` for example.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFilter {
+    default: LevelFilter,
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl LogFilter {
+    pub fn new(default: LevelFilter) -> LogFilter {
+        LogFilter {
+            default,
+            targets: Vec::new(),
+        }
+    }
+
+    pub fn add_target(&mut self, target: &str, level: LevelFilter) -> &mut Self {
+        self.targets.retain(|(t, _)| t != target);
+        self.targets.push((target.to_string(), level));
+        //
+        // longest (most specific) targets first
+        self.targets.sort_by_key(|t| std::cmp::Reverse(t.0.len()));
+        self
+    }
+
+    /// The level of the most specific directive matching `target`.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(t, _)| {
+                target == t
+                    || (target.starts_with(t.as_str()) && target[t.len()..].starts_with("::"))
+            })
+            .map(|&(_, level)| level)
+            .unwrap_or(self.default)
+    }
+
+    /// The most verbose level of all the directives.
+    pub fn max_level(&self) -> LevelFilter {
+        self.targets
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, std::cmp::max)
+    }
+}
+
+impl std::default::Default for LogFilter {
+    fn default() -> Self {
+        LogFilter::new(LevelFilter::Info)
+    }
+}
+
+impl std::str::FromStr for LogFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LogFilter, String> {
+        let parse_level = |level: &str| {
+            level
+                .trim()
+                .parse::<LevelFilter>()
+                .map_err(|_| format!("Unknown log level '{}'", level.trim()))
+        };
+
+        s.split(',')
+            .map(|directive| directive.trim())
+            .filter(|directive| !directive.is_empty())
+            .try_fold(LogFilter::default(), |mut filter, directive| {
+                match directive.find('=') {
+                    Some(pos) => {
+                        let level = parse_level(&directive[pos + 1..])?;
+                        filter.add_target(directive[..pos].trim(), level);
+                    }
+                    None => filter.default = parse_level(directive)?,
+                }
+                Ok(filter)
+            })
+    }
+}
+
+/// Writes the records that pass the filter to stderr.
+pub struct Logger {
+    filter: LogFilter,
+    format: LogFormat,
+    start: Instant,
+}
+
+impl Logger {
+    pub fn new(filter: LogFilter, format: LogFormat) -> Logger {
+        Logger {
+            filter,
+            format,
+            start: Instant::now(),
+        }
+    }
+
+    /// Installs the logger for the `log` macros, can only be done once.
+    pub fn install(self) -> Result<(), String> {
+        let max_level = self.filter.max_level();
+        log::set_boxed_logger(Box::new(self))
+            .map_err(|e| format!("Failed to install logger: {}", e))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+
+    fn format_record(&self, secs: f64, record: &Record) -> String {
+        match self.format {
+            LogFormat::Text => format!(
+                "[{:>10.3}s {:<5} {}] {}",
+                secs,
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+            LogFormat::Json => format!(
+                "{{\"ts\":{:.6},\"level\":\"{}\",\"target\":\"{}\",\"msg\":\"{}\"}}",
+                secs,
+                record.level(),
+                json_escape(record.target()),
+                json_escape(&record.args().to_string())
+            ),
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let line = self.format_record(self.start.elapsed().as_secs_f64(), record);
+        let stderr = std::io::stderr();
+        let _ = writeln!(stderr.lock(), "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// Escapes `s` for use inside a JSON string literal, the quotes are not added.
+pub fn json_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| match c {
+            '"' => vec!['\\', '"'],
+            '\\' => vec!['\\', '\\'],
+            '\n' => vec!['\\', 'n'],
+            c if c.is_control() => format!("\\u{:04x}", c as u32).chars().collect(),
+            c => vec![c],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter() {
+        let filter = "warn, rendering=debug,sys::window_x11=trace,,"
+            .parse::<LogFilter>()
+            .unwrap();
+
+        assert_eq!(filter.level_for("particles::particles"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("rendering"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("rendering::gl_debug"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("rendering_extra"), LevelFilter::Warn);
+        assert_eq!(filter.level_for("sys::window_x11"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("sys::logger"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+
+        assert_eq!(
+            "debug".parse::<LogFilter>(),
+            Ok(LogFilter::new(LevelFilter::Debug))
+        );
+        assert!("rendering=loud".parse::<LogFilter>().is_err());
+        assert_eq!("JSON".parse::<LogFormat>(), Ok(LogFormat::Json));
+    }
+
+    #[test]
+    fn test_record_format() {
+        let record = |logger: &Logger| {
+            logger.format_record(
+                1.5f64,
+                &Record::builder()
+                    .args(format_args!("say \"hi\"\n"))
+                    .level(log::Level::Warn)
+                    .target("sys::window_x11")
+                    .build(),
+            )
+        };
+
+        assert_eq!(
+            record(&Logger::new(LogFilter::default(), LogFormat::Text)),
+            "[     1.500s WARN  sys::window_x11] say \"hi\"\n"
+        );
+        assert_eq!(
+            record(&Logger::new(LogFilter::default(), LogFormat::Json)),
+            "{\"ts\":1.500000,\"level\":\"WARN\",\"target\":\"sys::window_x11\",\"msg\":\"say \\\"hi\\\"\\n\"}"
+        );
+    }
+}
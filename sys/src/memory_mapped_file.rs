@@ -275,13 +275,14 @@ mod tests {
         let mmfile = MemoryMappedFile::new(Path::new("non-existing-test-file.txt"));
         assert!(mmfile.is_err());
 
+        let path = std::env::temp_dir().join(format!("sys-mmfile-{}.txt", std::process::id()));
         let txt = b"A memory mapped file\0";
         {
-            let mut f = File::create("test.txt").unwrap();
+            let mut f = File::create(&path).unwrap();
             f.write_all(txt).unwrap();
         }
 
-        let mmfile = MemoryMappedFile::new(Path::new("test.txt"));
+        let mmfile = MemoryMappedFile::new(&path);
         assert!(!mmfile.is_err());
         let mmfile = mmfile.unwrap();
 
@@ -290,5 +291,8 @@ mod tests {
             let org = CStr::from_bytes_with_nul(b"A memory mapped file\0").unwrap();
             assert_eq!(m, org);
         }
+
+        drop(mmfile);
+        let _ = std::fs::remove_file(&path);
     }
 }
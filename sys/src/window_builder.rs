@@ -0,0 +1,287 @@
+use crate::error::WindowError;
+use crate::SimpleWindow;
+
+/// How a window covers its monitor.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// A regular window with the size and position given to the builder.
+    Windowed,
+    /// An undecorated window covering the whole monitor, the desktop keeps running.
+    Borderless,
+    /// Covers the monitor and asks the window manager/compositor to get out of the way.
+    /// The video mode of the monitor is never changed.
+    Exclusive,
+}
+
+impl FullscreenMode {
+    pub fn is_fullscreen(self) -> bool {
+        self != FullscreenMode::Windowed
+    }
+}
+
+/// Position and size of a monitor (or a window), in desktop coordinates.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MonitorRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// A monitor reported by the display server.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Monitor {
+    pub rect: MonitorRect,
+    pub primary: bool,
+}
+
+/// Options for the main window, `build` creates the window and its OpenGL context.
+///
+/// ```no_run
+/// # use sys::{FullscreenMode, WindowBuilder};
+/// let window = WindowBuilder::new()
+///     .set_title("particles")
+///     .set_size(1600, 900)
+///     .set_monitor(1)
+///     .set_fullscreen(FullscreenMode::Borderless)
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct WindowBuilder {
+    pub(crate) title: String,
+    size: Option<(i32, i32)>,
+    position: Option<(i32, i32)>,
+    monitor: Option<usize>,
+    pub(crate) fullscreen: FullscreenMode,
+    pub(crate) resizable: bool,
+    pub(crate) decorations: bool,
+    pub(crate) debug_context: bool,
+}
+
+impl Default for WindowBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WindowBuilder {
+    /// Used when no size is given, shrunk to fit the monitor.
+    pub const DEFAULT_SIZE: (i32, i32) = (1280, 720);
+
+    /// A resizable, decorated window with the default size, centered on the primary
+    /// monitor.
+    pub fn new() -> WindowBuilder {
+        WindowBuilder {
+            title: "Rusted OpenGL".to_string(),
+            size: None,
+            position: None,
+            monitor: None,
+            fullscreen: FullscreenMode::Windowed,
+            resizable: true,
+            decorations: true,
+            debug_context: false,
+        }
+    }
+
+    pub fn set_title(&mut self, title: &str) -> &mut Self {
+        self.title = title.to_string();
+        self
+    }
+
+    /// Size of the client area in windowed mode.
+    pub fn set_size(&mut self, width: i32, height: i32) -> &mut Self {
+        self.size = Some((width, height));
+        self
+    }
+
+    /// Position of the top left corner in windowed mode, relative to the monitor.
+    pub fn set_position(&mut self, x: i32, y: i32) -> &mut Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    /// Index of the monitor the window opens on, in the order the display server reports
+    /// them. The primary monitor is used if this is not set.
+    pub fn set_monitor(&mut self, index: usize) -> &mut Self {
+        self.monitor = Some(index);
+        self
+    }
+
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) -> &mut Self {
+        self.fullscreen = mode;
+        self
+    }
+
+    /// Some window managers refuse to make a window that is not resizable fullscreen.
+    pub fn set_resizable(&mut self, resizable: bool) -> &mut Self {
+        self.resizable = resizable;
+        self
+    }
+
+    /// Title bar and borders in windowed mode.
+    pub fn set_decorations(&mut self, decorations: bool) -> &mut Self {
+        self.decorations = decorations;
+        self
+    }
+
+    /// A debug context reports errors and performance warnings through the KHR_debug
+    /// callback, at some cost in performance.
+    pub fn set_debug_context(&mut self, debug_context: bool) -> &mut Self {
+        self.debug_context = debug_context;
+        self
+    }
+
+    pub fn build(&self) -> Result<SimpleWindow, WindowError> {
+        SimpleWindow::from_builder(self)
+    }
+
+    /// The monitor the window opens on: the one asked for, else the primary one, else
+    /// the first one.
+    pub(crate) fn select_monitor(&self, monitors: &[Monitor]) -> Result<usize, WindowError> {
+        match self.monitor {
+            Some(idx) if idx < monitors.len() => Ok(idx),
+            Some(idx) => Err(WindowError::Display(format!(
+                "No monitor {}, there are {} monitor(s)",
+                idx,
+                monitors.len()
+            ))),
+            None if monitors.is_empty() => {
+                Err(WindowError::Display("No monitors found!".to_string()))
+            }
+            None => Ok(monitors.iter().position(|m| m.primary).unwrap_or(0)),
+        }
+    }
+
+    /// Client area of the window when it is not fullscreen, centered on `monitor` if no
+    /// position was given.
+    pub(crate) fn windowed_rect(&self, monitor: &MonitorRect) -> Result<MonitorRect, WindowError> {
+        let (width, height) = match self.size {
+            Some((w, h)) if w <= 0 || h <= 0 => {
+                return Err(WindowError::Window(format!(
+                    "Invalid window size {}x{}",
+                    w, h
+                )));
+            }
+            Some(size) => size,
+            None => (
+                Self::DEFAULT_SIZE.0.min(monitor.width),
+                Self::DEFAULT_SIZE.1.min(monitor.height),
+            ),
+        };
+
+        let (x, y) = self.position.unwrap_or((
+            (monitor.width - width).max(0) / 2,
+            (monitor.height - height).max(0) / 2,
+        ));
+
+        Ok(MonitorRect {
+            x: monitor.x + x,
+            y: monitor.y + y,
+            width,
+            height,
+        })
+    }
+
+    /// Where the window is created: the whole monitor in fullscreen, the windowed rect
+    /// otherwise.
+    pub(crate) fn initial_rect(&self, monitor: &MonitorRect) -> Result<MonitorRect, WindowError> {
+        let windowed = self.windowed_rect(monitor)?;
+        if self.fullscreen.is_fullscreen() {
+            Ok(*monitor)
+        } else {
+            Ok(windowed)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitors() -> Vec<Monitor> {
+        vec![
+            Monitor {
+                rect: MonitorRect {
+                    x: 0,
+                    y: 0,
+                    width: 1920,
+                    height: 1080,
+                },
+                primary: false,
+            },
+            Monitor {
+                rect: MonitorRect {
+                    x: 1920,
+                    y: 0,
+                    width: 1024,
+                    height: 600,
+                },
+                primary: true,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_select_monitor() {
+        let monitors = monitors();
+        assert_eq!(WindowBuilder::new().select_monitor(&monitors), Ok(1));
+        assert_eq!(
+            WindowBuilder::new()
+                .set_monitor(0)
+                .select_monitor(&monitors),
+            Ok(0)
+        );
+        assert!(WindowBuilder::new()
+            .set_monitor(2)
+            .select_monitor(&monitors)
+            .is_err());
+        assert!(WindowBuilder::new().select_monitor(&[]).is_err());
+    }
+
+    #[test]
+    fn test_placement() {
+        let second = monitors()[1].rect;
+
+        //
+        // default size shrunk to the monitor and centered on it
+        assert_eq!(
+            WindowBuilder::new().initial_rect(&second),
+            Ok(MonitorRect {
+                x: 1920,
+                y: 0,
+                width: 1024,
+                height: 600,
+            })
+        );
+        assert_eq!(
+            WindowBuilder::new()
+                .set_size(800, 400)
+                .initial_rect(&second),
+            Ok(MonitorRect {
+                x: 2032,
+                y: 100,
+                width: 800,
+                height: 400,
+            })
+        );
+        assert_eq!(
+            WindowBuilder::new()
+                .set_size(800, 400)
+                .set_position(10, 20)
+                .initial_rect(&second)
+                .map(|r| (r.x, r.y)),
+            Ok((1930, 20))
+        );
+        assert_eq!(
+            WindowBuilder::new()
+                .set_size(800, 400)
+                .set_fullscreen(FullscreenMode::Borderless)
+                .initial_rect(&second),
+            Ok(second)
+        );
+        assert!(WindowBuilder::new()
+            .set_size(0, 400)
+            .initial_rect(&second)
+            .is_err());
+    }
+}
@@ -9,9 +9,9 @@ use std::string::String;
 
 use winapi::shared::basetsd::LONG_PTR;
 use winapi::shared::minwindef::{
-    DWORD, FALSE, HIWORD, LOWORD, LPARAM, LRESULT, MAKELONG, TRUE, UINT, WORD, WPARAM,
+    BOOL, DWORD, FALSE, HIWORD, LOWORD, LPARAM, LRESULT, MAKELONG, TRUE, UINT, WORD, WPARAM,
 };
-use winapi::shared::windef::{HBRUSH, HDC, HGLRC, HWND, POINT, RECT};
+use winapi::shared::windef::{HBRUSH, HDC, HGLRC, HMONITOR, HWND, LPRECT, RECT};
 use winapi::shared::windowsx::{GET_X_LPARAM, GET_Y_LPARAM};
 
 use winapi::um::libloaderapi::GetModuleHandleW;
@@ -27,11 +27,12 @@ use winapi::um::wingdi::{
 use winapi::um::winuser::CreateWindowExW;
 use winapi::um::winuser::DefWindowProcW;
 use winapi::um::winuser::DestroyWindow;
+use winapi::um::winuser::EnumDisplayMonitors;
 use winapi::um::winuser::GetDC;
 use winapi::um::winuser::GetMonitorInfoW;
 use winapi::um::winuser::LoadCursorW;
 use winapi::um::winuser::LoadIconW;
-use winapi::um::winuser::MonitorFromPoint;
+use winapi::um::winuser::MonitorFromWindow;
 use winapi::um::winuser::RegisterClassExW;
 use winapi::um::winuser::ReleaseDC;
 use winapi::um::winuser::WindowFromDC;
@@ -39,7 +40,8 @@ use winapi::um::winuser::CW_USEDEFAULT;
 use winapi::um::winuser::IDC_ARROW;
 use winapi::um::winuser::IDI_APPLICATION;
 use winapi::um::winuser::MONITORINFO;
-use winapi::um::winuser::MONITOR_DEFAULTTOPRIMARY;
+use winapi::um::winuser::MONITORINFOF_PRIMARY;
+use winapi::um::winuser::MONITOR_DEFAULTTONEAREST;
 use winapi::um::winuser::WNDCLASSEXW;
 use winapi::um::winuser::{
     AdjustWindowRectEx, DispatchMessageW, GetClientRect, GetKeyNameTextW, GetMessageW,
@@ -50,9 +52,16 @@ use winapi::um::winuser::{
     WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MOUSEMOVE, WM_MOUSEWHEEL,
     WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SIZE, WM_WINDOWPOSCHANGED, WS_OVERLAPPED, WS_POPUP,
 };
+use winapi::um::winuser::{
+    SetWindowPos, SetWindowTextW, GWL_EXSTYLE, GWL_STYLE, HWND_NOTOPMOST, HWND_TOP, HWND_TOPMOST,
+    SWP_FRAMECHANGED, SWP_NOOWNERZORDER, WS_EX_TOPMOST, WS_MAXIMIZEBOX, WS_OVERLAPPEDWINDOW,
+    WS_THICKFRAME, WS_VISIBLE,
+};
 
 use super::input::*;
 use super::scope_guard::ScopeGuard;
+use crate::error::WindowError;
+use crate::window_builder::{FullscreenMode, Monitor, MonitorRect, WindowBuilder};
 
 #[allow(non_snake_case)]
 fn MAKELPARAM(l: WORD, h: WORD) -> LPARAM {
@@ -157,7 +166,7 @@ macro_rules! wgl_load_proc {
     };
 }
 
-fn get_suitable_pixel_format() -> Result<i32, String> {
+fn get_suitable_pixel_format() -> Result<i32, WindowError> {
     let temp_window_classname: &'static str = "__temporary_gl_ctx_window__";
     let w32_temp_window_classname = make_win_str(temp_window_classname);
 
@@ -171,7 +180,9 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
 
     unsafe {
         if RegisterClassExW(&wclass) == FALSE as u16 {
-            return Err("Failed to register window class!".to_string());
+            return Err(WindowError::Window(
+                "Failed to register window class!".to_string(),
+            ));
         }
     }
 
@@ -204,7 +215,9 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
     );
 
     if (*guard_wnd).is_null() {
-        return Err("Failed to create temporary window".to_string());
+        return Err(WindowError::Window(
+            "Failed to create temporary window".to_string(),
+        ));
     }
 
     let temp_dc = ScopeGuard::new(unsafe { GetDC(*guard_wnd) }, |dc| {
@@ -217,7 +230,9 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
     });
 
     if (*temp_dc).is_null() {
-        return Err("Failed to create temporary DC!".to_string());
+        return Err(WindowError::Window(
+            "Failed to create temporary DC!".to_string(),
+        ));
     }
 
     let mut pfd = unsafe { MaybeUninit::<PIXELFORMATDESCRIPTOR>::zeroed().assume_init() };
@@ -233,12 +248,16 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
     let pixel_format = unsafe { ChoosePixelFormat(*temp_dc, &pfd) };
 
     if pixel_format == 0 {
-        return Err("Failed to found a suitable OpenGL pixel format!".to_string());
+        return Err(WindowError::PixelFormat(
+            "Failed to found a suitable OpenGL pixel format!".to_string(),
+        ));
     }
 
     unsafe {
         if SetPixelFormat(*temp_dc, pixel_format, &pfd) == FALSE {
-            return Err("Failed to set OpenGL pixel format for temporary window!".to_string());
+            return Err(WindowError::PixelFormat(
+                "Failed to set OpenGL pixel format for temporary window!".to_string(),
+            ));
         }
     }
 
@@ -251,7 +270,9 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
     });
 
     if (*wgl_ctx).is_null() {
-        return Err("Failed to create temporary OpenGL context!".to_string());
+        return Err(WindowError::Context(
+            "Failed to create temporary OpenGL context!".to_string(),
+        ));
     }
 
     let ctx_restores_when_done =
@@ -264,7 +285,9 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
         });
 
     if *ctx_restores_when_done == FALSE {
-        return Err("Failed to make OpenGL context as current context".to_string());
+        return Err(WindowError::Context(
+            "Failed to make OpenGL context as current context".to_string(),
+        ));
     }
 
     #[allow(non_snake_case)]
@@ -278,7 +301,9 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
             .unwrap_or(null_mut());
 
         if proc_addr.is_null() {
-            return Err("wglChoosePixelFormatARB procedure not found!".to_string());
+            return Err(WindowError::Context(
+                "wglChoosePixelFormatARB procedure not found!".to_string(),
+            ));
         }
 
         std::mem::transmute::<_, PFNWGLCHOOSEPIXELFORMATARBPROC>(proc_addr)
@@ -325,20 +350,15 @@ fn get_suitable_pixel_format() -> Result<i32, String> {
     };
 
     if query_result != TRUE || format_count == 0 {
-        return Err("wglChoosePixelFormatARB failed - no format found!".to_string());
+        return Err(WindowError::PixelFormat(
+            "wglChoosePixelFormatARB failed - no format found!".to_string(),
+        ));
     }
 
     Ok(supported_formats[0])
 }
 
-fn get_primary_monitor_dimensions() -> Result<(i32, i32), String> {
-    let origin = POINT { x: 0, y: 0 };
-    let primary_monitor = unsafe { MonitorFromPoint(origin, MONITOR_DEFAULTTOPRIMARY) };
-
-    if primary_monitor.is_null() {
-        return Err("Failed to get handle to primary monitor!".to_string());
-    }
-
+fn monitor_info(monitor: HMONITOR) -> Option<MONITORINFO> {
     let mut monitor_info = unsafe {
         MONITORINFO {
             cbSize: std::mem::size_of::<MONITORINFO>() as u32,
@@ -348,16 +368,72 @@ fn get_primary_monitor_dimensions() -> Result<(i32, i32), String> {
         }
     };
 
-    unsafe {
-        if GetMonitorInfoW(primary_monitor, &mut monitor_info) != TRUE {
-            return Err("Failed to query primary monitor dimensions!".to_string());
+    if unsafe { GetMonitorInfoW(monitor, &mut monitor_info) } == TRUE {
+        Some(monitor_info)
+    } else {
+        None
+    }
+}
+
+fn monitor_rect(rc: &RECT) -> MonitorRect {
+    MonitorRect {
+        x: rc.left,
+        y: rc.top,
+        width: (rc.right - rc.left).abs(),
+        height: (rc.bottom - rc.top).abs(),
+    }
+}
+
+/// The monitors in the order `EnumDisplayMonitors` reports them.
+fn get_monitors() -> Result<Vec<Monitor>, WindowError> {
+    unsafe extern "system" fn collect_monitor(
+        monitor: HMONITOR,
+        _dc: HDC,
+        _rc: LPRECT,
+        data: LPARAM,
+    ) -> BOOL {
+        let monitors = &mut *(data as *mut Vec<Monitor>);
+        if let Some(info) = monitor_info(monitor) {
+            monitors.push(Monitor {
+                rect: monitor_rect(&info.rcMonitor),
+                primary: (info.dwFlags & MONITORINFOF_PRIMARY) != 0,
+            });
         }
+        TRUE
+    }
+
+    let mut monitors = Vec::<Monitor>::new();
+    let enumerated = unsafe {
+        EnumDisplayMonitors(
+            null_mut(),
+            null(),
+            Some(collect_monitor),
+            &mut monitors as *mut Vec<Monitor> as LPARAM,
+        )
+    };
+
+    if enumerated != TRUE || monitors.is_empty() {
+        return Err(WindowError::Display(
+            "Failed to enumerate monitors!".to_string(),
+        ));
     }
 
-    Ok((
-        (monitor_info.rcWork.right - monitor_info.rcWork.left).abs(),
-        (monitor_info.rcWork.bottom - monitor_info.rcWork.top).abs(),
-    ))
+    Ok(monitors)
+}
+
+/// Window styles (style, extended style) for a fullscreen mode.
+fn window_style(mode: FullscreenMode, decorations: bool, resizable: bool) -> (DWORD, DWORD) {
+    match mode {
+        FullscreenMode::Windowed if decorations && resizable => (WS_OVERLAPPEDWINDOW, 0),
+        FullscreenMode::Windowed if decorations => {
+            (WS_OVERLAPPEDWINDOW & !(WS_THICKFRAME | WS_MAXIMIZEBOX), 0)
+        }
+        FullscreenMode::Windowed | FullscreenMode::Borderless => (WS_POPUP, 0),
+        //
+        // no display mode change, a topmost popup covering the monitor lets the driver
+        // flip the back buffer directly
+        FullscreenMode::Exclusive => (WS_POPUP, WS_EX_TOPMOST),
+    }
 }
 
 gen_unique_resource_type!(
@@ -385,6 +461,23 @@ gen_unique_resource_type!(
     }
 );
 
+/// Asks the message loop running on this thread to exit. The loop finishes the current
+/// iteration (input, loop event, buffer swap) and then returns.
+pub fn request_quit() {
+    unsafe {
+        PostQuitMessage(0);
+    }
+}
+
+static FULLSCREEN_TOGGLE_REQUESTED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Asks the message loop to toggle fullscreen on the window it runs, after the buffer
+/// swap of the current iteration.
+pub fn request_fullscreen_toggle() {
+    FULLSCREEN_TOGGLE_REQUESTED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct FrameContext {
     pub screen_width: i32,
@@ -398,6 +491,13 @@ pub struct SimpleWindow {
     window: HWND,
     win_size: Cell<(i32, i32)>,
     framebuffer_size: Cell<(i32, i32)>,
+    fullscreen: Cell<FullscreenMode>,
+    /// Mode used when toggling fullscreen on.
+    fullscreen_mode: Cell<FullscreenMode>,
+    decorations: bool,
+    resizable: bool,
+    /// Outer rect of the window before it went fullscreen, restored when it leaves.
+    windowed_rect: Cell<MonitorRect>,
 }
 
 impl std::ops::Drop for SimpleWindow {
@@ -409,8 +509,31 @@ impl std::ops::Drop for SimpleWindow {
 }
 
 impl SimpleWindow {
-    pub fn new() -> Result<SimpleWindow, String> {
-        let screen_size = get_primary_monitor_dimensions()?;
+    /// A borderless window covering the primary monitor.
+    pub fn new() -> Result<SimpleWindow, WindowError> {
+        Self::with_debug_context(false)
+    }
+
+    /// A borderless window covering the primary monitor. A debug context reports errors
+    /// and performance warnings through the KHR_debug callback, at some cost in
+    /// performance.
+    pub fn with_debug_context(debug_context: bool) -> Result<SimpleWindow, WindowError> {
+        WindowBuilder::new()
+            .set_fullscreen(FullscreenMode::Borderless)
+            .set_debug_context(debug_context)
+            .build()
+    }
+
+    pub(crate) fn from_builder(builder: &WindowBuilder) -> Result<SimpleWindow, WindowError> {
+        let monitors = get_monitors()?;
+        let monitor_idx = builder.select_monitor(&monitors)?;
+        let monitor = monitors[monitor_idx].rect;
+        log::info!("Monitor {} {:?}", monitor_idx, monitor);
+
+        let debug_context = builder.debug_context;
+        let rect = builder.initial_rect(&monitor)?;
+        let (style, ex_style) =
+            window_style(builder.fullscreen, builder.decorations, builder.resizable);
 
         let window_class_name = make_win_str("__rusted_opengl_window__");
         let mut wclass = unsafe { MaybeUninit::<WNDCLASSEXW>::zeroed().assume_init() };
@@ -426,36 +549,62 @@ impl SimpleWindow {
 
         unsafe {
             if RegisterClassExW(&wclass) == FALSE as u16 {
-                return Err("Failed to register main window class".to_string());
+                return Err(WindowError::Window(
+                    "Failed to register main window class".to_string(),
+                ));
             }
         }
 
         let client_rect = unsafe {
             let mut client_rect = RECT {
-                left: 0,
-                top: 0,
-                right: screen_size.0,
-                bottom: screen_size.1,
+                left: rect.x,
+                top: rect.y,
+                right: rect.x + rect.width,
+                bottom: rect.y + rect.height,
             };
 
-            if AdjustWindowRectEx(&mut client_rect, WS_POPUP, FALSE, 0) != TRUE {
-                return Err("Failed to calc window client size!".to_string());
+            if AdjustWindowRectEx(&mut client_rect, style, FALSE, ex_style) != TRUE {
+                return Err(WindowError::Window(
+                    "Failed to calc window client size!".to_string(),
+                ));
             }
 
             client_rect
         };
 
-        println!(
+        log::debug!(
             "Adjusted client rect = {}:{} x {}:{}",
-            client_rect.left, client_rect.top, client_rect.right, client_rect.bottom
+            client_rect.left,
+            client_rect.top,
+            client_rect.right,
+            client_rect.bottom
         );
 
+        //
+        // where the window goes when it leaves fullscreen
+        let windowed_rect = unsafe {
+            let client = builder.windowed_rect(&monitor)?;
+            let (style, ex_style) = window_style(
+                FullscreenMode::Windowed,
+                builder.decorations,
+                builder.resizable,
+            );
+            let mut rc = RECT {
+                left: client.x,
+                top: client.y,
+                right: client.x + client.width,
+                bottom: client.y + client.height,
+            };
+            AdjustWindowRectEx(&mut rc, style, FALSE, ex_style);
+            monitor_rect(&rc)
+        };
+
         let window = unsafe {
             CreateWindowExW(
-                0,
+                ex_style,
                 window_class_name.as_ptr(),
-                make_win_str("Rusted OpenGL").as_ptr(),
-                WS_POPUP,
+                make_win_str(&builder.title).as_ptr(),
+                style,
                 client_rect.left,
                 client_rect.top,
                 (client_rect.right - client_rect.left).abs(),
@@ -468,13 +617,15 @@ impl SimpleWindow {
         };
 
         if window.is_null() {
-            return Err("Failed to create main window!".to_string());
+            return Err(WindowError::Window(
+                "Failed to create main window!".to_string(),
+            ));
         }
 
         //
         // get the device context
         let window_dc = UniqueDeviceContext::new(unsafe { GetDC(window) })
-            .ok_or("Failed to get window DC!".to_string())?;
+            .ok_or_else(|| WindowError::Window("Failed to get window DC!".to_string()))?;
 
         //
         // set pixel a format suitable for OpenGL
@@ -488,7 +639,9 @@ impl SimpleWindow {
                 &mut pfd,
             ) == FALSE
             {
-                Err("Failed to describe pixel format".to_string())
+                Err(WindowError::PixelFormat(
+                    "Failed to describe pixel format".to_string(),
+                ))
             } else {
                 Ok(pfd)
             }
@@ -496,7 +649,9 @@ impl SimpleWindow {
 
         unsafe {
             if SetPixelFormat(*window_dc, pixel_format, &pfd) != TRUE {
-                return Err("Failed to set pixel format".to_string());
+                return Err(WindowError::PixelFormat(
+                    "Failed to set pixel format".to_string(),
+                ));
             }
         }
 
@@ -504,7 +659,9 @@ impl SimpleWindow {
         let wglCreateContextAttribsARB = unsafe {
             let wgl_ctx = ScopeGuard::new(wglCreateContext(*window_dc), wgl_context_destructor);
             if wgl_ctx.is_null() {
-                return Err("Failed to create temporary OpenGL context".to_string());
+                return Err(WindowError::Context(
+                    "Failed to create temporary OpenGL context".to_string(),
+                ));
             }
 
             let _context_needs_restored =
@@ -515,7 +672,9 @@ impl SimpleWindow {
                 });
 
             if *_context_needs_restored != TRUE {
-                return Err("Failed to make temporary OpenGL context current".to_string());
+                return Err(WindowError::Context(
+                    "Failed to make temporary OpenGL context current".to_string(),
+                ));
             }
 
             let func_ptr = wgl_load_proc!(
@@ -524,7 +683,8 @@ impl SimpleWindow {
             );
 
             func_ptr
-        }?;
+        }
+        .map_err(WindowError::Context)?;
 
         let opengl_context_attributes: [i32; 9] = [
             wgl_ffi::WGL_CONTEXT_MAJOR_VERSION_ARB,
@@ -532,7 +692,11 @@ impl SimpleWindow {
             wgl_ffi::WGL_CONTEXT_MINOR_VERSION_ARB,
             5,
             wgl_ffi::WGL_CONTEXT_FLAGS_ARB,
-            wgl_ffi::WGL_CONTEXT_DEBUG_BIT_ARB | wgl_ffi::WGL_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB,
+            if debug_context {
+                wgl_ffi::WGL_CONTEXT_DEBUG_BIT_ARB | wgl_ffi::WGL_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB
+            } else {
+                wgl_ffi::WGL_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB
+            },
             wgl_ffi::WGL_CONTEXT_PROFILE_MASK_ARB,
             wgl_ffi::WGL_CONTEXT_CORE_PROFILE_BIT_ARB,
             0,
@@ -541,11 +705,13 @@ impl SimpleWindow {
         let opengl_context = UniqueOpenGLContext::new(unsafe {
             wglCreateContextAttribsARB(*window_dc, null_mut(), opengl_context_attributes.as_ptr())
         })
-        .ok_or("Failed to create OpenGL context!".to_string())?;
+        .ok_or_else(|| WindowError::Context("Failed to create OpenGL context!".to_string()))?;
 
         unsafe {
             if wglMakeCurrent(*window_dc, *opengl_context) != TRUE {
-                return Err("Failed to make OpenGL context current!".to_string());
+                return Err(WindowError::Context(
+                    "Failed to make OpenGL context current!".to_string(),
+                ));
             }
         }
 
@@ -567,6 +733,15 @@ impl SimpleWindow {
             opengl_context,
             win_size: Cell::new(win_size),
             framebuffer_size: Cell::new((0, 0)),
+            fullscreen: Cell::new(builder.fullscreen),
+            fullscreen_mode: Cell::new(if builder.fullscreen.is_fullscreen() {
+                builder.fullscreen
+            } else {
+                FullscreenMode::Borderless
+            }),
+            decorations: builder.decorations,
+            resizable: builder.resizable,
+            windowed_rect: Cell::new(windowed_rect),
         })
     }
 
@@ -574,6 +749,77 @@ impl SimpleWindow {
         self.win_size.get()
     }
 
+    pub fn set_title(&self, title: &str) {
+        unsafe {
+            SetWindowTextW(self.window, make_win_str(title).as_ptr());
+        }
+    }
+
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.fullscreen.get()
+    }
+
+    /// Switches the window style and covers the monitor the window is on, or restores the
+    /// rect the window had before going fullscreen. The new size arrives with WM_SIZE.
+    pub fn set_fullscreen(&self, mode: FullscreenMode) {
+        let current = self.fullscreen.get();
+        if mode == current {
+            return;
+        }
+
+        if !current.is_fullscreen() {
+            let mut wr = unsafe { MaybeUninit::<RECT>::zeroed().assume_init() };
+            unsafe {
+                GetWindowRect(self.window, &mut wr);
+            }
+            self.windowed_rect.set(monitor_rect(&wr));
+        }
+
+        let target = if mode.is_fullscreen() {
+            unsafe { monitor_info(MonitorFromWindow(self.window, MONITOR_DEFAULTTONEAREST)) }
+                .map(|info| monitor_rect(&info.rcMonitor))
+                .unwrap_or_else(|| self.windowed_rect.get())
+        } else {
+            self.windowed_rect.get()
+        };
+
+        let (style, ex_style) = window_style(mode, self.decorations, self.resizable);
+        let insert_after = match mode {
+            FullscreenMode::Exclusive => HWND_TOPMOST,
+            _ if current == FullscreenMode::Exclusive => HWND_NOTOPMOST,
+            _ => HWND_TOP,
+        };
+
+        unsafe {
+            SetWindowLongPtrW(self.window, GWL_STYLE, (style | WS_VISIBLE) as LONG_PTR);
+            SetWindowLongPtrW(self.window, GWL_EXSTYLE, ex_style as LONG_PTR);
+            SetWindowPos(
+                self.window,
+                insert_after,
+                target.x,
+                target.y,
+                target.width,
+                target.height,
+                SWP_FRAMECHANGED | SWP_NOOWNERZORDER,
+            );
+        }
+
+        log::info!("Fullscreen {:?} -> {:?}", current, mode);
+        self.fullscreen.set(mode);
+        if mode.is_fullscreen() {
+            self.fullscreen_mode.set(mode);
+        }
+    }
+
+    /// Switches between windowed and the last fullscreen mode used.
+    pub fn toggle_fullscreen(&self) {
+        if self.fullscreen.get().is_fullscreen() {
+            self.set_fullscreen(FullscreenMode::Windowed);
+        } else {
+            self.set_fullscreen(self.fullscreen_mode.get());
+        }
+    }
+
     fn window_proc(&self, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
         let message_processing_result = match msg {
             WM_CLOSE => unsafe {
@@ -722,10 +968,7 @@ impl SimpleWindow {
 
     fn map_key_symbol(key_sym: WPARAM) -> KeySymbol {
         if key_sym as usize >= WIN32_KEYS_MAPPING_TABLE.len() {
-            println!(
-                "Warning : no mapping exists for native key symbol {}",
-                key_sym
-            );
+            log::warn!("No mapping exists for native key symbol {}", key_sym);
             return KeySymbol::Unknown;
         }
 
@@ -782,7 +1025,7 @@ impl SimpleWindow {
     }
 
     fn event_windowposchanged(&self, w: &WINDOWPOS) {
-        println!("WINDOWPOSCHANGED {} {}", w.cx, w.cy);
+        log::trace!("WINDOWPOSCHANGED {} {}", w.cx, w.cy);
         self.win_size.set((w.cx, w.cy));
     }
 
@@ -795,9 +1038,12 @@ impl SimpleWindow {
             (wr.right - wr.left, wr.bottom - wr.top)
         };
 
-        println!(
+        log::debug!(
             "WM_SIZE event! W: {}, H: {}, FBX: {}, FBY: {}",
-            width, height, win_size.0, win_size.1
+            width,
+            height,
+            win_size.0,
+            win_size.1
         );
 
         self.win_size.set(win_size);
@@ -871,6 +1117,10 @@ impl SimpleWindow {
                 });
 
                 SwapBuffers(*self.window_dc);
+
+                if FULLSCREEN_TOGGLE_REQUESTED.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                    self.toggle_fullscreen();
+                }
                 // std::thread::sleep(std::time::Duration::from_millis(50));
             }
         }
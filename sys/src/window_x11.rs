@@ -22,24 +22,27 @@ use x11::xinerama::{XineramaQueryScreens, XineramaScreenInfo};
 use x11::xlib::{
     AllocNone, AlreadyGrabbed, Atom, Bool, Button1, Button1Mask, Button2, Button2Mask, Button3,
     Button3Mask, Button4, Button4Mask, Button5, Button5Mask, ButtonPress, ButtonPressMask,
-    ButtonRelease, ButtonReleaseMask, CWBackPixel, CWColormap, CWEventMask, CWOverrideRedirect,
-    ClientMessage, ConfigureNotify, ControlMask, CurrentTime, Display, EnterWindowMask,
-    ExposureMask, False, FocusChangeMask, FocusIn, FocusOut, GrabFrozen, GrabInvalidTime,
-    GrabModeAsync, GrabNotViewable, InputHint, InputOutput, KeyPress, KeyPressMask, KeyRelease,
-    KeyReleaseMask, KeySym, LeaveWindowMask, MotionNotify, NoSymbol, PBaseSize, PMinSize,
+    ButtonRelease, ButtonReleaseMask, CWBackPixel, CWColormap, CWEventMask, ClientMessage,
+    ConfigureNotify, ControlMask, CurrentTime, Display, EnterWindowMask, ExposureMask, False,
+    FocusChangeMask, FocusIn, FocusOut, GrabFrozen, GrabInvalidTime, GrabModeAsync,
+    GrabNotViewable, InputHint, InputOutput, KeyPress, KeyPressMask, KeyRelease, KeyReleaseMask,
+    KeySym, LeaveWindowMask, MotionNotify, NoSymbol, PBaseSize, PMaxSize, PMinSize,
     PointerMotionMask, PropModeReplace, RevertToNone, Screen, ShiftMask, StateHint,
-    StructureNotifyMask, SubstructureNotifyMask, SubstructureRedirectMask, True,
-    VisibilityChangeMask, VisibilityNotify, VisibilityUnobscured, Window, XAllocSizeHints,
+    StructureNotifyMask, SubstructureNotifyMask, SubstructureRedirectMask, True, USPosition,
+    USSize, VisibilityChangeMask, VisibilityNotify, VisibilityUnobscured, Window, XAllocSizeHints,
     XAllocWMHints, XButtonEvent, XChangeProperty, XClearWindow, XClientMessageEvent, XCloseDisplay,
     XConfigureEvent, XCreateColormap, XCreateWindow, XDefaultRootWindow, XDefaultScreen,
-    XDestroyWindow, XEvent, XEventsQueued, XFlush, XFocusChangeEvent, XFree, XGetGeometry,
-    XGrabKeyboard, XInternAtom, XKeyEvent, XLookupString, XMapRaised, XMotionEvent, XMoveWindow,
-    XNextEvent, XOpenDisplay, XQueryPointer, XRootWindow, XSendEvent, XSetInputFocus,
-    XSetWMProperties, XSetWMProtocols, XSetWindowAttributes, XSizeHints, XSync, XUngrabKeyboard,
-    XVisibilityEvent, XVisualInfo, XWMHints, XWarpPointer, XWhitePixel, XA_ATOM,
+    XDeleteProperty, XDestroyWindow, XEvent, XEventsQueued, XFlush, XFocusChangeEvent, XFree,
+    XGetGeometry, XGrabKeyboard, XInternAtom, XKeyEvent, XLookupString, XMapRaised, XMotionEvent,
+    XMoveWindow, XNextEvent, XOpenDisplay, XQueryPointer, XRootWindow, XSendEvent, XSetInputFocus,
+    XSetWMProperties, XSetWMProtocols, XSetWindowAttributes, XSizeHints, XStoreName, XSync,
+    XUngrabKeyboard, XVisibilityEvent, XVisualInfo, XWMHints, XWarpPointer, XWhitePixel, XA_ATOM,
+    XA_CARDINAL,
 };
 
 use super::input::*;
+use crate::error::WindowError;
+use crate::window_builder::{FullscreenMode, Monitor, MonitorRect, WindowBuilder};
 
 use std::ffi::{CStr, CString};
 use std::mem::transmute;
@@ -56,6 +59,23 @@ type PFNGLXCREATECONTEXTATTRIBSARBPROC = unsafe extern "C" fn(
 // type PFNGLXSWAPINTERVALEXT =
 //     unsafe extern "C" fn(dpy: *mut Display, drawable: GLXDrawable, interval: c_int);
 
+static QUIT_REQUESTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Asks the message loop to exit. The loop finishes the current
+/// iteration (input, loop event, buffer swap) and then returns.
+pub fn request_quit() {
+    QUIT_REQUESTED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
+static FULLSCREEN_TOGGLE_REQUESTED: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Asks the message loop to toggle fullscreen on the window it runs, after the buffer
+/// swap of the current iteration.
+pub fn request_fullscreen_toggle() {
+    FULLSCREEN_TOGGLE_REQUESTED.store(true, std::sync::atomic::Ordering::SeqCst);
+}
+
 #[link(name = "GL")]
 extern "C" {
     pub fn glXGetProcAddress(_1: *const c_uchar) -> *mut c_void;
@@ -119,17 +139,40 @@ pub struct SimpleWindow {
     dpy: *mut Display,
     size: std::cell::Cell<(i32, i32)>,
     win_size: std::cell::Cell<(i32, i32)>,
+    fullscreen: std::cell::Cell<FullscreenMode>,
+    /// Mode used when toggling fullscreen on.
+    fullscreen_mode: std::cell::Cell<FullscreenMode>,
 }
 
 impl SimpleWindow {
-    pub fn new() -> Result<SimpleWindow, String> {
+    /// A borderless window covering the primary monitor.
+    pub fn new() -> Result<SimpleWindow, WindowError> {
+        Self::with_debug_context(false)
+    }
+
+    /// A borderless window covering the primary monitor. A debug context reports errors
+    /// and performance warnings through the KHR_debug callback, at some cost in
+    /// performance.
+    pub fn with_debug_context(debug_context: bool) -> Result<SimpleWindow, WindowError> {
+        WindowBuilder::new()
+            .set_fullscreen(FullscreenMode::Borderless)
+            .set_debug_context(debug_context)
+            .build()
+    }
+
+    pub(crate) fn from_builder(builder: &WindowBuilder) -> Result<SimpleWindow, WindowError> {
         let dpy = unsafe { XOpenDisplay(null()) };
         if dpy.is_null() {
-            return Err("Failed to open display!".into());
+            return Err(WindowError::Display("Failed to open display!".into()));
         }
 
-        let primary_screen = platform_utils::get_primary_screen_info(dpy)?;
-        println!("Primary screen {:?}", primary_screen);
+        let monitors = platform_utils::get_monitors(dpy)?;
+        let monitor_idx = builder.select_monitor(&monitors)?;
+        let monitor = monitors[monitor_idx].rect;
+        log::info!("Monitor {} {:?}", monitor_idx, monitor);
+
+        let windowed_rect = builder.windowed_rect(&monitor)?;
+        let rect = builder.initial_rect(&monitor)?;
 
         let default_screen = unsafe { XDefaultScreen(dpy) };
         let (xvisual, fbcfg) = platform_utils::get_suitable_xvisual(dpy)?;
@@ -160,34 +203,50 @@ impl SimpleWindow {
             XCreateWindow(
                 dpy,
                 root_window,
-                primary_screen.x_org as i32,
-                primary_screen.y_org as i32,
-                primary_screen.width as u32,
-                primary_screen.height as u32,
+                rect.x,
+                rect.y,
+                rect.width as u32,
+                rect.height as u32,
                 0,
                 (**xvisual).depth,
                 InputOutput as u32,
                 (**xvisual).visual,
-                CWEventMask | CWColormap | CWBackPixel | CWOverrideRedirect,
+                CWEventMask | CWColormap | CWBackPixel,
                 &mut xswa as *mut XSetWindowAttributes,
             )
         };
 
         if window == 0 {
-            return Err("XCreateWindow() failed!".to_string());
+            return Err(WindowError::Window("XCreateWindow() failed!".to_string()));
         }
 
-        platform_utils::setup_size_hints(dpy, window, &primary_screen)?;
+        platform_utils::setup_size_hints(dpy, window, &windowed_rect, builder.resizable)?;
+        platform_utils::set_title(dpy, window, &builder.title)?;
+        if !builder.decorations {
+            platform_utils::disable_decorations(dpy, window);
+        }
+        //
+        // the initial state is a property set before mapping, later changes go through
+        // client messages to the window manager
+        if builder.fullscreen.is_fullscreen() {
+            platform_utils::set_initial_fullscreen(dpy, window);
+        }
+        platform_utils::set_bypass_compositor(dpy, window, builder.fullscreen);
 
         //
         // Create and make modern OpenGL context as current
-        let glcontext = platform_utils::create_opengl_context(dpy, &fbcfg, default_screen)?;
+        let glcontext = platform_utils::create_opengl_context(
+            dpy,
+            &fbcfg,
+            default_screen,
+            builder.debug_context,
+        )?;
         unsafe {
             glXMakeContextCurrent(dpy, window, window, glcontext);
         }
 
-        println!("OpenGL context created!");
-        let delete_atom = platform_utils::setup_window(dpy, window, &primary_screen);
+        log::info!("OpenGL context created, debug: {}", builder.debug_context);
+        let delete_atom = platform_utils::setup_window(dpy, window, &rect);
 
         //
         // we have an OpenGL active context so it's safe to load the function pointers now.
@@ -195,6 +254,11 @@ impl SimpleWindow {
         gl::load_with(|s| gl_loader::get_proc_address(s) as *const _);
 
         let size = platform_utils::get_window_client_rect(dpy, window);
+        let fullscreen_mode = if builder.fullscreen.is_fullscreen() {
+            builder.fullscreen
+        } else {
+            FullscreenMode::Borderless
+        };
 
         Ok(SimpleWindow {
             event_receiver: None,
@@ -204,6 +268,8 @@ impl SimpleWindow {
             dpy,
             size: std::cell::Cell::new(size),
             win_size: std::cell::Cell::new(size),
+            fullscreen: std::cell::Cell::new(builder.fullscreen),
+            fullscreen_mode: std::cell::Cell::new(fullscreen_mode),
         })
     }
 
@@ -211,13 +277,63 @@ impl SimpleWindow {
         self.win_size.get()
     }
 
+    pub fn set_title(&self, title: &str) {
+        match platform_utils::set_title(self.dpy, self.window, title) {
+            Ok(()) => unsafe {
+                XFlush(self.dpy);
+            },
+            Err(e) => log::warn!("{}", e),
+        }
+    }
+
+    pub fn fullscreen(&self) -> FullscreenMode {
+        self.fullscreen.get()
+    }
+
+    /// Asks the window manager to change the fullscreen state, the new size arrives with
+    /// a configure event. Leaving fullscreen restores the previous window geometry.
+    pub fn set_fullscreen(&self, mode: FullscreenMode) {
+        let current = self.fullscreen.get();
+        if mode == current {
+            return;
+        }
+
+        if mode.is_fullscreen() != current.is_fullscreen() {
+            platform_utils::send_fullscreen_state(self.dpy, self.window, mode.is_fullscreen());
+        }
+        platform_utils::set_bypass_compositor(self.dpy, self.window, mode);
+        unsafe {
+            XFlush(self.dpy);
+        }
+
+        log::info!("Fullscreen {:?} -> {:?}", current, mode);
+        self.fullscreen.set(mode);
+        if mode.is_fullscreen() {
+            self.fullscreen_mode.set(mode);
+        }
+    }
+
+    /// Switches between windowed and the last fullscreen mode used.
+    pub fn toggle_fullscreen(&self) {
+        if self.fullscreen.get().is_fullscreen() {
+            self.set_fullscreen(FullscreenMode::Windowed);
+        } else {
+            self.set_fullscreen(self.fullscreen_mode.get());
+        }
+    }
+
     fn handle_client_message_event(&self, cme: &XClientMessageEvent) -> bool {
-        println!("{:?}", cme);
+        log::trace!("{:?}", cme);
         cme.data.as_longs()[0] == self.delete_atom as c_long
     }
 
+    /// A fullscreen window takes the focus back when it becomes visible again, a windowed
+    /// one leaves focus to the window manager.
     fn handle_visibility_event(&self, vse: &XVisibilityEvent) {
-        if vse.window == self.window && vse.state == VisibilityUnobscured {
+        if vse.window == self.window
+            && vse.state == VisibilityUnobscured
+            && self.fullscreen.get().is_fullscreen()
+        {
             unsafe {
                 XSetInputFocus(self.dpy, self.window, RevertToNone, CurrentTime);
             }
@@ -285,18 +401,18 @@ impl SimpleWindow {
         Event::Input(InputEventData::Key(ke))
     }
 
-    fn handle_mouse_button_event(&self, xbe: &XButtonEvent) -> Event {
-        let mapped_btn = match xbe.button {
-            Button1 => Some(MouseButtonId::Button1),
-            Button2 => Some(MouseButtonId::Button2),
-            Button3 => Some(MouseButtonId::Button3),
-            _ => None,
-        };
-
-        mapped_btn.map_or_else(
-            || {
+    /// Buttons 1 to 3 become button events, a wheel notch (a press and a release of
+    /// Button4 or Button5) becomes a single wheel event. Other buttons are ignored.
+    fn handle_mouse_button_event(&self, xbe: &XButtonEvent) -> Option<Event> {
+        let btn = match xbe.button {
+            Button1 => MouseButtonId::Button1,
+            Button2 => MouseButtonId::Button2,
+            Button3 => MouseButtonId::Button3,
+            Button4 | Button5 if xbe.type_ == ButtonPress => {
                 let mut mwe = MouseWheelEventData::default();
-                mwe.delta = if xbe.button == Button4 { -1 } else { 1 };
+                //
+                // Button4 is wheel up, positive like WM_MOUSEWHEEL on Windows
+                mwe.delta = if xbe.button == Button4 { 1 } else { -1 };
                 mwe.pointer_x = xbe.x;
                 mwe.pointer_y = xbe.y;
                 mwe.button1 = (xbe.state & Button1Mask) != 0;
@@ -307,30 +423,30 @@ impl SimpleWindow {
                 mwe.shift = (xbe.state & ShiftMask) != 0;
                 mwe.control = (xbe.state & ControlMask) != 0;
 
-                Event::Input(InputEventData::MouseWheel(mwe))
-            },
-            |btn| {
-                let mut mbtn_evt = MouseButtonEventData::default();
-                mbtn_evt.pointer_x = xbe.x;
-                mbtn_evt.pointer_y = xbe.y;
-                mbtn_evt.type_ = if xbe.type_ == ButtonPress {
-                    ActionType::Press
-                } else {
-                    ActionType::Release
-                };
-
-                mbtn_evt.button = btn;
-                mbtn_evt.button1 = (xbe.state & Button1Mask) != 0;
-                mbtn_evt.button2 = (xbe.state & Button2Mask) != 0;
-                mbtn_evt.button3 = (xbe.state & Button3Mask) != 0;
-                mbtn_evt.button4 = (xbe.state & Button4Mask) != 0;
-                mbtn_evt.button5 = (xbe.state & Button5Mask) != 0;
-                mbtn_evt.shift = (xbe.state & ShiftMask) != 0;
-                mbtn_evt.control = (xbe.state & ControlMask) != 0;
-
-                Event::Input(InputEventData::MouseButton(mbtn_evt))
-            },
-        )
+                return Some(Event::Input(InputEventData::MouseWheel(mwe)));
+            }
+            _ => return None,
+        };
+
+        let mut mbtn_evt = MouseButtonEventData::default();
+        mbtn_evt.pointer_x = xbe.x;
+        mbtn_evt.pointer_y = xbe.y;
+        mbtn_evt.type_ = if xbe.type_ == ButtonPress {
+            ActionType::Press
+        } else {
+            ActionType::Release
+        };
+
+        mbtn_evt.button = btn;
+        mbtn_evt.button1 = (xbe.state & Button1Mask) != 0;
+        mbtn_evt.button2 = (xbe.state & Button2Mask) != 0;
+        mbtn_evt.button3 = (xbe.state & Button3Mask) != 0;
+        mbtn_evt.button4 = (xbe.state & Button4Mask) != 0;
+        mbtn_evt.button5 = (xbe.state & Button5Mask) != 0;
+        mbtn_evt.shift = (xbe.state & ShiftMask) != 0;
+        mbtn_evt.control = (xbe.state & ControlMask) != 0;
+
+        Some(Event::Input(InputEventData::MouseButton(mbtn_evt)))
     }
 
     fn handle_mouse_motion_event(&self, x11evt: &XMotionEvent) -> Event {
@@ -389,7 +505,7 @@ impl SimpleWindow {
                         KeyPress | KeyRelease => Some(self.handle_key_event(&mut window_event.key)),
 
                         ButtonPress | ButtonRelease => {
-                            Some(self.handle_mouse_button_event(&window_event.button))
+                            self.handle_mouse_button_event(&window_event.button)
                         }
 
                         MotionNotify => Some(self.handle_mouse_motion_event(&window_event.motion)),
@@ -428,6 +544,14 @@ impl SimpleWindow {
             unsafe {
                 glXSwapBuffers(self.dpy, self.window);
             }
+
+            if QUIT_REQUESTED.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                break 'main_loop;
+            }
+
+            if FULLSCREEN_TOGGLE_REQUESTED.swap(false, std::sync::atomic::Ordering::SeqCst) {
+                self.toggle_fullscreen();
+            }
         }
     }
 }
@@ -489,38 +613,48 @@ mod platform_utils {
         (width as i32, height as i32)
     }
 
-    pub fn get_primary_screen_info(dpy: *mut Display) -> Result<XineramaScreenInfo, String> {
+    /// The Xinerama screens, the one matching the default X screen is the primary monitor.
+    pub fn get_monitors(dpy: *mut Display) -> Result<Vec<Monitor>, WindowError> {
         let mut num_screens: c_int = 0;
         let screens = ScopedXineramaScreenInfo::new(unsafe {
             XineramaQueryScreens(dpy, &mut num_screens as *mut c_int)
         })
-        .ok_or("Failed to query xinerama screens!".to_string())?;
+        .ok_or_else(|| WindowError::Display("Failed to query xinerama screens!".to_string()))?;
 
         if screens.is_null() || num_screens == 0 {
-            return Err("Failed to get screen information".to_string());
+            return Err(WindowError::Display(
+                "Failed to get screen information".to_string(),
+            ));
         }
 
         let root_screen = unsafe { XDefaultScreen(dpy) };
-        (0..num_screens)
-            .find_map(|idx| unsafe {
-                let curr_screen = screens.offset(idx as isize);
-                if (*curr_screen).screen_number == root_screen {
-                    Some(*curr_screen)
-                } else {
-                    None
+        Ok((0..num_screens)
+            .map(|idx| unsafe {
+                let screen = *screens.offset(idx as isize);
+                log::debug!("Xinerama screen {:?}", screen);
+                Monitor {
+                    rect: MonitorRect {
+                        x: screen.x_org as i32,
+                        y: screen.y_org as i32,
+                        width: screen.width as i32,
+                        height: screen.height as i32,
+                    },
+                    primary: screen.screen_number == root_screen,
                 }
             })
-            .ok_or("Failed to get root screen info!".to_string())
+            .collect())
     }
 
     pub fn get_suitable_xvisual(
         dpy: *mut Display,
-    ) -> Result<(ScopedXVisualInfo, GLXFBConfig), String> {
+    ) -> Result<(ScopedXVisualInfo, GLXFBConfig), WindowError> {
         let glx_extension_present =
             unsafe { glXQueryExtension(dpy, null_mut(), null_mut()) != False };
 
         if !glx_extension_present {
-            return Err("GLX extension is not present!".to_string());
+            return Err(WindowError::Context(
+                "GLX extension is not present!".to_string(),
+            ));
         }
 
         let mut glx_major: c_int = 0;
@@ -536,7 +670,7 @@ mod platform_utils {
 
         if glx_major < 1 || glx_minor != 4 {
             let s = format!("Wrong glx version : {} {}, need 1.4", glx_major, glx_minor);
-            return Err(s);
+            return Err(WindowError::Context(s));
         }
 
         let framebuffer_attributes = [
@@ -568,7 +702,9 @@ mod platform_utils {
                 &mut supported_cfgs_count as *mut i32,
             )
         })
-        .ok_or("Failed to get valid FB configurations".to_string())?;
+        .ok_or_else(|| {
+            WindowError::PixelFormat("Failed to get valid FB configurations".to_string())
+        })?;
 
         (0..supported_cfgs_count)
             .find_map(|idx| {
@@ -576,17 +712,20 @@ mod platform_utils {
                 ScopedXVisualInfo::new(unsafe { glXGetVisualFromFBConfig(dpy, cfg) })
                     .map(|vi| (vi, cfg))
             })
-            .ok_or("Failed to get XVisual!".to_string())
+            .ok_or_else(|| WindowError::PixelFormat("Failed to get XVisual!".to_string()))
     }
 
     pub fn create_opengl_context(
         dpy: *mut Display,
         cfg: &GLXFBConfig,
         scr: c_int,
-    ) -> Result<GLXContext, String> {
+        debug_context: bool,
+    ) -> Result<GLXContext, WindowError> {
         let extensions_list = unsafe { glXQueryExtensionsString(dpy, scr) };
         if extensions_list.is_null() {
-            return Err("Failed to get extensions list".to_string());
+            return Err(WindowError::Context(
+                "Failed to get extensions list".to_string(),
+            ));
         }
 
         // println!(
@@ -607,7 +746,8 @@ mod platform_utils {
                 } else {
                     Ok(transmute::<_, PFNGLXCREATECONTEXTATTRIBSARBPROC>(func_addr))
                 }
-            })?;
+            })
+            .map_err(WindowError::Context)?;
 
         let opengl_context_attribs = [
             GLX_CONTEXT_MAJOR_VERSION_ARB,
@@ -615,7 +755,11 @@ mod platform_utils {
             GLX_CONTEXT_MINOR_VERSION_ARB,
             5,
             GLX_CONTEXT_FLAGS_ARB,
-            GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB | GLX_CONTEXT_DEBUG_BIT_ARB,
+            if debug_context {
+                GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB | GLX_CONTEXT_DEBUG_BIT_ARB
+            } else {
+                GLX_CONTEXT_FORWARD_COMPATIBLE_BIT_ARB
+            },
             GLX_CONTEXT_PROFILE_MASK_ARB,
             GLX_CONTEXT_CORE_PROFILE_BIT_ARB,
             GLX_RENDER_TYPE,
@@ -628,29 +772,43 @@ mod platform_utils {
         };
 
         if context.is_null() {
-            Err("Failed to create OpenGL context!".to_string())
+            Err(WindowError::Context(
+                "Failed to create OpenGL context!".to_string(),
+            ))
         } else {
             Ok(context)
         }
     }
 
+    /// Position and size of the window in windowed mode, fixed if it is not resizable.
     pub fn setup_size_hints(
         dpy: *mut Display,
         win: Window,
-        primary_screen: &XineramaScreenInfo,
-    ) -> Result<(), String> {
+        rect: &MonitorRect,
+        resizable: bool,
+    ) -> Result<(), WindowError> {
         unsafe {
             let mut size_hints = ScopedXSizeHints::new(XAllocSizeHints())
-                .ok_or("Failed to allocate size hints!".to_string())?;
-
-            (**size_hints).flags = PMinSize | PBaseSize;
-            (**size_hints).min_width = 1024;
-            (**size_hints).max_width = 1024;
-            (**size_hints).base_width = primary_screen.width as i32;
-            (**size_hints).base_height = primary_screen.height as i32;
+                .ok_or_else(|| WindowError::Window("Failed to allocate size hints!".to_string()))?;
+
+            (**size_hints).flags = USPosition | USSize | PBaseSize;
+            (**size_hints).x = rect.x;
+            (**size_hints).y = rect.y;
+            (**size_hints).width = rect.width;
+            (**size_hints).height = rect.height;
+            (**size_hints).base_width = rect.width;
+            (**size_hints).base_height = rect.height;
+
+            if !resizable {
+                (**size_hints).flags |= PMinSize | PMaxSize;
+                (**size_hints).min_width = rect.width;
+                (**size_hints).min_height = rect.height;
+                (**size_hints).max_width = rect.width;
+                (**size_hints).max_height = rect.height;
+            }
 
             let mut wm_hints = ScopedXWMHints::new(XAllocWMHints())
-                .ok_or("Failed to allocate WM hints!".to_string())?;
+                .ok_or_else(|| WindowError::Window("Failed to allocate WM hints!".to_string()))?;
             (**wm_hints).flags = StateHint | InputHint;
             (**wm_hints).initial_state = 0;
             (**wm_hints).input = True;
@@ -671,48 +829,105 @@ mod platform_utils {
         Ok(())
     }
 
-    pub fn setup_window(dpy: *mut Display, window: Window, screen: &XineramaScreenInfo) -> Atom {
+    fn intern_atom(dpy: *mut Display, name: &[u8]) -> Atom {
         unsafe {
-            XClearWindow(dpy, window);
-            XMapRaised(dpy, window);
-            XMoveWindow(dpy, window, screen.x_org as i32, screen.y_org as i32);
-
-            let mut window_delete_atom = XInternAtom(
+            XInternAtom(
                 dpy,
-                CStr::from_bytes_with_nul(b"WM_DELETE_ATOM\0")
-                    .unwrap_or_default()
-                    .as_ptr(),
+                CStr::from_bytes_with_nul(name).unwrap_or_default().as_ptr(),
                 False,
+            )
+        }
+    }
+
+    /// Sets WM_NAME and the UTF-8 _NET_WM_NAME used by EWMH window managers.
+    pub fn set_title(dpy: *mut Display, window: Window, title: &str) -> Result<(), WindowError> {
+        let c_title = CString::new(title)
+            .map_err(|_| WindowError::Window(format!("Invalid window title '{}'", title)))?;
+
+        unsafe {
+            XStoreName(dpy, window, c_title.as_ptr());
+            XChangeProperty(
+                dpy,
+                window,
+                intern_atom(dpy, b"_NET_WM_NAME\0"),
+                intern_atom(dpy, b"UTF8_STRING\0"),
+                8,
+                PropModeReplace,
+                title.as_ptr(),
+                title.len() as c_int,
             );
+        }
 
-            XSetWMProtocols(dpy, window, &mut window_delete_atom as *mut c_ulong, 1);
+        Ok(())
+    }
 
-            let _NET_WM_STATE = XInternAtom(
+    /// Asks for no title bar and borders through the Motif hints, which most window
+    /// managers still honour.
+    pub fn disable_decorations(dpy: *mut Display, window: Window) {
+        const MWM_HINTS_DECORATIONS: c_long = 1 << 1;
+        //
+        // flags, functions, decorations, input mode, status
+        let hints: [c_long; 5] = [MWM_HINTS_DECORATIONS, 0, 0, 0, 0];
+        let motif_hints = intern_atom(dpy, b"_MOTIF_WM_HINTS\0");
+
+        unsafe {
+            XChangeProperty(
                 dpy,
-                CStr::from_bytes_with_nul(b"_NET_WM_STATE\0")
-                    .unwrap_or_default()
-                    .as_ptr(),
-                False,
+                window,
+                motif_hints,
+                motif_hints,
+                32,
+                PropModeReplace,
+                hints.as_ptr() as *const c_uchar,
+                hints.len() as c_int,
             );
+        }
+    }
 
-            let _NET_WM_STATE_FULLSCREEN = XInternAtom(
+    /// Starts the window fullscreen, must be called before the window is mapped.
+    pub fn set_initial_fullscreen(dpy: *mut Display, window: Window) {
+        let fullscreen = intern_atom(dpy, b"_NET_WM_STATE_FULLSCREEN\0");
+
+        unsafe {
+            XChangeProperty(
                 dpy,
-                CStr::from_bytes_with_nul(b"_NET_WM_STATE_FULLSCREEN\0")
-                    .unwrap()
-                    .as_ptr(),
-                False,
+                window,
+                intern_atom(dpy, b"_NET_WM_STATE\0"),
+                XA_ATOM,
+                32,
+                PropModeReplace,
+                &fullscreen as *const Atom as *const c_uchar,
+                1,
             );
+        }
+    }
+
+    /// Adds or removes _NET_WM_STATE_FULLSCREEN on a mapped window.
+    pub fn send_fullscreen_state(dpy: *mut Display, window: Window, fullscreen: bool) {
+        const _NET_WM_STATE_REMOVE: c_long = 0;
+        const _NET_WM_STATE_ADD: c_long = 1;
 
+        unsafe {
             let mut e = MaybeUninit::<XEvent>::zeroed().assume_init();
             e.type_ = ClientMessage;
             e.client_message.window = window;
-            e.client_message.message_type = _NET_WM_STATE;
+            e.client_message.message_type = intern_atom(dpy, b"_NET_WM_STATE\0");
             e.client_message.format = 32;
-            e.client_message.data.set_long(0, 1);
+            e.client_message.data.set_long(
+                0,
+                if fullscreen {
+                    _NET_WM_STATE_ADD
+                } else {
+                    _NET_WM_STATE_REMOVE
+                },
+            );
             e.client_message
                 .data
-                .set_long(1, _NET_WM_STATE_FULLSCREEN as c_long);
+                .set_long(1, intern_atom(dpy, b"_NET_WM_STATE_FULLSCREEN\0") as c_long);
             e.client_message.data.set_long(2, 0);
+            //
+            // source indication: a normal application
+            e.client_message.data.set_long(3, 1);
 
             XSendEvent(
                 dpy,
@@ -721,6 +936,41 @@ mod platform_utils {
                 SubstructureNotifyMask | SubstructureRedirectMask,
                 &mut e,
             );
+        }
+    }
+
+    /// Exclusive fullscreen asks the compositor to stop redirecting the window, the
+    /// closest X11 gets to exclusive ownership of the monitor without a mode switch.
+    pub fn set_bypass_compositor(dpy: *mut Display, window: Window, mode: FullscreenMode) {
+        let bypass_compositor = intern_atom(dpy, b"_NET_WM_BYPASS_COMPOSITOR\0");
+
+        unsafe {
+            if mode == FullscreenMode::Exclusive {
+                let bypass: c_long = 1;
+                XChangeProperty(
+                    dpy,
+                    window,
+                    bypass_compositor,
+                    XA_CARDINAL,
+                    32,
+                    PropModeReplace,
+                    &bypass as *const c_long as *const c_uchar,
+                    1,
+                );
+            } else {
+                XDeleteProperty(dpy, window, bypass_compositor);
+            }
+        }
+    }
+
+    pub fn setup_window(dpy: *mut Display, window: Window, rect: &MonitorRect) -> Atom {
+        unsafe {
+            XClearWindow(dpy, window);
+            XMapRaised(dpy, window);
+            XMoveWindow(dpy, window, rect.x, rect.y);
+
+            let mut window_delete_atom = intern_atom(dpy, b"WM_DELETE_WINDOW\0");
+            XSetWMProtocols(dpy, window, &mut window_delete_atom as *mut c_ulong, 1);
 
             XFlush(dpy);
             window_delete_atom